@@ -36,14 +36,24 @@ elif [[ $num_trials < 1 ]]; then
 	exit 1
 fi
 
+# Optionally extract the join columns as left:right pairs
+# Example: 5:5,6:6 (defaults to 5:5)
+join_columns=${4:-5:5}
+
 # Choose block sizes
 block_sizes=( 50 500 5000 )
 
-# Get left table
-left_table="$(ls $exp_name/*.csv | tail -1)"
+# Choose bits per pass for the radix join, from one to three passes
+radix_bits=( 10 5,5 4,3,3 )
+
+# Choose memory budgets in bytes for the grace hash join
+memory_budgets=( 65536 1048576 16777216 )
+
+# Get left table in any of the table formats
+left_table="$(ls $exp_name/*.csv $exp_name/*.bin $exp_name/*.rec $exp_name/*.arrow $exp_name/*.parquet 2>/dev/null | tail -1)"
 
-# Get right tables, separated by ,
-right_tables="$(ls $exp_name/rights/*.csv | xargs | sed -e 's/ /;/g')"
+# Get right tables, separated by ;
+right_tables="$(ls $exp_name/rights/*.csv $exp_name/rights/*.bin $exp_name/rights/*.rec $exp_name/rights/*.arrow $exp_name/rights/*.parquet 2>/dev/null | xargs | sed -e 's/ /;/g')"
 
 # Make output directory
 base_exp_name="$(basename $exp_name)"
@@ -64,11 +74,21 @@ for algo in ${algos[@]}; do
 		# For block nested loops, run all block size combinations
 		for lbs in ${block_sizes[@]}; do
 			for rbs in ${block_sizes[@]}; do
-				cargo run --release $left_table $right_tables $outfile $lbs $rbs $algo $num_trials
+				cargo run --release $left_table $right_tables $outfile $lbs $rbs $algo $num_trials $join_columns
 			done
 		done
+	elif [[ $algo == "radix" ]]; then
+		# For radix join, run every number of bits and passes
+		for bits in ${radix_bits[@]}; do
+			cargo run --release $left_table $right_tables $outfile 1 1 $algo $num_trials $join_columns --radix-bits=$bits
+		done
+	elif [[ $algo == "grace" ]]; then
+		# For grace hash join, run every memory budget
+		for budget in ${memory_budgets[@]}; do
+			cargo run --release $left_table $right_tables $outfile 1 1 $algo $num_trials $join_columns --memory-budget=$budget
+		done
 	else
 		# For all non-BNL joins, run with some irrelevant number of blocks
-		cargo run --release $left_table $right_tables $outfile 1 1 $algo $num_trials
+		cargo run --release $left_table $right_tables $outfile 1 1 $algo $num_trials $join_columns
 	fi
 done
\ No newline at end of file
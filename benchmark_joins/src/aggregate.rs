@@ -0,0 +1,463 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use rayon::iter::ParallelIterator;
+use rayon::slice::ParallelSlice;
+use serde::{Deserialize, Serialize};
+
+use crate::join::{JoinKind, JoinOutput};
+use crate::key::JoinKeys;
+use crate::materialize::Projection;
+use crate::parjoin::CHUNK_SIZE;
+use crate::record::{Record, M};
+use crate::schema::{Column, ColumnType, Schema, Value};
+
+// Function an aggregate computes over the rows of a group. As in
+// SQL, NULL values are skipped, so a group whose values are all
+// NULL sums to NULL, and COUNT of no column counts the rows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+  Count,
+  Sum,
+  Min,
+  Max,
+}
+
+impl fmt::Display for AggregateFunction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      AggregateFunction::Count => "count",
+      AggregateFunction::Sum => "sum",
+      AggregateFunction::Min => "min",
+      AggregateFunction::Max => "max",
+    })
+  }
+}
+
+impl FromStr for AggregateFunction {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "count" => Ok(AggregateFunction::Count),
+      "sum" => Ok(AggregateFunction::Sum),
+      "min" => Ok(AggregateFunction::Min),
+      "max" => Ok(AggregateFunction::Max),
+      _ => Err(format!("Unknown aggregate {:?}, expected count, sum, min or max", s)),
+    }
+  }
+}
+
+// An aggregate of the join output grouped by the join key, i.e.
+// SELECT key, FUNCTION(column) ... GROUP BY key. The column indexes
+// the join output schema, left columns then right columns
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Aggregate {
+  pub function: AggregateFunction,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub column: Option<usize>,
+}
+
+impl Aggregate {
+  // COUNT of the rows of each join key
+  pub fn count() -> Self {
+    Self {
+      function: AggregateFunction::Count,
+      column: None,
+    }
+  }
+
+  pub fn new(function: AggregateFunction, column: usize) -> Self {
+    Self {
+      function,
+      column: Some(column),
+    }
+  }
+
+  // Schema of the aggregated records of a join,
+  // the left key columns followed by the aggregate
+  pub fn schema(&self, left: &Schema, right: &Schema, keys: &JoinKeys, kind: JoinKind) -> Schema {
+    let output = Projection::default().schema(left, right, kind);
+    left.project(keys.left_columns()).merge(&Schema::new(vec![self.output_column(&output)]))
+  }
+
+  // Column of the aggregate of rows of the input schema, named after
+  // the aggregated column, e.g. sum_price, or count for COUNT of rows
+  pub fn output_column(&self, input: &Schema) -> Column {
+    let value_type = self.value_type(input);
+    let name = match self.column {
+      None => self.function.to_string(),
+      Some(c) => format!("{}_{}", self.function, input.columns()[c].name),
+    };
+    Column::new(&name, value_type)
+  }
+
+  // Type of the aggregate's values. Counts are Int64s, as are sums
+  // of integers, so that they do not overflow as easily
+  fn value_type(&self, output: &Schema) -> ColumnType {
+    let column = match (self.function, self.column) {
+      (AggregateFunction::Count, _) => return ColumnType::Int64,
+      (function, None) => panic!("Aggregate {} needs a column", function),
+      (_, Some(column)) => column,
+    };
+    match (self.function, output.column_type(column)) {
+      (function, ColumnType::Utf8) => panic!("Cannot {} utf8 column {:?}", function, column),
+      (AggregateFunction::Sum, ColumnType::Int32) => ColumnType::Int64,
+      (_, column_type) => column_type,
+    }
+  }
+}
+
+// A function, then the column unless counting rows, e.g. sum:3 or count
+impl FromStr for Aggregate {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (function, column) = match s.split_once(':') {
+      None => (s.parse::<AggregateFunction>()?, None),
+      Some((function, column)) => (
+        function.parse::<AggregateFunction>()?,
+        Some(column.trim().parse::<usize>().map_err(|e| format!("{:?} is not a column index, {}", column, e))?)),
+    };
+    if column.is_none() && function != AggregateFunction::Count {
+      return Err(format!("Aggregate {} needs a column, e.g. {}:0", function, function));
+    }
+    Ok(Aggregate { function, column })
+  }
+}
+
+// Slots of a group's join key, compared and hashed by value.
+// Groups are ordered by the values of their keys, see sorted_groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GroupKey {
+  nulls: u32,
+  len: usize,
+  slots: [i32; M],
+}
+
+impl GroupKey {
+  // Key of the given slots of a record
+  fn new(record: &Record, key_slots: &[usize]) -> Self {
+    let mut key = GroupKey { nulls: 0, len: key_slots.len(), slots: [0; M] };
+    for (i, &slot) in key_slots.iter().enumerate() {
+      match record.get_nullable_column(slot) {
+        None => key.nulls |= 1 << i,
+        Some(v) => key.slots[i] = *v,
+      }
+    }
+    key
+  }
+
+  // Record of width slots which starts with the key's
+  fn record(&self, width: usize) -> Record {
+    let mut fields: Vec<i32> = vec![0; width];
+    fields[..self.len].copy_from_slice(&self.slots[..self.len]);
+    let mut record = Record::new(&fields);
+    for i in (0..self.len).filter(|i| self.nulls & (1 << i) != 0) {
+      record.set_null(i);
+    }
+    record
+  }
+}
+
+// Only the key's slots are hashed, the rest being zero
+impl Hash for GroupKey {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.nulls.hash(state);
+    self.slots[..self.len].hash(state);
+  }
+}
+
+// Records of the groups, starting with their keys, in key order. Keys
+// are compared by value, so that Utf8 keys sort by their strings and
+// not by intern id, and Int64 keys not slot by slot. NULLs sort last
+fn sorted_groups<T>(groups: HashMap<GroupKey, T>, schema: &Schema, num_keys: usize) -> Vec<(Record, T)> {
+  let mut groups: Vec<(Vec<Value>, Record, T)> = groups.into_iter()
+    .map(|(key, group)| {
+      let record = key.record(schema.num_slots());
+      let values: Vec<Value> = (0..num_keys).map(|c| record.get_value(schema, c)).collect();
+      (values, record, group)
+    })
+    .collect();
+  groups.sort_unstable_by(|a, b| {
+    a.0.iter().zip(&b.0).map(|(a, b)| compare_values(a, b)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+  });
+  groups.into_iter().map(|(_, record, group)| (record, group)).collect()
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+  match (a, b) {
+    (Value::Null, Value::Null) => Ordering::Equal,
+    (Value::Null, _) => Ordering::Greater,
+    (_, Value::Null) => Ordering::Less,
+    (Value::Int32(a), Value::Int32(b)) => a.cmp(b),
+    (Value::Int64(a), Value::Int64(b)) => a.cmp(b),
+    (Value::Float64(a), Value::Float64(b)) => a.total_cmp(b),
+    (Value::Utf8(a), Value::Utf8(b)) => a.cmp(b),
+    (a, b) => panic!("Cannot compare keys {:?} and {:?}", a, b),
+  }
+}
+
+// Running value of an aggregate. Integers are summed as i128s, which
+// cannot overflow, and compared as i64s, floats as f64s, and None
+// stands for no value yet
+#[derive(Debug, Clone, Copy)]
+enum Accumulator {
+  Count(i64),
+  Int(Option<i128>),
+  Float(Option<f64>),
+}
+
+impl Accumulator {
+  fn new(function: AggregateFunction, value_type: ColumnType) -> Self {
+    match (function, value_type) {
+      (AggregateFunction::Count, _) => Accumulator::Count(0),
+      (_, ColumnType::Float64) => Accumulator::Float(None),
+      _ => Accumulator::Int(None),
+    }
+  }
+
+  // Adds a value which is not NULL, or a row when counting rows
+  fn add(&mut self, function: AggregateFunction, value: Option<&Value>) {
+    if let Accumulator::Count(count) = self {
+      *count += 1;
+      return;
+    }
+    let value = match value {
+      Some(Value::Int32(v)) => Accumulator::Int(Some(*v as i128)),
+      Some(Value::Int64(v)) => Accumulator::Int(Some(*v as i128)),
+      Some(Value::Float64(v)) => Accumulator::Float(Some(*v)),
+      value => panic!("Cannot {} {:?}", function, value),
+    };
+    self.merge(function, value);
+  }
+
+  // Combines the values of two parts of a group
+  fn merge(&mut self, function: AggregateFunction, other: Accumulator) {
+    let combine_int = |a: i128, b: i128| match function {
+      AggregateFunction::Min => a.min(b),
+      AggregateFunction::Max => a.max(b),
+      _ => a + b,
+    };
+    let combine_float = |a: f64, b: f64| match function {
+      AggregateFunction::Min => a.min(b),
+      AggregateFunction::Max => a.max(b),
+      _ => a + b,
+    };
+    *self = match (*self, other) {
+      (Accumulator::Count(a), Accumulator::Count(b)) => Accumulator::Count(a + b),
+      (Accumulator::Int(a), Accumulator::Int(b)) => Accumulator::Int(match (a, b) {
+        (Some(a), Some(b)) => Some(combine_int(a, b)),
+        (a, b) => a.or(b),
+      }),
+      (Accumulator::Float(a), Accumulator::Float(b)) => Accumulator::Float(match (a, b) {
+        (Some(a), Some(b)) => Some(combine_float(a, b)),
+        (a, b) => a.or(b),
+      }),
+      (a, b) => panic!("Cannot merge aggregates {:?} and {:?}", a, b),
+    };
+  }
+
+  fn value(&self, value_type: ColumnType) -> Value {
+    match (*self, value_type) {
+      (Accumulator::Count(count), _) => Value::Int64(count),
+      (Accumulator::Int(Some(v)), ColumnType::Int32) => Value::Int32(v as i32),
+      (Accumulator::Int(Some(v)), _) => match i64::try_from(v) {
+        Err(_) => panic!("The sum {} does not fit an Int64", v),
+        Ok(v) => Value::Int64(v),
+      },
+      (Accumulator::Float(Some(v)), _) => Value::Float64(v),
+      _ => Value::Null,
+    }
+  }
+}
+
+// Which side of the join an aggregated column comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+  Left,
+  Right,
+}
+
+// Output reduced to an aggregate per join key as the operator emits
+// rows, so no record is ever merged. Matched pairs and padded left
+// rows group by the left key, padded right rows by the right key,
+// and NULL keys of unmatched rows form a group of their own
+pub struct AggregateOutput {
+  function: AggregateFunction,
+  // Column of one side's schema, None when counting rows
+  column: Option<(Side, usize)>,
+  value_type: ColumnType,
+  left_schema: Schema,
+  right_schema: Schema,
+  keys: JoinKeys,
+  schema: Schema,
+  groups: HashMap<GroupKey, Accumulator>,
+}
+
+impl AggregateOutput {
+  // Output of a join of tables of the given schemas on the
+  // given keys, which are resolved to slots as the operators do
+  pub fn new(aggregate: &Aggregate, keys: &JoinKeys, left: &Schema, right: &Schema, kind: JoinKind) -> Self {
+    let schema = aggregate.schema(left, right, keys, kind);
+    let column = aggregate.column.map(|c| match c < left.num_columns() {
+      true => (Side::Left, c),
+      false => (Side::Right, c - left.num_columns()),
+    });
+    Self {
+      function: aggregate.function,
+      column,
+      value_type: schema.column_type(schema.num_columns() - 1),
+      left_schema: left.clone(),
+      right_schema: right.clone(),
+      keys: keys.resolve(left, right),
+      schema,
+      groups: HashMap::new(),
+    }
+  }
+
+  // Schema of the records into_records returns
+  pub fn schema(&self) -> &Schema {
+    &self.schema
+  }
+
+  // A record per group, holding its key then its aggregate, in key order
+  pub fn into_records(self) -> Vec<Record> {
+    let aggregate_column = self.schema.num_columns() - 1;
+    let (schema, value_type) = (&self.schema, self.value_type);
+    sorted_groups(self.groups, schema, aggregate_column).into_iter()
+      .map(|(mut record, accumulator)| {
+        record.set_value(schema, aggregate_column, &accumulator.value(value_type));
+        record
+      })
+      .collect()
+  }
+
+  // Adds a row to the group of the key of one of its records
+  fn add(&mut self, key_side: Side, key_record: &Record, left: Option<&Record>, right: Option<&Record>) {
+    let key = match key_side {
+      Side::Left => GroupKey::new(key_record, self.keys.left_columns()),
+      Side::Right => GroupKey::new(key_record, self.keys.right_columns()),
+    };
+
+    // The padded side of a row has only NULLs
+    let value = match self.column {
+      None => None,
+      Some((Side::Left, c)) => Some(left.map_or(Value::Null, |r| r.get_value(&self.left_schema, c))),
+      Some((Side::Right, c)) => Some(right.map_or(Value::Null, |r| r.get_value(&self.right_schema, c))),
+    };
+    let (function, value_type) = (self.function, self.value_type);
+    let accumulator = self.groups.entry(key).or_insert_with(|| Accumulator::new(function, value_type));
+    match value {
+      Some(Value::Null) => {},
+      value => accumulator.add(function, value.as_ref()),
+    }
+  }
+}
+
+impl JoinOutput for AggregateOutput {
+  fn pair(&mut self, _: u32, left: &Record, _: u32, right: &Record) {
+    self.add(Side::Left, left, Some(left), Some(right));
+  }
+
+  fn left_padded(&mut self, _: u32, left: &Record) {
+    self.add(Side::Left, left, Some(left), None);
+  }
+
+  fn right_padded(&mut self, _: u32, right: &Record) {
+    self.add(Side::Right, right, None, Some(right));
+  }
+
+  fn left_only(&mut self, _: u32, left: &Record) {
+    self.add(Side::Left, left, Some(left), None);
+  }
+
+  fn split(&self) -> Self {
+    Self {
+      function: self.function,
+      column: self.column,
+      value_type: self.value_type,
+      left_schema: self.left_schema.clone(),
+      right_schema: self.right_schema.clone(),
+      keys: self.keys.clone(),
+      schema: self.schema.clone(),
+      groups: HashMap::new(),
+    }
+  }
+
+  fn absorb(&mut self, other: Self) {
+    for (key, accumulator) in other.groups {
+      match self.groups.get_mut(&key) {
+        Some(group) => group.merge(self.function, accumulator),
+        None => {
+          self.groups.insert(key, accumulator);
+        },
+      }
+    }
+  }
+}
+
+// Aggregates of records of the schema per value of the group by
+// columns, i.e. SELECT group_by, aggregates ... GROUP BY group_by,
+// as records of the group by columns then the aggregates, in key
+// order. Workers aggregate chunks of the records, whose groups are
+// then merged. Without group by columns every record is in the one
+// group, which is there even when there are no records, as in SQL
+pub fn hash_aggregate(schema: &Schema, records: &[Record], group_by: &[usize], aggregates: &[Aggregate]) -> (Schema, Vec<Record>) {
+  let output = schema.project(group_by).merge(&Schema::new(aggregates.iter().map(|a| a.output_column(schema)).collect()));
+  if !output.fits_record() {
+    panic!("Aggregates take {:?} slots, but max support is {:?}", output.num_slots(), M);
+  }
+  let key_slots: Vec<usize> = group_by.iter().flat_map(|c| schema.slots(*c)).collect();
+  let value_types: Vec<ColumnType> = (group_by.len()..output.num_columns()).map(|c| output.column_type(c)).collect();
+  let new_group = || -> Vec<Accumulator> {
+    aggregates.iter().zip(&value_types).map(|(a, t)| Accumulator::new(a.function, *t)).collect()
+  };
+
+  let mut groups: HashMap<GroupKey, Vec<Accumulator>> = records.par_chunks(CHUNK_SIZE)
+    .map(|chunk| {
+      let mut groups: HashMap<GroupKey, Vec<Accumulator>> = HashMap::new();
+      for record in chunk {
+        let group = groups.entry(GroupKey::new(record, &key_slots)).or_insert_with(new_group);
+        for (accumulator, aggregate) in group.iter_mut().zip(aggregates) {
+          match aggregate.column.map(|c| record.get_value(schema, c)) {
+            Some(Value::Null) => {},
+            value => accumulator.add(aggregate.function, value.as_ref()),
+          }
+        }
+      }
+      groups
+    })
+    .reduce(HashMap::new, |mut groups, other| {
+      for (key, accumulators) in other {
+        match groups.get_mut(&key) {
+          Some(group) => {
+            for ((accumulator, other), aggregate) in group.iter_mut().zip(accumulators).zip(aggregates) {
+              accumulator.merge(aggregate.function, other);
+            }
+          },
+          None => {
+            groups.insert(key, accumulators);
+          },
+        }
+      }
+      groups
+    });
+  if group_by.is_empty() && groups.is_empty() {
+    groups.insert(GroupKey::new(&Record::new(&[]), &[]), new_group());
+  }
+
+  let records: Vec<Record> = sorted_groups(groups, &output, group_by.len()).into_iter()
+    .map(|(mut record, accumulators)| {
+      for (i, (accumulator, value_type)) in accumulators.iter().zip(&value_types).enumerate() {
+        record.set_value(&output, group_by.len() + i, &accumulator.value(*value_type));
+      }
+      record
+    })
+    .collect();
+  (output, records)
+}
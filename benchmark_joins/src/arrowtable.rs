@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Read};
+use std::sync::Arc;
+
+use arrow_array::{Array, ArrayRef, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow_cast::cast::{cast_with_options, CastOptions};
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema as ArrowSchema, SchemaRef};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::record::{Record, M};
+use crate::schema::{Column, ColumnType, Schema, Value};
+
+// Tables in Apache Arrow formats, for other tools to read: Arrow IPC
+// files (.arrow) and Parquet files (.parquet). Columns are written as
+// nullable Int32, Int64, Float64 or Utf8 columns, keeping the NULLs of
+// outer join output. Integer columns are read as Int32s, or Int64s if
+// they are wider than 32 bits, floats as Float64s and strings as Utf8
+pub const IPC_EXTENSION: &str = "arrow";
+pub const PARQUET_EXTENSION: &str = "parquet";
+
+const IPC_MAGIC: &[u8; 6] = b"ARROW1";
+const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+
+// Rows per record batch written
+const BATCH_ROWS: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrowFormat {
+  Ipc,
+  Parquet,
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+  std::path::Path::new(path).extension().is_some_and(|e| e == extension)
+}
+
+// Format of a path by its extension, .arrow or .parquet
+pub fn arrow_path_format(path: &str) -> Option<ArrowFormat> {
+  if has_extension(path, IPC_EXTENSION) {
+    Some(ArrowFormat::Ipc)
+  } else if has_extension(path, PARQUET_EXTENSION) {
+    Some(ArrowFormat::Parquet)
+  } else {
+    None
+  }
+}
+
+// Format of a file by its magic bytes, or None for other files
+pub fn arrow_format(path: &str) -> Option<ArrowFormat> {
+  let mut magic = [0u8; 6];
+  let mut f = File::open(path).ok()?;
+  f.read_exact(&mut magic).ok()?;
+  if &magic == IPC_MAGIC {
+    Some(ArrowFormat::Ipc)
+  } else if &magic[..4] == PARQUET_MAGIC {
+    Some(ArrowFormat::Parquet)
+  } else {
+    None
+  }
+}
+
+fn data_type(column_type: ColumnType) -> DataType {
+  match column_type {
+    ColumnType::Int32 => DataType::Int32,
+    ColumnType::Int64 => DataType::Int64,
+    ColumnType::Float64 => DataType::Float64,
+    ColumnType::Utf8 => DataType::Utf8,
+  }
+}
+
+fn arrow_schema(schema: &Schema) -> SchemaRef {
+  let fields: Vec<Field> = schema.columns().iter()
+    .map(|c| Field::new(&c.name, data_type(c.column_type), true))
+    .collect();
+  Arc::new(ArrowSchema::new(fields))
+}
+
+fn to_batch(arrow_schema: &SchemaRef, schema: &Schema, records: &[Record]) -> Result<RecordBatch, ArrowError> {
+  let columns: Vec<ArrayRef> = (0..schema.num_columns())
+    .map(|i| {
+      let values = records.iter().map(|r| r.get_value(schema, i));
+      match schema.column_type(i) {
+        ColumnType::Int32 => {
+          let slot = schema.slots(i).start;
+          Arc::new(records.iter().map(|r| r.get_nullable_column(slot).copied()).collect::<Int32Array>()) as ArrayRef
+        },
+        ColumnType::Int64 => Arc::new(values.map(|v| match v { Value::Int64(v) => Some(v), _ => None }).collect::<Int64Array>()),
+        ColumnType::Float64 => Arc::new(values.map(|v| match v { Value::Float64(v) => Some(v), _ => None }).collect::<Float64Array>()),
+        ColumnType::Utf8 => Arc::new(values.map(|v| match v { Value::Utf8(v) => Some(v), _ => None }).collect::<StringArray>()),
+      }
+    })
+    .collect();
+  RecordBatch::try_new(arrow_schema.clone(), columns)
+}
+
+// Type a column of the data type is read as
+fn column_type(data_type: &DataType) -> Option<ColumnType> {
+  match data_type {
+    DataType::Int64 | DataType::UInt32 | DataType::UInt64 => Some(ColumnType::Int64),
+    t if t.is_integer() => Some(ColumnType::Int32),
+    t if t.is_floating() => Some(ColumnType::Float64),
+    DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => Some(ColumnType::Utf8),
+    _ => None,
+  }
+}
+
+fn to_schema(arrow_schema: &ArrowSchema) -> Result<Schema, Box<dyn Error>> {
+  let columns: Vec<Column> = arrow_schema.fields().iter()
+    .map(|f| match column_type(f.data_type()) {
+      Some(t) => Ok(Column::new(f.name(), t)),
+      None => Err(format!("Column {:?} has type {}, expected integers, floats or strings", f.name(), f.data_type())),
+    })
+    .collect::<Result<_, _>>()?;
+  let types: Vec<ColumnType> = columns.iter().map(|c| c.column_type).collect();
+  if !Schema::fits(&types) {
+    return Err(format!("Table has {} columns, which take more than the {} slots of a record", columns.len(), M).into());
+  }
+  Ok(Schema::new(columns))
+}
+
+// Appends the rows of a batch to records, casting each column to
+// the type of its schema column and failing on overflow
+fn append_batch(schema: &Schema, batch: &RecordBatch, records: &mut Vec<Record>) -> Result<(), Box<dyn Error>> {
+  let options = CastOptions { safe: false, ..CastOptions::default() };
+  let mut columns: Vec<ArrayRef> = Vec::with_capacity(batch.num_columns());
+  for (c, column) in schema.columns().iter().zip(batch.columns()) {
+    let column = cast_with_options(column, &data_type(c.column_type), &options)
+      .map_err(|e| format!("Column {:?} does not fit in {} {}", c.name, c.column_type, e))?;
+    columns.push(column);
+  }
+
+  let empty = Record::new(&vec![0; schema.num_slots()]);
+  for row in 0..batch.num_rows() {
+    let mut record = empty.clone();
+    for (i, column) in columns.iter().enumerate() {
+      let any = column.as_any();
+      let value = if column.is_null(row) {
+        Value::Null
+      } else {
+        match schema.column_type(i) {
+          ColumnType::Int32 => Value::Int32(any.downcast_ref::<Int32Array>().unwrap().value(row)),
+          ColumnType::Int64 => Value::Int64(any.downcast_ref::<Int64Array>().unwrap().value(row)),
+          ColumnType::Float64 => Value::Float64(any.downcast_ref::<Float64Array>().unwrap().value(row)),
+          ColumnType::Utf8 => Value::Utf8(any.downcast_ref::<StringArray>().unwrap().value(row).into()),
+        }
+      };
+      record.set_value(schema, i, &value);
+    }
+    records.push(record);
+  }
+  Ok(())
+}
+
+pub fn write_ipc_table(path: &str, schema: &Schema, records: &[Record]) -> Result<(), Box<dyn Error>> {
+  let arrow_schema = arrow_schema(schema);
+  let mut writer = FileWriter::try_new(BufWriter::new(File::create(path)?), &arrow_schema)?;
+  for block in records.chunks(BATCH_ROWS) {
+    writer.write(&to_batch(&arrow_schema, schema, block)?)?;
+  }
+  writer.finish()?;
+  Ok(())
+}
+
+pub fn read_ipc_table(path: &str) -> Result<(Schema, Vec<Record>), Box<dyn Error>> {
+  let reader = FileReader::try_new_buffered(File::open(path)?, None)?;
+  let schema = to_schema(&reader.schema())?;
+  let mut records: Vec<Record> = Vec::new();
+  for batch in reader {
+    append_batch(&schema, &batch?, &mut records)?;
+  }
+  Ok((schema, records))
+}
+
+// Snappy compressed, like most Parquet writers default to
+pub fn write_parquet_table(path: &str, schema: &Schema, records: &[Record]) -> Result<(), Box<dyn Error>> {
+  let arrow_schema = arrow_schema(schema);
+  let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+  let mut writer = ArrowWriter::try_new(BufWriter::new(File::create(path)?), arrow_schema.clone(), Some(props))?;
+  for block in records.chunks(BATCH_ROWS) {
+    writer.write(&to_batch(&arrow_schema, schema, block)?)?;
+  }
+  writer.close()?;
+  Ok(())
+}
+
+pub fn read_parquet_table(path: &str) -> Result<(Schema, Vec<Record>), Box<dyn Error>> {
+  let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+  let schema = to_schema(builder.schema())?;
+  let mut records: Vec<Record> = Vec::with_capacity(builder.metadata().file_metadata().num_rows() as usize);
+  for batch in builder.with_batch_size(BATCH_ROWS).build()? {
+    append_batch(&schema, &batch?, &mut records)?;
+  }
+  Ok((schema, records))
+}
+
+// Reads a table in either format, telling them apart by magic bytes
+pub fn read_arrow_table(path: &str) -> Result<(Schema, Vec<Record>), Box<dyn Error>> {
+  match arrow_format(path) {
+    Some(ArrowFormat::Ipc) => read_ipc_table(path),
+    Some(ArrowFormat::Parquet) => read_parquet_table(path),
+    None => Err(format!("{:?} is not an Arrow IPC or Parquet file", path).into()),
+  }
+}
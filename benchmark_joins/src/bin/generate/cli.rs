@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Flags given as --name=value, checked against the names
+// a subcommand accepts. A bare --name is --name=true
+pub struct Flags {
+	values: HashMap<String, String>,
+}
+
+impl Flags {
+	pub fn parse(args: &[String], accepted: &[&str]) -> Result<Flags, String> {
+		let mut values = HashMap::new();
+		for arg in args {
+			let (name, value) = match arg.strip_prefix("--") {
+				None => return Err(format!("Expected --flag=value, got {:?}", arg)),
+				Some(a) => a.split_once('=').unwrap_or((a, "true")),
+			};
+			if !accepted.contains(&name) {
+				return Err(format!("Unrecognized flag --{}", name));
+			}
+			if values.insert(name.to_owned(), value.to_owned()).is_some() {
+				return Err(format!("Flag --{} given twice", name));
+			}
+		}
+		Ok(Flags { values })
+	}
+
+	pub fn get_str(&self, name: &str) -> Option<&str> {
+		self.values.get(name).map(|v| v.as_str())
+	}
+
+	// Value of an optional flag
+	pub fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+		match self.values.get(name) {
+			None => Ok(None),
+			Some(v) => match v.parse() {
+				Err(_) => Err(format!("Bad value {:?} for --{}", v, name)),
+				Ok(parsed) => Ok(Some(parsed)),
+			},
+		}
+	}
+
+	pub fn get_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+		Ok(self.get(name)?.unwrap_or(default))
+	}
+
+	pub fn require<T: FromStr>(&self, name: &str) -> Result<T, String> {
+		self.get(name)?.ok_or(format!("Missing flag --{}", name))
+	}
+}
+
+// Row counts like 10000, 10K, 100M or 1B, the
+// inverse of the names format_row_count makes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowCount(pub usize);
+
+impl FromStr for RowCount {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (digits, scale) = match s.chars().last() {
+			Some('K') => (&s[..s.len() - 1], 1_000),
+			Some('M') => (&s[..s.len() - 1], 1_000_000),
+			Some('B') => (&s[..s.len() - 1], 1_000_000_000),
+			_ => (s, 1),
+		};
+		match digits.parse::<usize>() {
+			Err(_) => Err(format!("Bad row count {:?}", s)),
+			Ok(0) => Err(format!("Row count {:?} must be positive", s)),
+			Ok(n) => Ok(RowCount(n * scale)),
+		}
+	}
+}
+
+// Lists like 2K,4K,6K
+pub fn parse_list<T: FromStr>(s: &str) -> Result<Vec<T>, String> {
+	s.split(',')
+		.map(|v| v.parse().map_err(|_| format!("Bad list entry {:?}", v)))
+		.collect()
+}
+
+// Join columns as left:right pairs separated by , e.g. 5:5,6:6
+pub fn parse_key_columns(s: &str) -> Result<Vec<(usize, usize)>, String> {
+	s.split(',')
+		.map(|pair| {
+			let (l, r) = pair.split_once(':').ok_or(format!("Expected left:right join columns, got {:?}", pair))?;
+			match (l.parse(), r.parse()) {
+				(Ok(l), Ok(r)) => Ok((l, r)),
+				_ => Err(format!("Bad join columns {:?}", pair)),
+			}
+		})
+		.collect()
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand_distr::{Distribution, Normal, Zipf};
+
+// How the values of a generated column are distributed
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyDistribution {
+	// Every value equally likely, drawn from all of i32
+	// or from a fixed number of distinct random values
+	Uniform { distinct: Option<usize> },
+	// The k-th most frequent of the distinct random values
+	// is drawn with probability proportional to 1 / k^exponent
+	Zipf { exponent: f64, distinct: usize },
+	// Normally distributed values, rounded to integers
+	Normal { mean: f64, std_dev: f64 },
+	// Row i gets value i, wrapping around after distinct
+	// values, so keys are dense and have no gaps
+	Sequential { distinct: Option<usize> },
+	// Values packed into runs of consecutive integers,
+	// each starting at a random value
+	Clustered { num_clusters: usize, cluster_width: u32 },
+}
+
+impl Default for KeyDistribution {
+	fn default() -> Self {
+		KeyDistribution::Uniform { distinct: None }
+	}
+}
+
+// Short name used in table file names,
+// e.g. zipf1.2-d1000 or clustered10-w100
+impl fmt::Display for KeyDistribution {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			KeyDistribution::Uniform { distinct: None } => write!(f, "uniform"),
+			KeyDistribution::Uniform { distinct: Some(d) } => write!(f, "uniform-d{}", d),
+			KeyDistribution::Zipf { exponent, distinct } => write!(f, "zipf{}-d{}", exponent, distinct),
+			KeyDistribution::Normal { mean, std_dev } => write!(f, "normal{}-sd{}", mean, std_dev),
+			KeyDistribution::Sequential { distinct: None } => write!(f, "seq"),
+			KeyDistribution::Sequential { distinct: Some(d) } => write!(f, "seq-d{}", d),
+			KeyDistribution::Clustered { num_clusters, cluster_width } => write!(f, "clustered{}-w{}", num_clusters, cluster_width),
+		}
+	}
+}
+
+// Parses the names written by Display, e.g. zipf1.2-d1000
+impl FromStr for KeyDistribution {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		fn number<T: FromStr>(s: &str, name: &str) -> Result<T, String> {
+			s.parse().map_err(|_| format!("Bad {} {:?}", name, s))
+		}
+		// Splits e.g. "1.2-d1000" at "-d" into ("1.2", "1000")
+		fn pair<'a>(s: &'a str, separator: &str) -> Result<(&'a str, &'a str), String> {
+			s.rsplit_once(separator).ok_or(format!("Expected {:?} in {:?}", separator, s))
+		}
+
+		if s == "uniform" {
+			Ok(KeyDistribution::Uniform { distinct: None })
+		} else if let Some(d) = s.strip_prefix("uniform-d") {
+			Ok(KeyDistribution::Uniform { distinct: Some(number(d, "distinct values")?) })
+		} else if let Some(rest) = s.strip_prefix("zipf") {
+			let (exponent, distinct) = pair(rest, "-d")?;
+			Ok(KeyDistribution::Zipf { exponent: number(exponent, "exponent")?, distinct: number(distinct, "distinct values")? })
+		} else if let Some(rest) = s.strip_prefix("normal") {
+			let (mean, std_dev) = pair(rest, "-sd")?;
+			Ok(KeyDistribution::Normal { mean: number(mean, "mean")?, std_dev: number(std_dev, "standard deviation")? })
+		} else if s == "seq" {
+			Ok(KeyDistribution::Sequential { distinct: None })
+		} else if let Some(d) = s.strip_prefix("seq-d") {
+			Ok(KeyDistribution::Sequential { distinct: Some(number(d, "distinct values")?) })
+		} else if let Some(rest) = s.strip_prefix("clustered") {
+			let (num_clusters, cluster_width) = pair(rest, "-w")?;
+			Ok(KeyDistribution::Clustered { num_clusters: number(num_clusters, "number of clusters")?, cluster_width: number(cluster_width, "cluster width")? })
+		} else {
+			Err(format!("Unrecognized distribution {:?}", s))
+		}
+	}
+}
+
+// Distinct random values, none of them i32::MIN
+fn distinct_random_values<R: Rng>(n: usize, rng: &mut R) -> Vec<i32> {
+	let mut seen: HashSet<i32> = HashSet::with_capacity(n);
+	let mut values: Vec<i32> = Vec::with_capacity(n);
+	while values.len() < n {
+		let v = rng.gen_range(i32::MIN+1..=i32::MAX);
+		if seen.insert(v) {
+			values.push(v);
+		}
+	}
+	values
+}
+
+// Draws the values of one column following a KeyDistribution
+pub struct ValueSampler {
+	distribution: KeyDistribution,
+	// The distinct values to draw from, if there is a fixed set
+	domain: Vec<i32>,
+	zipf: Option<Zipf<f64>>,
+	normal: Option<Normal<f64>>,
+	next_row: usize,
+}
+
+impl ValueSampler {
+	pub fn new<R: Rng>(distribution: &KeyDistribution, rng: &mut R) -> Result<ValueSampler, String> {
+		let mut sampler = ValueSampler {
+			distribution: distribution.clone(),
+			domain: Vec::new(),
+			zipf: None,
+			normal: None,
+			next_row: 0,
+		};
+
+		match *distribution {
+			KeyDistribution::Uniform { distinct: Some(0) }
+			| KeyDistribution::Zipf { distinct: 0, .. }
+			| KeyDistribution::Sequential { distinct: Some(0) } => {
+				return Err(format!("{} needs at least one distinct value", distribution));
+			},
+			KeyDistribution::Uniform { distinct: Some(d) } => {
+				sampler.domain = distinct_random_values(d, rng);
+			},
+			KeyDistribution::Zipf { exponent, distinct } => {
+				sampler.zipf = match Zipf::new(distinct as u64, exponent) {
+					Err(e) => return Err(format!("Bad Zipf distribution {}: {}", distribution, e)),
+					Ok(z) => Some(z),
+				};
+				sampler.domain = distinct_random_values(distinct, rng);
+			},
+			KeyDistribution::Normal { mean, std_dev } => {
+				sampler.normal = match Normal::new(mean, std_dev) {
+					Err(e) => return Err(format!("Bad normal distribution {}: {}", distribution, e)),
+					Ok(n) => Some(n),
+				};
+			},
+			KeyDistribution::Clustered { num_clusters, cluster_width } => {
+				if num_clusters == 0 || cluster_width == 0 {
+					return Err(format!("{} needs non-empty clusters", distribution));
+				}
+				if cluster_width > i32::MAX as u32 {
+					return Err(format!("{} has clusters wider than i32::MAX", distribution));
+				}
+				// Cluster starts leave room for the whole cluster
+				sampler.domain = (0..num_clusters)
+					.map(|_| rng.gen_range(i32::MIN+1..=i32::MAX - cluster_width as i32))
+					.collect();
+			},
+			_ => {},
+		}
+		Ok(sampler)
+	}
+
+	pub fn next<R: Rng>(&mut self, rng: &mut R) -> i32 {
+		let row = self.next_row;
+		self.next_row += 1;
+
+		match self.distribution {
+			KeyDistribution::Uniform { distinct: None } => rng.gen_range(i32::MIN+1..=i32::MAX),
+			KeyDistribution::Uniform { distinct: Some(_) } => self.domain[rng.gen_range(0..self.domain.len())],
+			KeyDistribution::Zipf { .. } => {
+				// Ranks go from 1 to distinct
+				let rank = self.zipf.as_ref().unwrap().sample(rng) as usize;
+				self.domain[rank - 1]
+			},
+			KeyDistribution::Normal { .. } => {
+				let v = self.normal.as_ref().unwrap().sample(rng).round();
+				v.clamp((i32::MIN + 1) as f64, i32::MAX as f64) as i32
+			},
+			KeyDistribution::Sequential { distinct: None } => row as i32,
+			KeyDistribution::Sequential { distinct: Some(d) } => (row % d) as i32,
+			KeyDistribution::Clustered { cluster_width, .. } => {
+				let start = self.domain[rng.gen_range(0..self.domain.len())];
+				start + rng.gen_range(0..cluster_width) as i32
+			},
+		}
+	}
+}
@@ -1,138 +1,547 @@
 extern crate joinlib;
+use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 
+use joinlib::arrowtable;
+use joinlib::bintable;
+use joinlib::readtable::{CsvColumn, CsvMode, CsvOptions};
+use joinlib::schema::ColumnType;
 use joinlib::table::SimpleTable;
 use joinlib::record::Record;
+pub mod cli;
+pub mod distribution;
+pub mod streaming;
 pub mod tablegenerator;
+use cli::{parse_key_columns, parse_list, Flags, RowCount};
+use distribution::KeyDistribution;
+use rand::Rng;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use tablegenerator::generate_table;
-use tablegenerator::generate_right_table;
-use tablegenerator::write_table;
+use streaming::{verify_right_table, write_left_table, write_right_table, KeySpace};
+use tablegenerator::{derive_seed, read_manifest, table_file_name, table_rng, write_manifest, TableManifest};
+use tablegenerator::generate_table_with_distributions;
+use tablegenerator::{generate_right_table, join_cardinality, repeat_keys, MatchTarget, RightTableSpec};
+use tablegenerator::{convert_table, write_table};
 
 
 #[derive(Debug)]
 struct LeftTableGenConfig {
 	left_rows: usize,
 	left_cols: usize,
+	key_distributions: Vec<(usize, KeyDistribution)>,
+	// Rows per distinct key
+	fanout: usize,
+	// Written in chunks rather than built in memory
+	streaming: bool,
 	path: String,
+	seed: u64,
+	// The command which generated the table, for its manifest
+	generator_args: Vec<String>,
 }
 
+// A left table is either kept in memory, or streamed to
+// disk and recomputed from its key space by right tables
 #[derive(Debug)]
-struct RightTableGenConfig {
-	left_table: Vec<Record>,
-	right_rows: usize,
-	right_cols: usize,
-	left_col: usize,
-	right_col: usize,
-	join_selectivity: f64,
+enum LeftTable {
+	Records(Vec<Record>),
+	Streamed(KeySpace),
+}
+
+#[derive(Debug)]
+struct RightTableGenConfig<'a> {
+	left_table: &'a LeftTable,
+	spec: RightTableSpec,
 	path: String,
+	seed: u64,
+	generator_args: Vec<String>,
 }
 
 fn format_row_count(mut num_rows: usize) -> String {
 	// Figure out the right suffix for the row count
-	// For example, thousands is K, millions is M
-	let suffix: String;
-	if num_rows <= 0 {
-		panic!("What do you think you are doing");
+	// For example, thousands is K, millions is M. Counts
+	// that are not a whole number of thousands keep all
+	// their digits, so that no two counts share a name
+	let mut suffix = "";
+	for next in ["K", "M", "B"] {
+		if num_rows == 0 || !num_rows.is_multiple_of(1000) {
+			break;
+		}
+		suffix = next;
+		num_rows /= 1000;
 	}
-	else if num_rows < 1000 {
-		suffix = "".to_owned();
+
+	// Add suffix to the number of rows
+	let mut out = num_rows.to_string();
+	out.push_str(suffix);
+	out
+}
+
+fn format_key_columns(key_columns: &[(usize, usize)]) -> String {
+	// Single keys are named like left5_right5, and
+	// composite keys like left5-6_right5-6
+	let left: Vec<String> = key_columns.iter().map(|p| p.0.to_string()).collect();
+	let right: Vec<String> = key_columns.iter().map(|p| p.1.to_string()).collect();
+	format!("left{}_right{}", left.join("-"), right.join("-"))
+}
+
+fn left_table_name(rows: usize, cols: usize, distribution: &KeyDistribution, fanout: usize, extension: &str) -> String {
+	// Uniform tables are named like 10KR_10C, others like 10KR_10C_zipf1.2-d1000
+	let mut name = format!("{}R_{}C", format_row_count(rows), cols);
+	if *distribution != KeyDistribution::default() {
+		name.push_str(&format!("_{}", distribution));
 	}
-	else if num_rows < 1000 * 1000 {
-		suffix = "K".to_owned();
-		num_rows = num_rows / 1000;
+	if fanout > 1 {
+		name.push_str(&format!("_fanout{}", fanout));
 	}
-	else if num_rows < 1000 * 1000 * 1000 {
-		suffix = "M".to_owned();
-		num_rows = num_rows / (1000 * 1000);
+	name.push('.');
+	name.push_str(extension);
+	name
+}
+
+// select20 for 20% of the left rows matching, select20r for 20%
+// of the right rows matching and out5000 for 5000 output rows
+fn match_target_name(target: &MatchTarget) -> String {
+	match target {
+		MatchTarget::LeftRows(share) => format!("select{}", (share * 100.0).round()),
+		MatchTarget::RightRows(share) => format!("select{}r", (share * 100.0).round()),
+		MatchTarget::OutputRows(rows) => format!("out{}", rows),
+	}
+}
+
+fn right_table_name(spec: &RightTableSpec, extension: &str) -> String {
+	let mut name = format!("{}R_{}C_{}_{}", 
+			format_row_count(spec.num_rows), 
+			format_row_count(spec.num_cols), 
+			match_target_name(&spec.target),
+			format_key_columns(&spec.key_columns));
+	if spec.fanout > 1 {
+		name.push_str(&format!("_fanout{}", spec.fanout));
+	}
+	let hot_key_perc = (spec.hot_key_share * 100.0).round();
+	if hot_key_perc > 0.0 {
+		name.push_str(&format!("_hot{}", hot_key_perc));
 	}
-	else {
-		panic!("Billion-or-more formatting is not supported");
+	if spec.foreign_keys != (KeyDistribution::Sequential { distinct: None }) {
+		name.push_str(&format!("_fk-{}", spec.foreign_keys));
 	}
+	name.push('.');
+	name.push_str(extension);
+	name
+}
 
-	// Add suffix to the number of rows
-	let mut out = num_rows.to_string();
-	out.push_str(&suffix);
-	out
+// Extension of the default table names, csv, bin, rec, arrow or parquet
+fn parse_format(flags: &Flags, default: &str) -> Result<String, String> {
+	match flags.get_str("format").unwrap_or(default) {
+		"csv" => Ok("csv".to_owned()),
+		"binary" | "bin" => Ok(bintable::EXTENSION.to_owned()),
+		"records" | "rec" => Ok(bintable::RECORDS_EXTENSION.to_owned()),
+		"arrow" | "ipc" => Ok(arrowtable::IPC_EXTENSION.to_owned()),
+		"parquet" => Ok(arrowtable::PARQUET_EXTENSION.to_owned()),
+		format => Err(format!("Format is csv, binary, records, arrow or parquet, got {:?}", format)),
+	}
+}
+
+// Format of an existing table, for tables derived from it
+fn path_format(path: &str) -> &str {
+	match Path::new(path).extension().and_then(|e| e.to_str()) {
+		Some(e) if e == bintable::EXTENSION || e == bintable::RECORDS_EXTENSION => e,
+		Some(e) if e == arrowtable::IPC_EXTENSION || e == arrowtable::PARQUET_EXTENSION => e,
+		_ => "csv",
+	}
+}
+
+fn generate_left(config: &LeftTableGenConfig) -> Result<LeftTable, String> {
+	println!("left_rows: {:?}", config.left_rows);
+	let key_columns: Vec<usize> = config.key_distributions.iter().map(|d| d.0).collect();
+	let left_table = if config.streaming {
+		if config.key_distributions.iter().any(|d| d.1 != KeyDistribution::default()) {
+			return Err("Streamed tables only have uniform keys".to_owned());
+		}
+		let space = KeySpace {
+			seed: config.seed,
+			num_rows: config.left_rows,
+			fanout: config.fanout,
+			key_columns,
+		};
+		write_left_table(&config.path, &space, config.left_cols)?;
+		LeftTable::Streamed(space)
+	} else {
+		let mut rng = table_rng(config.seed);
+		let mut records = generate_table_with_distributions(config.left_rows, config.left_cols, &config.key_distributions, &mut rng)?;
+		if config.fanout > 1 {
+			repeat_keys(&mut records, &key_columns, config.fanout, &mut rng);
+		}
+		write_table(&records, &config.path);
+		LeftTable::Records(records)
+	};
+
+	write_manifest(&config.path, &TableManifest {
+		table: table_file_name(&config.path),
+		seed: config.seed,
+		num_rows: config.left_rows,
+		num_columns: config.left_cols,
+		generator_args: config.generator_args.clone(),
+		join: None,
+		key_space: match &left_table {
+			LeftTable::Streamed(space) => Some(space.clone()),
+			LeftTable::Records(_) => None,
+		},
+	});
+	println!("Wrote {} with seed {}", config.path, config.seed);
+	Ok(left_table)
+}
+
+// Generates and writes a right table, then reads it back to check
+// that its join with the left table has the planned size
+fn generate_right(config: RightTableGenConfig) -> Result<(), String> {
+	println!("right_rows: {:?}", config.spec.num_rows);
+	let (planned, achieved) = match config.left_table {
+		LeftTable::Records(left_table) => {
+			let mut rng = table_rng(config.seed);
+			let (right_table, planned) = generate_right_table(left_table, &config.spec, &mut rng)?;
+			write_table(&right_table, &config.path);
+			let written = SimpleTable::new(&config.path).copy_to_vec_of_records();
+			(planned, join_cardinality(left_table, &written, &config.spec.key_columns))
+		},
+		LeftTable::Streamed(space) => {
+			let planned = write_right_table(&config.path, space, &config.spec, config.seed)?;
+			(planned, verify_right_table(&config.path, space, &config.spec.key_columns))
+		},
+	};
+	if achieved != planned {
+		return Err(format!("{} joins to {:?}, planned {:?}", config.path, achieved, planned));
+	}
+	write_manifest(&config.path, &TableManifest {
+		table: table_file_name(&config.path),
+		seed: config.seed,
+		num_rows: config.spec.num_rows,
+		num_columns: config.spec.num_cols,
+		generator_args: config.generator_args,
+		join: Some(achieved),
+		key_space: None,
+	});
+	println!("Wrote {} with seed {}, joining to {} rows ({} left and {} right rows match)",
+		config.path, config.seed, achieved.output_rows, achieved.matched_left_rows, achieved.matched_right_rows);
+	Ok(())
+}
+
+// Seed given with --seed, or a random one. Either way it ends up in
+// the manifests, along with the arguments which regenerate the tables
+fn seed_and_args(command: &str, args: &[String], flags: &Flags) -> Result<(u64, Vec<String>), String> {
+	let seed: u64 = match flags.get("seed")? {
+		Some(s) => s,
+		None => rand::thread_rng().gen(),
+	};
+	let mut generator_args: Vec<String> = vec![command.to_owned()];
+	generator_args.extend(args.iter().filter(|a| !a.starts_with("--seed=")).cloned());
+	generator_args.push(format!("--seed={}", seed));
+	Ok((seed, generator_args))
+}
+
+// Either --output-rows, or --selectivity percent of the
+// rows on the --match-side (left by default) match
+fn parse_match_target(flags: &Flags) -> Result<MatchTarget, String> {
+	let selectivity_perc: Option<usize> = flags.get("selectivity")?;
+	if let Some(RowCount(rows)) = flags.get("output-rows")? {
+		if selectivity_perc.is_some() || flags.get_str("match-side").is_some() {
+			return Err("--output-rows cannot be combined with --selectivity or --match-side".to_owned());
+		}
+		return Ok(MatchTarget::OutputRows(rows));
+	}
+
+	let selectivity_perc = selectivity_perc.unwrap_or(20);
+	if selectivity_perc > 100 {
+		return Err(format!("Selectivity is a percent, got {}", selectivity_perc));
+	}
+	let share = (selectivity_perc as f64) / 100.0;
+	match flags.get_str("match-side").unwrap_or("left") {
+		"left" => Ok(MatchTarget::LeftRows(share)),
+		"right" => Ok(MatchTarget::RightRows(share)),
+		side => Err(format!("Match side is left or right, got {:?}", side)),
+	}
 }
 
-fn main() -> () {
-	// General Params
-	let join_name = "10K_left_select20";
-	let tables_dir = format!("tables/{}/", join_name);
-	let right_tables_dir = format!("tables/{}/rights", join_name);
-
-	// Create dirs
-	let _ = fs::create_dir_all(&tables_dir).unwrap();
-	let _ = fs::create_dir_all(&right_tables_dir).unwrap();
-	
-	// Scaling factor
-	// 1k for 10k left table
-	// 10k for 100k left table
-	// 100k for 1M left table
-	// 1M for 10M left table, etc
-	let scale = 1_000;
-
-	// ******************** Left Table Params *******************
-	let left_rows = 10 * scale;
-	let left_cols = 10;
-	println!("left_rows: {:?}", left_rows);
-
-	let left_table_name = format!("{}R_{}C.csv", format_row_count(left_rows), left_cols);
-	let left_path = tables_dir.to_owned() + &left_table_name;
-	
-	// **********************************************************
-	
+fn parse_fanout(flags: &Flags, name: &str) -> Result<usize, String> {
+	match flags.get_or(name, 1)? {
+		0 => Err(format!("--{} needs to be at least 1", name)),
+		fanout => Ok(fanout),
+	}
+}
+
+// Share of the matching right rows given to the hot key
+fn parse_hot_key(flags: &Flags) -> Result<f64, String> {
+	let perc: usize = flags.get_or("hot-key", 0)?;
+	if perc > 100 {
+		return Err(format!("--hot-key is a percent of the matching rows, not {}", perc));
+	}
+	Ok(perc as f64 / 100.0)
+}
+
+fn create_dir(dir: &Path) -> Result<(), String> {
+	fs::create_dir_all(dir).map_err(|e| format!("Could not create {:?} {:?}", dir, e))
+}
+
+fn left_command(args: &[String]) -> Result<(), String> {
+	let flags = Flags::parse(args, &["rows", "cols", "key-columns", "distribution", "fanout", "streaming", "format", "output", "seed"])?;
+	let (seed, generator_args) = seed_and_args("left", args, &flags)?;
+	let RowCount(left_rows) = flags.get_or("rows", RowCount(10_000))?;
+	let left_cols: usize = flags.get_or("cols", 10)?;
+	let key_columns: Vec<usize> = parse_list(flags.get_str("key-columns").unwrap_or("5"))?;
+	let distribution: KeyDistribution = flags.get_or("distribution", KeyDistribution::default())?;
+	let fanout = parse_fanout(&flags, "fanout")?;
+	let streaming: bool = flags.get_or("streaming", false)?;
+	let extension = parse_format(&flags, "csv")?;
+	let path = match flags.get_str("output") {
+		None => format!("tables/{}", left_table_name(left_rows, left_cols, &distribution, fanout, &extension)),
+		Some(p) => p.to_owned(),
+	};
+	if let Some(dir) = Path::new(&path).parent() {
+		create_dir(dir)?;
+	}
+
+	generate_left(&LeftTableGenConfig {
+		left_rows,
+		left_cols,
+		key_distributions: key_columns.iter().map(|c| (*c, distribution.clone())).collect(),
+		fanout,
+		streaming,
+		path,
+		seed,
+		generator_args,
+	})?;
+	Ok(())
+}
+
+fn right_command(args: &[String]) -> Result<(), String> {
+	let flags = Flags::parse(args, &[
+		"left", "rows", "cols", "join-columns", "selectivity", "match-side", "output-rows", "fanout",
+		"hot-key", "foreign-keys", "format", "output", "seed",
+	])?;
+	let (seed, generator_args) = seed_and_args("right", args, &flags)?;
+	let left_path: String = flags.require("left")?;
+	let RowCount(right_rows) = flags.get_or("rows", RowCount(10_000))?;
+	let right_cols: usize = flags.get_or("cols", 10)?;
+	let key_columns = parse_key_columns(flags.get_str("join-columns").unwrap_or("5:5"))?;
+	let target = parse_match_target(&flags)?;
+	let fanout = parse_fanout(&flags, "fanout")?;
+	let hot_key_share = parse_hot_key(&flags)?;
+	let foreign_keys: KeyDistribution = flags.get_or("foreign-keys", KeyDistribution::Sequential { distinct: None })?;
+	let spec = RightTableSpec {
+		num_rows: right_rows,
+		num_cols: right_cols,
+		target,
+		key_columns,
+		fanout,
+		hot_key_share,
+		foreign_keys,
+	};
+
+	// Right tables of streamed left tables are streamed too,
+	// recomputing the left keys rather than reading them
+	let left_table = match read_manifest(&left_path).ok().and_then(|m| m.key_space) {
+		Some(space) => LeftTable::Streamed(space),
+		None => LeftTable::Records(SimpleTable::new(&left_path).copy_to_vec_of_records()),
+	};
+
+	// Right tables go next to the left table, in its format, by default
+	let extension = parse_format(&flags, path_format(&left_path))?;
+	let path = match flags.get_str("output") {
+		Some(p) => PathBuf::from(p),
+		None => Path::new(&left_path)
+			.parent()
+			.unwrap_or_else(|| Path::new(""))
+			.join("rights")
+			.join(right_table_name(&spec, &extension)),
+	};
+	if let Some(dir) = path.parent() {
+		create_dir(dir)?;
+	}
+
+	generate_right(RightTableGenConfig {
+		left_table: &left_table,
+		spec,
+		path: path.to_string_lossy().into_owned(),
+		seed,
+		generator_args,
+	})
+}
+
+// A left table and right tables of several sizes, in the
+// layout profiler.sh expects: [output]/[name]/ for the left
+// table and [output]/[name]/rights/ for the right tables
+fn experiment_command(args: &[String]) -> Result<(), String> {
+	let flags = Flags::parse(args, &[
+		"name", "left-rows", "left-cols", "left-fanout", "right-rows", "right-cols", "right-fanout",
+		"join-columns", "selectivity", "match-side", "output-rows", "distribution", "hot-key",
+		"foreign-keys", "streaming", "format", "output", "seed",
+	])?;
+	let (seed, generator_args) = seed_and_args("experiment", args, &flags)?;
+	let RowCount(left_rows) = flags.get_or("left-rows", RowCount(10_000))?;
+	let left_cols: usize = flags.get_or("left-cols", 10)?;
+	let left_fanout = parse_fanout(&flags, "left-fanout")?;
+	let right_rows: Vec<RowCount> = parse_list(flags.get_str("right-rows").unwrap_or("2K,4K,6K,8K,10K"))?;
+	// Right tables are named and seeded by their row count, so
+	// the same count twice would write one table over the other
+	if let Some((i, RowCount(rows))) = right_rows.iter().enumerate().find(|(i, r)| right_rows[..*i].contains(r)) {
+		return Err(format!("--right-rows lists {} rows twice, at entry {}", rows, i + 1));
+	}
+	let right_cols: usize = flags.get_or("right-cols", 10)?;
+	let right_fanout = parse_fanout(&flags, "right-fanout")?;
+	let key_columns = parse_key_columns(flags.get_str("join-columns").unwrap_or("5:5"))?;
+	let target = parse_match_target(&flags)?;
+	let distribution: KeyDistribution = flags.get_or("distribution", KeyDistribution::default())?;
+	let hot_key_share = parse_hot_key(&flags)?;
+	let foreign_keys: KeyDistribution = flags.get_or("foreign-keys", KeyDistribution::Sequential { distinct: None })?;
+	let streaming: bool = flags.get_or("streaming", false)?;
+	let extension = parse_format(&flags, "csv")?;
+
+	let join_name = match flags.get_str("name") {
+		None => format!("{}_left_{}", format_row_count(left_rows), match_target_name(&target)),
+		Some(n) => n.to_owned(),
+	};
+	let tables_dir = Path::new(flags.get_str("output").unwrap_or("tables")).join(join_name);
+	let right_tables_dir = tables_dir.join("rights");
+	create_dir(&right_tables_dir)?;
+
 	let left_config = LeftTableGenConfig {
-		left_rows: left_rows,
-		left_cols: left_cols,
-		path: left_path.clone(),
+		left_rows,
+		left_cols,
+		key_distributions: key_columns.iter().map(|p| (p.0, distribution.clone())).collect(),
+		fanout: left_fanout,
+		streaming,
+		path: tables_dir.join(left_table_name(left_rows, left_cols, &distribution, left_fanout, &extension)).to_string_lossy().into_owned(),
+		seed,
+		generator_args: generator_args.clone(),
 	};
+	let left_table = generate_left(&left_config)?;
 
-	// To generate left table, uncomment following
-	
-	let left_table = generate_table(left_config.left_rows, left_config.left_cols);
-	write_table(&left_table, &left_config.path);
-
-	// To use existing left table, uncomment following
-	let left_table = SimpleTable::new(&left_path).copy_to_vec_of_records();
-
-	// ******************** Right Table Params ********************
-	vec![2, 4, 6, 8, 10].par_iter().for_each(|i| {
-
-		let right_rows = i * scale;
-		let right_cols = 10;
-		println!("right_rows: {:?}", right_rows);
-
-		// Join Params
-		let left_col = 5;
-		let right_col = 5;
-		let join_selectivity_perc = 20;
-
-		let right_table_name = format!("{}R_{}C_select{}_left{}_right{}.csv", 
-				format_row_count(right_rows), 
-				format_row_count(right_cols), 
-				join_selectivity_perc,
-				left_col, right_col);
-
-		// Ensure sizes + selectivity play friendly w/ each other
-		assert!(right_rows >= ((join_selectivity_perc * left_rows) / 100));
-		
-		let right_path = tables_dir.to_owned() + "rights/" + &right_table_name;
-	
-		// **********************************************************
-
-		let rc = RightTableGenConfig {
-			left_table: left_table.clone(),
-			right_rows: right_rows,
-			right_cols: right_cols,
-			left_col: left_col,
-			right_col: right_col,
-			join_selectivity: (join_selectivity_perc as f64) / 100.0,  
-			path: right_path,
+	// Each right table gets its own seed, derived from its size, so
+	// generating them in parallel does not change what they contain
+	let generate = |RowCount(rows): &RowCount| {
+		let spec = RightTableSpec {
+			num_rows: *rows,
+			num_cols: right_cols,
+			target,
+			key_columns: key_columns.clone(),
+			fanout: right_fanout,
+			hot_key_share,
+			foreign_keys: foreign_keys.clone(),
 		};
+		let path = right_tables_dir.join(right_table_name(&spec, &extension)).to_string_lossy().into_owned();
+		generate_right(RightTableGenConfig {
+			left_table: &left_table,
+			spec,
+			path,
+			seed: derive_seed(seed, *rows as u64),
+			generator_args: generator_args.clone(),
+		})
+	};
+	// Streamed tables are written in parallel chunks already
+	if streaming {
+		right_rows.iter().try_for_each(generate)
+	} else {
+		right_rows.par_iter().map(generate).collect()
+	}
+}
 
-		let right_table = generate_right_table(rc.left_table, rc.right_rows, rc.right_cols, rc.join_selectivity, rc.left_col, rc.right_col);
-		write_table(&right_table, &rc.path);
-	});
+// How to read CSV inputs: --delimiter is one character or tab,
+// --columns lists indexes or header names to keep, --types the
+// types of the kept columns, and --lenient skips bad rows rather
+// than failing
+fn parse_csv_options(flags: &Flags) -> Result<CsvOptions, String> {
+	let delimiter = match flags.get_str("delimiter") {
+		None => b',',
+		Some("tab") => b'\t',
+		Some(d) if d.len() == 1 => d.as_bytes()[0],
+		Some(d) => return Err(format!("Delimiter is one character or tab, got {:?}", d)),
+	};
+	let columns = match flags.get_str("columns") {
+		None => None,
+		Some(c) => Some(parse_list::<CsvColumn>(c)?),
+	};
+	let types = match flags.get_str("types") {
+		None => None,
+		Some(t) => Some(parse_list::<ColumnType>(t)?),
+	};
+	Ok(CsvOptions {
+		delimiter,
+		has_headers: !flags.get_or("no-header", false)?,
+		columns,
+		types,
+		mode: if flags.get_or("lenient", false)? { CsvMode::Lenient } else { CsvMode::Strict },
+	})
+}
+
+// Rewrites a table in another format
+fn convert_command(args: &[String]) -> Result<(), String> {
+	let flags = Flags::parse(args, &["input", "output", "format", "delimiter", "no-header", "columns", "types", "lenient"])?;
+	let input: String = flags.require("input")?;
+	let output: String = match flags.get_str("output") {
+		Some(p) => p.to_owned(),
+		// Next to the input, in the given format or else
+		// binary for CSV tables and CSV for the others
+		None => {
+			let default = if path_format(&input) == "csv" { bintable::EXTENSION } else { "csv" };
+			let extension = parse_format(&flags, default)?;
+			Path::new(&input).with_extension(extension).to_string_lossy().into_owned()
+		},
+	};
+	if output == input {
+		return Err(format!("Converting {:?} would overwrite it", input));
+	}
+	let (num_rows, skipped) = convert_table(&input, &output, &parse_csv_options(&flags)?)?;
+	for e in skipped.iter() {
+		println!("Skipped {}", e);
+	}
+	println!("Wrote {} rows to {}", num_rows, output);
+	Ok(())
+}
+
+const USAGE: &str = "Usage:
+  generate left [--rows=10K] [--cols=10] [--key-columns=5] [--distribution=uniform] [--fanout=1]
+                [--streaming] [--format=csv] [--output=PATH] [--seed=N]
+  generate right --left=PATH [--rows=10K] [--cols=10] [--join-columns=5:5] [--selectivity=20]
+                 [--match-side=left] [--output-rows=N] [--fanout=1] [--hot-key=0] [--foreign-keys=seq]
+                 [--format=csv] [--output=PATH] [--seed=N]
+  generate experiment [--name=NAME] [--left-rows=10K] [--left-cols=10] [--left-fanout=1]
+                      [--right-rows=2K,4K,6K,8K,10K] [--right-cols=10] [--right-fanout=1]
+                      [--join-columns=5:5] [--selectivity=20] [--match-side=left] [--output-rows=N]
+                      [--distribution=uniform] [--hot-key=0] [--foreign-keys=seq] [--streaming]
+                      [--format=csv] [--output=tables] [--seed=N]
+  generate convert --input=PATH [--format=FORMAT] [--output=PATH]
+                   [--delimiter=,] [--no-header] [--columns=0,1,...] [--types=i32,...] [--lenient]
+
+Row counts take K, M and B suffixes. Selectivity and hot-key are percents. Selectivity is the share
+of the left (or, with --match-side=right, the right) rows with a match; --output-rows instead sets
+the exact join output size. Fan-outs are rows per key. Distributions are
+uniform, uniform-d[distinct], zipf[exponent]-d[distinct], normal[mean]-sd[std_dev],
+seq, seq-d[distinct] and clustered[clusters]-w[width]. Without --seed a random seed is used;
+the seed and arguments of every table are written to a .manifest.json next to it. --streaming
+writes tables in chunks with bounded memory, for uniform keys; right tables of a streamed left
+table are streamed too. Formats are csv, binary (columnar, .bin), records (.rec, loaded by
+memory-mapping), arrow (Arrow IPC files, .arrow) and parquet, and a table's path extension picks
+its format; streamed tables cannot be arrow or parquet. convert rewrites a table in --format, or
+by default turns CSV tables into binary ones and other tables into CSV ones. CSV inputs may use
+another delimiter or lack a header, --columns keeps the given column indexes or names in order,
+--types gives the kept columns' types (i32, i64, f64 or utf8, all i32 by default) and --lenient
+skips and reports bad rows instead of failing. Binary tables cannot hold utf8 columns.";
+
+fn main() {
+	let args: Vec<String> = env::args().collect();
+	let result = match args.get(1).map(|a| a.as_str()) {
+		Some("left") => left_command(&args[2..]),
+		Some("right") => right_command(&args[2..]),
+		Some("experiment") => experiment_command(&args[2..]),
+		Some("convert") => convert_command(&args[2..]),
+		Some(command) => Err(format!("Unrecognized command {:?}", command)),
+		None => Err("Expected a command".to_owned()),
+	};
+
+	if let Err(e) = result {
+		println!("{}", e);
+		println!();
+		println!("{}", USAGE);
+		process::exit(1);
+	}
 }
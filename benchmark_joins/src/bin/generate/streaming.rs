@@ -0,0 +1,419 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+extern crate joinlib;
+use joinlib::arrowtable::arrow_path_format;
+use joinlib::bintable::{default_column_names, encode_records, is_binary_path, is_record_path, BinaryTableWriter, MappedColumns, RecordTableWriter};
+use joinlib::record::Record;
+use joinlib::schema::Schema;
+use joinlib::table::SimpleTable;
+use rand::Rng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::distribution::KeyDistribution;
+use crate::tablegenerator::{check_key_columns, derive_seed, table_rng, JoinCardinality, MatchTarget, RightTableSpec};
+
+// Rows generated and formatted by one task
+const CHUNK_ROWS: usize = 1 << 16;
+
+// Seed labels, kept apart from the column numbers
+// which label the key value permutations
+const ROW_ORDER_LABEL: u64 = u64::MAX;
+const CHUNK_LABEL: u64 = u64::MAX - 1;
+const NON_MATCHING_LABEL: u64 = u64::MAX - 2;
+
+// Key values are permutations of u32, where the lower half of the
+// indices are left keys and the upper half are kept for right keys
+// which must not match. Index 2^31 would be i32::MIN, which is left out
+const NON_MATCHING_BASE: u64 = (1 << 31) + 1;
+pub const MAX_KEYS: usize = 1 << 31;
+
+// A bijection on [0, size) minus an excluded value: a keyed Feistel
+// network over the smallest power of 4 at least size, which steps
+// again whenever it lands outside of the domain (cycle walking)
+struct Permutation {
+	half_bits: u32,
+	round_keys: [u64; 4],
+	size: u64,
+	excluded: Option<u64>,
+}
+
+impl Permutation {
+	fn new(size: u64, excluded: Option<u64>, seed: u64) -> Permutation {
+		assert!(size > 0);
+		let mut half_bits = 1;
+		while (1u64 << (2 * half_bits)) < size {
+			half_bits += 1;
+		}
+		Permutation {
+			half_bits,
+			round_keys: [0, 1, 2, 3].map(|round| derive_seed(seed, round)),
+			size,
+			excluded,
+		}
+	}
+
+	fn in_domain(&self, x: u64) -> bool {
+		x < self.size && Some(x) != self.excluded
+	}
+
+	fn encrypt(&self, x: u64) -> u64 {
+		let mask = (1u64 << self.half_bits) - 1;
+		let (mut l, mut r) = (x >> self.half_bits, x & mask);
+		for key in self.round_keys.iter() {
+			let next = l ^ (derive_seed(*key, r) & mask);
+			l = r;
+			r = next;
+		}
+		(l << self.half_bits) | r
+	}
+
+	fn decrypt(&self, y: u64) -> u64 {
+		let mask = (1u64 << self.half_bits) - 1;
+		let (mut l, mut r) = (y >> self.half_bits, y & mask);
+		for key in self.round_keys.iter().rev() {
+			let previous = r ^ (derive_seed(*key, l) & mask);
+			r = l;
+			l = previous;
+		}
+		(l << self.half_bits) | r
+	}
+
+	fn apply(&self, x: u64) -> u64 {
+		debug_assert!(self.in_domain(x));
+		let mut y = self.encrypt(x);
+		while !self.in_domain(y) {
+			y = self.encrypt(y);
+		}
+		y
+	}
+
+	fn invert(&self, y: u64) -> u64 {
+		debug_assert!(self.in_domain(y));
+		let mut x = self.decrypt(y);
+		while !self.in_domain(x) {
+			x = self.decrypt(x);
+		}
+		x
+	}
+}
+
+// Values of one key column, by key index
+struct KeyValues(Permutation);
+
+impl KeyValues {
+	fn value(&self, index: u64) -> i32 {
+		self.0.apply(index) as u32 as i32
+	}
+
+	fn index(&self, value: i32) -> u64 {
+		self.0.invert(value as u32 as u64)
+	}
+}
+
+// Everything needed to recompute the keys of a streamed left table,
+// so right tables never need to read it: row i has key index
+// order(i) / fanout, and key index j has the value of j under
+// the permutation of each key column
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeySpace {
+	pub seed: u64,
+	pub num_rows: usize,
+	pub fanout: usize,
+	pub key_columns: Vec<usize>,
+}
+
+impl KeySpace {
+	pub fn num_keys(&self) -> usize {
+		self.num_rows / self.fanout
+	}
+
+	fn key_values(&self, col: usize) -> KeyValues {
+		KeyValues(Permutation::new(1 << 32, Some(1 << 31), derive_seed(self.seed, col as u64)))
+	}
+}
+
+// Where write_rows puts the chunks: CSV text and record table rows
+// are appended, while each column of a chunk goes to its place in
+// a binary table
+enum ChunkSink {
+	Csv(BufWriter<File>),
+	Binary(BinaryTableWriter),
+	Records(RecordTableWriter),
+}
+
+// Writes num_rows rows in chunks of CHUNK_ROWS, encoding a batch of
+// chunks in parallel and writing them in order, so memory stays
+// bounded by the batch. Every chunk has its own generator, which
+// makes the output depend on the seed only. Columns are uniformly
+// random before set_keys fills in the keys of a row, given its number.
+// Paths ending in .bin get binary tables, .rec record tables, others CSV
+fn write_rows<F>(path: &str, num_rows: usize, num_cols: usize, seed: u64, set_keys: F)
+where
+	F: Fn(usize, &mut [i32]) + Sync,
+{
+	let column_names = default_column_names(num_cols);
+	let schema = Schema::int32(&column_names);
+	let mut sink = if is_binary_path(path) {
+		match BinaryTableWriter::create(path, &schema, num_rows) {
+			Err(e) => panic!("Could not create {:?} {:?}", path, e),
+			Ok(w) => ChunkSink::Binary(w),
+		}
+	} else if is_record_path(path) {
+		match RecordTableWriter::create(path, &schema, num_rows) {
+			Err(e) => panic!("Could not create {:?} {:?}", path, e),
+			Ok(w) => ChunkSink::Records(w),
+		}
+	} else {
+		let file = match File::create(path) {
+			Err(e) => panic!("Could not create {:?} {:?}", path, e),
+			Ok(f) => f,
+		};
+		let mut out = BufWriter::new(file);
+		writeln!(out, "{}", column_names.join(",")).unwrap();
+		ChunkSink::Csv(out)
+	};
+	let binary = matches!(sink, ChunkSink::Binary(_));
+	let records = matches!(sink, ChunkSink::Records(_));
+
+	let chunk_seed = derive_seed(seed, CHUNK_LABEL);
+	let num_chunks = num_rows.div_ceil(CHUNK_ROWS);
+	let batch = rayon::current_num_threads() * 2;
+	for first in (0..num_chunks).step_by(batch) {
+		// One buffer of text or rows, or one buffer per column
+		let chunks: Vec<Vec<Vec<u8>>> = (first..(first + batch).min(num_chunks)).into_par_iter()
+			.map(|chunk| {
+				let mut rng = table_rng(derive_seed(chunk_seed, chunk as u64));
+				let mut bufs: Vec<Vec<u8>> = vec![Vec::new(); if binary { num_cols } else { 1 }];
+				let mut fields: Vec<i32> = vec![0; num_cols];
+				let mut chunk_records: Vec<Record> = Vec::new();
+				for row in chunk * CHUNK_ROWS..((chunk + 1) * CHUNK_ROWS).min(num_rows) {
+					for field in fields.iter_mut() {
+						*field = rng.gen_range(i32::MIN+1..=i32::MAX);
+					}
+					set_keys(row, &mut fields);
+					if records {
+						chunk_records.push(Record::new(&fields));
+						continue;
+					}
+					if binary {
+						for (buf, field) in bufs.iter_mut().zip(fields.iter()) {
+							buf.extend_from_slice(&field.to_le_bytes());
+						}
+						continue;
+					}
+					let buf = &mut bufs[0];
+					for (i, field) in fields.iter().enumerate() {
+						if i > 0 {
+							buf.push(b',');
+						}
+						write!(buf, "{}", field).unwrap();
+					}
+					buf.push(b'\n');
+				}
+				if records {
+					bufs[0] = encode_records(&chunk_records);
+				}
+				bufs
+			})
+			.collect();
+		for (chunk, bufs) in (first..).zip(chunks) {
+			match &mut sink {
+				ChunkSink::Csv(out) => out.write_all(&bufs[0]).unwrap(),
+				ChunkSink::Records(writer) => if let Err(e) = writer.write_encoded(&bufs[0]) {
+					panic!("Could not write {:?} {:?}", path, e);
+				},
+				ChunkSink::Binary(writer) => {
+					for (col, buf) in bufs.iter().enumerate() {
+						if let Err(e) = writer.write_column_block(col, chunk * CHUNK_ROWS, buf) {
+							panic!("Could not write {:?} {:?}", path, e);
+						}
+					}
+				},
+			}
+		}
+	}
+	match sink {
+		ChunkSink::Csv(mut out) => out.flush().unwrap(),
+		ChunkSink::Binary(writer) => if let Err(e) = writer.finish() {
+			panic!("Could not write {:?} {:?}", path, e);
+		},
+		ChunkSink::Records(writer) => if let Err(e) = writer.finish() {
+			panic!("Could not write {:?} {:?}", path, e);
+		},
+	}
+}
+
+// A left table with fanout rows per key, spread in random order
+// Chunks are appended, which Arrow and Parquet files do not allow
+fn check_streamable(path: &str) -> Result<(), String> {
+	match arrow_path_format(path) {
+		Some(_) => Err(format!("Streamed tables are CSV, binary or record tables, not {:?}", path)),
+		None => Ok(()),
+	}
+}
+
+pub fn write_left_table(path: &str, space: &KeySpace, num_cols: usize) -> Result<(), String> {
+	check_streamable(path)?;
+	check_key_columns(space.key_columns.iter().copied(), num_cols)?;
+	if space.fanout == 0 || !space.num_rows.is_multiple_of(space.fanout) {
+		return Err(format!("{} rows do not split into keys of {} rows", space.num_rows, space.fanout));
+	}
+	if space.num_keys() > MAX_KEYS {
+		return Err(format!("At most {} distinct keys can be streamed", MAX_KEYS));
+	}
+	let order = Permutation::new(space.num_rows as u64, None, derive_seed(space.seed, ROW_ORDER_LABEL));
+	let values: Vec<(usize, KeyValues)> = space.key_columns.iter().map(|c| (*c, space.key_values(*c))).collect();
+	write_rows(path, space.num_rows, num_cols, space.seed, |row, fields| {
+		let key = order.apply(row as u64) / space.fanout as u64;
+		for (col, key_values) in values.iter() {
+			fields[*col] = key_values.value(key);
+		}
+	});
+	Ok(())
+}
+
+// Number of matching right rows, and the join they make. Matching
+// rows go to key indices 0, 1, 2, ... spec.fanout at a time, and
+// since every left key has the same number of rows the targets
+// are exact whenever they are a whole number of keys
+fn plan_right(left: &KeySpace, spec: &RightTableSpec) -> Result<(usize, JoinCardinality), String> {
+	let left_fanout = left.fanout;
+	let matching_rows = match spec.target {
+		MatchTarget::LeftRows(share) => {
+			let target = (left.num_rows as f64 * share).round() as usize;
+			if !target.is_multiple_of(left_fanout) {
+				return Err(format!("{} left rows are not a whole number of keys of {} rows", target, left_fanout));
+			}
+			(target / left_fanout) * spec.fanout
+		},
+		MatchTarget::RightRows(share) => (spec.num_rows as f64 * share).round() as usize,
+		MatchTarget::OutputRows(target) => {
+			if !target.is_multiple_of(left_fanout) {
+				return Err(format!("Every matching right row outputs {} rows, which do not add up to {}", left_fanout, target));
+			}
+			target / left_fanout
+		},
+	};
+
+	let matched_keys = matching_rows.div_ceil(spec.fanout);
+	if matched_keys > left.num_keys() {
+		return Err(format!("{} matching right rows need {} left keys, but there are {}", matching_rows, matched_keys, left.num_keys()));
+	}
+	if matching_rows > spec.num_rows {
+		return Err(format!("{} right rows cannot hold the {} matching rows", spec.num_rows, matching_rows));
+	}
+	Ok((matching_rows, JoinCardinality {
+		output_rows: matching_rows * left_fanout,
+		matched_left_rows: matched_keys * left_fanout,
+		matched_right_rows: matching_rows,
+	}))
+}
+
+// Streams a right table against a streamed left table, without reading
+// it: matching rows take the values of a left key index, and the last
+// key column of the other rows takes a value no left key has
+pub fn write_right_table(path: &str, left: &KeySpace, spec: &RightTableSpec, seed: u64) -> Result<JoinCardinality, String> {
+	check_streamable(path)?;
+	if spec.hot_key_share > 0.0 || spec.foreign_keys != (KeyDistribution::Sequential { distinct: None }) {
+		return Err("Streamed right tables have no hot keys and sequential foreign keys".to_owned());
+	}
+	if let Some((l, _)) = spec.key_columns.iter().find(|(l, _)| !left.key_columns.contains(l)) {
+		return Err(format!("Column {} is not a key column of the left table", l));
+	}
+	check_key_columns(spec.key_columns.iter().map(|p| p.1), spec.num_cols)?;
+	if spec.num_rows as u64 > u32::MAX as u64 - NON_MATCHING_BASE {
+		return Err(format!("At most {} right rows can be streamed", u32::MAX as u64 - NON_MATCHING_BASE));
+	}
+	let (matching_rows, planned) = plan_right(left, spec)?;
+
+	let order = Permutation::new(spec.num_rows as u64, None, derive_seed(seed, ROW_ORDER_LABEL));
+	let non_matching_seed = derive_seed(seed, NON_MATCHING_LABEL);
+	let values: Vec<(usize, KeyValues)> = spec.key_columns.iter().map(|(l, r)| (*r, left.key_values(*l))).collect();
+	let last = values.len() - 1;
+	write_rows(path, spec.num_rows, spec.num_cols, seed, |row, fields| {
+		let rank = order.apply(row as u64) as usize;
+		if rank < matching_rows {
+			let key = (rank / spec.fanout) as u64;
+			for (col, key_values) in values.iter() {
+				fields[*col] = key_values.value(key);
+			}
+		} else {
+			// Leading key columns agree with some left key
+			let key = derive_seed(non_matching_seed, row as u64) % left.num_keys() as u64;
+			for (i, (col, key_values)) in values.iter().enumerate() {
+				fields[*col] = if i == last { key_values.value(NON_MATCHING_BASE + row as u64) } else { key_values.value(key) };
+			}
+		}
+	});
+	Ok(planned)
+}
+
+// Counts the join of a written right table with the streamed left
+// table by reading it back a row at a time and inverting its last
+// key column. Binary tables are memory-mapped, so that only their key
+// columns are read, and only a bit per left key is kept, to count the
+// matched left rows
+pub fn verify_right_table(path: &str, left: &KeySpace, key_columns: &[(usize, usize)]) -> JoinCardinality {
+	let values: Vec<KeyValues> = key_columns.iter().map(|(l, _)| left.key_values(*l)).collect();
+	let (leading, last) = values.split_at(values.len() - 1);
+
+	let mut matched: Vec<u64> = vec![0; left.num_keys().div_ceil(64)];
+	let mut cardinality = JoinCardinality::default();
+	// Counts a row, given the values of its key columns
+	let mut count = |row: &[i32]| {
+		let (leading_row, last_value) = row.split_at(row.len() - 1);
+		let key = last[0].index(last_value[0]);
+		if key >= left.num_keys() as u64 || leading.iter().zip(leading_row).any(|(key_values, v)| key_values.value(key) != *v) {
+			return;
+		}
+		cardinality.output_rows += left.fanout;
+		cardinality.matched_right_rows += 1;
+		let (word, bit) = ((key / 64) as usize, key % 64);
+		if matched[word] & (1 << bit) == 0 {
+			matched[word] |= 1 << bit;
+			cardinality.matched_left_rows += left.fanout;
+		}
+	};
+
+	let right_columns: Vec<usize> = key_columns.iter().map(|p| p.1).collect();
+	let mut row: Vec<i32> = vec![0; right_columns.len()];
+	if is_binary_path(path) {
+		let columns = match MappedColumns::open(path) {
+			Err(e) => panic!("Could not read {:?} {:?}", path, e),
+			Ok((_, columns)) => columns,
+		};
+		if let Some(c) = right_columns.iter().find(|c| !columns.null_words(**c).is_empty()) {
+			panic!("Column {:?} of {:?} has NULLs", c, path);
+		}
+		let key_columns: Vec<&[i32]> = right_columns.iter().map(|c| columns.column(*c)).collect();
+		for i in 0..columns.num_rows() {
+			for (v, column) in row.iter_mut().zip(&key_columns) {
+				*v = column[i];
+			}
+			count(&row);
+		}
+	} else if is_record_path(path) {
+		// Mapped, so only a page of rows is read in at a time
+		for record in SimpleTable::new(path).record_iterator() {
+			for (v, col) in row.iter_mut().zip(right_columns.iter()) {
+				*v = *record.get_column(*col);
+			}
+			count(&row);
+		}
+	} else {
+		let mut reader = match csv::Reader::from_path(path) {
+			Err(e) => panic!("Could not read {:?} {:?}", path, e),
+			Ok(r) => r,
+		};
+		for result in reader.records() {
+			let record = result.unwrap();
+			for (v, col) in row.iter_mut().zip(right_columns.iter()) {
+				*v = record[*col].parse().unwrap();
+			}
+			count(&row);
+		}
+	}
+	cardinality
+}
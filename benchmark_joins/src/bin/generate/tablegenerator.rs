@@ -1,31 +1,103 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
 extern crate joinlib;
+use joinlib::arrowtable::arrow_format;
+use joinlib::bintable::{default_column_names, is_binary_table};
+use joinlib::readtable::{read_csv_table, CsvError, CsvOptions};
 use joinlib::record::Record;
+use joinlib::schema::Schema;
+use joinlib::table::read_table;
 
-use csv::Writer;
-use rand::{Rng, prelude::ThreadRng};
-use rand::{seq::SliceRandom, thread_rng};
+use crate::distribution::{KeyDistribution, ValueSampler};
+use crate::streaming::KeySpace;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+// Random generator behind every table. ChaCha8 gives the same
+// stream for a seed on every platform and rand version, unlike
+// StdRng, so a seed is enough to regenerate a table exactly
+pub type TableRng = ChaCha8Rng;
+
+pub fn table_rng(seed: u64) -> TableRng {
+	ChaCha8Rng::seed_from_u64(seed)
+}
+
+// Seed of one of the tables generated from a base seed, e.g. each
+// right table of an experiment. Mixes with SplitMix64 so that nearby
+// labels give unrelated seeds
+pub fn derive_seed(seed: u64, label: u64) -> u64 {
+	let mut z = seed ^ label.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^ (z >> 31)
+}
+
+// Written next to every generated table, recording how to generate
+// it again: the same generator arguments with the same seed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TableManifest {
+	// File name of the table, without its directory
+	pub table: String,
+	pub seed: u64,
+	pub num_rows: usize,
+	pub num_columns: usize,
+	pub generator_args: Vec<String>,
+	// For right tables, the size of their join with the left table,
+	// as counted from the written table
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub join: Option<JoinCardinality>,
+	// For streamed left tables, how to recompute their keys
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub key_space: Option<KeySpace>,
+}
+
+// 10KR_10C.csv gets the manifest 10KR_10C.manifest.json
+pub fn manifest_path(table_path: &str) -> PathBuf {
+	Path::new(table_path).with_extension("manifest.json")
+}
+
+pub fn table_file_name(table_path: &str) -> String {
+	Path::new(table_path).file_name().map_or(table_path.to_owned(), |n| n.to_string_lossy().into_owned())
+}
+
+pub fn read_manifest(table_path: &str) -> Result<TableManifest, String> {
+	let path = manifest_path(table_path);
+	let file = File::open(&path).map_err(|e| format!("Could not open {:?} {:?}", path, e))?;
+	serde_json::from_reader(file).map_err(|e| format!("Could not read manifest {:?} {:?}", path, e))
+}
+
+pub fn write_manifest(table_path: &str, manifest: &TableManifest) {
+	let path = manifest_path(table_path);
+	let file = match File::create(&path) {
+		Err(e) => panic!("Could not create {:?} {:?}", path, e),
+		Ok(f) => f,
+	};
+	if let Err(e) = serde_json::to_writer_pretty(file, manifest) {
+		panic!("Could not write manifest {:?} {:?}", path, e);
+	}
+}
 
 struct MissingValuePicker {
 	values: HashSet<i32>,
-	rng: ThreadRng,
 }
 
 impl MissingValuePicker {
 	fn new(values: HashSet<i32>) -> MissingValuePicker {
 		MissingValuePicker {
 			values,
-			rng: rand::thread_rng(),
 		}
 	}
 
-	fn next(&mut self) -> i32 {
+	fn next<R: Rng>(&mut self, rng: &mut R) -> i32 {
 		// Keep generating random numbers uniformly until we've
 		// not seen the new generated value before
-		let mut v = self.rng.gen_range(i32::MIN+1..=i32::MAX);
+		let mut v = rng.gen_range(i32::MIN+1..=i32::MAX);
 		while self.values.contains(&v) {
 			//println!("MVP collision!");
-			v = self.rng.gen_range(i32::MIN+1..=i32::MAX);
+			v = rng.gen_range(i32::MIN+1..=i32::MAX);
 		}
 
 		// Assert that we've indeed added this value to the values
@@ -37,132 +109,395 @@ impl MissingValuePicker {
 	}
 }
 
-pub fn generate_table(num_rows: usize, num_cols: usize) -> Vec<Record> {
+// Key columns have to be columns of the table
+pub fn check_key_columns(columns: impl IntoIterator<Item = usize>, num_cols: usize) -> Result<(), String> {
+	match columns.into_iter().find(|c| *c >= num_cols) {
+		Some(col) => Err(format!("Key column {} is not one of the {} columns", col, num_cols)),
+		None => Ok(()),
+	}
+}
+
+pub fn generate_table<R: Rng>(num_rows: usize, num_cols: usize, rng: &mut R) -> Vec<Record> {
+	// Uniform columns over all of i32 always have a sampler
+	generate_table_with_distributions(num_rows, num_cols, &[], rng).unwrap()
+}
+
+// Columns are uniformly random, except for the (column, distribution)
+// pairs given, whose values follow their own distribution
+pub fn generate_table_with_distributions<R: Rng>(num_rows: usize,
+																								 num_cols: usize,
+																								 distributions: &[(usize, KeyDistribution)],
+																								 rng: &mut R) -> Result<Vec<Record>, String> {
 	// use a random generator to generate values
 	let mut table: Vec<Record> = Vec::with_capacity(num_rows);
-	let mut rng = rand::thread_rng();
+
+	// Uniform over all of i32 unless told otherwise
+	let mut samplers: Vec<ValueSampler> = (0..num_cols)
+		.map(|_| ValueSampler::new(&KeyDistribution::default(), rng))
+		.collect::<Result<_, _>>()?;
+	check_key_columns(distributions.iter().map(|d| d.0), num_cols)?;
+	for (col, distribution) in distributions {
+		samplers[*col] = ValueSampler::new(distribution, rng)?;
+	}
 	
 	for _row in 0..num_rows {
 		let mut fields: Vec<i32> = vec![0; num_cols];
-		for col in 0..num_cols {
+		for (field, sampler) in fields.iter_mut().zip(samplers.iter_mut()) {
 			// In order to do .abs() we cannot have i32::MIN in the table
-			let field = rng.gen_range(i32::MIN+1..=i32::MAX);
-			fields[col] = field;
+			*field = sampler.next(rng);
 		}
 		let record = Record::new(fields.as_slice());
 		table.push(record);
 	}
 	assert!(table.len() == num_rows);
 	assert!(table[0].get_num_columns() == num_cols);
-	table
+	Ok(table)
 }
 
-pub fn write_table(table: &Vec<Record>, path: &str) -> () {
-	let mut writer = Writer::from_path(path).unwrap();
-	let num_cols: usize = table.get(0).unwrap().get_num_columns();
-	
-	// Write header
-	let mut header: Vec<String> = Vec::with_capacity(num_cols);
-	for col in 1..=num_cols {
-		let mut column_name: String = "col".to_owned();
-		column_name.push_str(&col.to_string());
-		header.push(column_name);
-	}
-	match writer.write_record(header) {
-		Err(e) => panic!("Oh no {:?}", e),
-		Ok(_) => {}
-	}
-	writer.flush().unwrap();
-
-	// Write records
-	for r in table {
-		let column_values: Vec<String> = r.get_column_values().into_iter().map(i32::to_string).collect();
-		match writer.write_record(&column_values) {
-			Err(e) => panic!("Oh no {:?}", e),
-			Ok(_) => {}
+// Makes every key appear in exactly fanout rows (the last key may
+// appear in fewer) by copying the key columns of the first row of
+// each group of fanout rows onto the rest of the group
+pub fn repeat_keys<R: Rng>(table: &mut [Record], key_columns: &[usize], fanout: usize, rng: &mut R) {
+	assert!(fanout > 0, "Every key needs at least one row");
+	for group in table.chunks_mut(fanout) {
+		let (first, rest) = group.split_first_mut().unwrap();
+		for record in rest {
+			for &col in key_columns {
+				record.set_column(col, *first.get_column(col));
+			}
 		}
 	}
-	writer.flush().unwrap();
+	table.shuffle(rng);
+}
+
+// Columnar binary when the path ends in .bin, a record table for
+// .rec, Arrow IPC for .arrow, Parquet for .parquet, CSV otherwise
+pub fn write_table(table: &[Record], path: &str) {
+	let num_cols: usize = table.first().unwrap().get_num_columns();
+	write_table_with_names(table, &default_column_names(num_cols), path);
+}
+
+pub fn write_table_with_names(table: &[Record], column_names: &[String], path: &str) {
+	if let Err(e) = joinlib::table::write_table(path, &Schema::int32(column_names), table) {
+		panic!("Oh no {:?}", e);
+	}
 }
 
-pub fn generate_and_write_table(num_rows: usize, num_cols: usize, path: &str) -> () {
-	let table: Vec<Record> = generate_table(num_rows, num_cols);
+// Converts between table formats, keeping the column names and types.
+// The input format is detected, the output format is chosen by
+// the output extension. CSV inputs are read with the given options,
+// and the errors of the rows they skip are returned
+pub fn convert_table(input: &str, output: &str, csv_options: &CsvOptions) -> Result<(usize, Vec<CsvError>), String> {
+	let (schema, records, skipped) = if is_binary_table(input) || arrow_format(input).is_some() {
+		let (schema, records) = read_table(input).map_err(|e| format!("Could not read {:?} {}", input, e))?;
+		(schema, records, Vec::new())
+	} else {
+		let table = read_csv_table(input, csv_options).map_err(|e| format!("Could not read {}", e))?;
+		(table.schema, table.records, table.skipped)
+	};
+	joinlib::table::write_table(output, &schema, &records).map_err(|e| format!("Could not write {:?} {}", output, e))?;
+	Ok((records.len(), skipped))
+}
+
+pub fn generate_and_write_table<R: Rng>(num_rows: usize, num_cols: usize, path: &str, rng: &mut R) {
+	let table: Vec<Record> = generate_table(num_rows, num_cols, rng);
 	write_table(&table, path);
 }
 
-// given a left table, make new table based on following:
-// left table: Vec<Record>
-// num_rows: i32
-// num_cols: i32
-// join selectivity: f32
-
-// helper data structure: missing_value_picker(target_col_values)
-// which takes in a sorted list of numbers
-// and exposes a next() which returns a number that is guaranteed
-// to not be in the inputted list
-
-// iterate over left and get a sorted list of the target_col_values
-// matches = selectivity * (num_left_rows * num_right_rows)
-//		is the number of records we wish to produce in the right table
-//		which will satisfy the join. There will be other records
-// create matches # of rows in the right table by randomly choose from target_col_values
-// for remaining rows, get value from missing_value_picker(target_col_values) and use that as col value
-
-fn get_table_column_values(table: &Vec<Record>, col: usize) -> HashSet<i32> {
-	let mut cols = HashSet::new();
-	for record in table {
-		let val = *record.get_column(col);
-		cols.insert(val);
-	}
-	cols
-}
-
-pub fn generate_right_table(left_table: Vec<Record>, 
-														num_rows: usize, 
-														num_cols: usize, 
-														selectivity: f64,
-														left_col: usize,
-														right_col: usize) -> Vec<Record> {
-	assert!((left_table.len() as f64) * selectivity <= num_rows as f64);
-
-  // Normalize the number of records that the join should produce
-	let matches: f64 = (left_table.len() as f64) * selectivity;
-  let rounded_matches: usize = matches.floor() as usize; // TODO problem due to precision
-	println!("rounded matches: {:?}", rounded_matches);
-
-	// Assert num_rows >= matches
-	assert!(num_rows as f64 >= matches);
-	assert!(num_rows >= rounded_matches);
-
-  // Construct the missing value picker
-	let left_col_set: HashSet<i32> = get_table_column_values(&left_table, left_col);
+// How much of the join should match, measured one of three ways
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchTarget {
+	// Share of the left rows which join with at least one right row
+	LeftRows(f64),
+	// Share of the right rows which join with at least one left row
+	RightRows(f64),
+	// Exact number of rows the join outputs
+	OutputRows(usize),
+}
+
+// What a generated right table looks like
+#[derive(Debug, Clone)]
+pub struct RightTableSpec {
+	pub num_rows: usize,
+	pub num_cols: usize,
+	pub target: MatchTarget,
+	pub key_columns: Vec<(usize, usize)>,
+	// Right rows per matched left key
+	pub fanout: usize,
+	pub hot_key_share: f64,
+	pub foreign_keys: KeyDistribution,
+}
+
+// Size of an equi-join, counted from its inputs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct JoinCardinality {
+	pub output_rows: usize,
+	pub matched_left_rows: usize,
+	pub matched_right_rows: usize,
+}
+
+fn key_of(record: &Record, columns: impl Iterator<Item = usize>) -> Vec<i32> {
+	columns.map(|c| *record.get_column(c)).collect()
+}
+
+// Counts the join output without running a join: each right row
+// outputs as many rows as there are left rows with its key
+pub fn join_cardinality(left_table: &[Record], right_table: &[Record], key_columns: &[(usize, usize)]) -> JoinCardinality {
+	let mut left_counts: HashMap<Vec<i32>, usize> = HashMap::new();
+	for lr in left_table {
+		*left_counts.entry(key_of(lr, key_columns.iter().map(|p| p.0))).or_default() += 1;
+	}
+
+	let mut cardinality = JoinCardinality::default();
+	let mut matched_keys: HashSet<Vec<i32>> = HashSet::new();
+	for rr in right_table {
+		let key = key_of(rr, key_columns.iter().map(|p| p.1));
+		if let Some(count) = left_counts.get(&key) {
+			cardinality.output_rows += count;
+			cardinality.matched_right_rows += 1;
+			matched_keys.insert(key);
+		}
+	}
+	cardinality.matched_left_rows = matched_keys.iter().map(|k| left_counts[k]).sum();
+	cardinality
+}
+
+// Distinct keys of the left table in order of first appearance,
+// along with the number of left rows having each of them
+struct LeftKeys {
+	records: Vec<usize>,
+	counts: Vec<usize>,
+}
+
+impl LeftKeys {
+	fn new(left_table: &[Record], key_columns: &[(usize, usize)]) -> LeftKeys {
+		let mut index: HashMap<Vec<i32>, usize> = HashMap::new();
+		let mut keys = LeftKeys { records: Vec::new(), counts: Vec::new() };
+		for (row, lr) in left_table.iter().enumerate() {
+			let next = keys.records.len();
+			let k = *index.entry(key_of(lr, key_columns.iter().map(|p| p.0))).or_insert(next);
+			if k == next {
+				keys.records.push(row);
+				keys.counts.push(0);
+			}
+			keys.counts[k] += 1;
+		}
+		keys
+	}
+
+	fn len(&self) -> usize {
+		self.records.len()
+	}
+}
+
+// Spreads num_rows matching right rows over the chosen keys, giving
+// every key at least one. Sequential foreign keys spread them evenly,
+// other distributions draw the key of each further row
+fn spread_rows<R: Rng>(chosen: &[usize], num_rows: usize, foreign_keys: &KeyDistribution, rng: &mut R) -> Result<Vec<usize>, String> {
+	assert!(num_rows >= chosen.len());
+	let mut sampler = ValueSampler::new(foreign_keys, rng)?;
+	Ok((0..num_rows)
+		.map(|i| match foreign_keys {
+			KeyDistribution::Sequential { distinct: None } => chosen[i % chosen.len()],
+			_ if i < chosen.len() => chosen[i],
+			_ => chosen[(sampler.next(rng) as i64).rem_euclid(chosen.len() as i64) as usize],
+		})
+		.collect())
+}
+
+// Takes up to max_copies of each key in order while they fit, where a
+// copy of a key adds its count, until exactly target is reached.
+// Returns (key, copies) pairs, or None when target was not hit
+fn fill_target(order: &[usize], counts: &[usize], target: usize, max_copies: usize) -> Option<Vec<(usize, usize)>> {
+	let mut taken: Vec<(usize, usize)> = Vec::new();
+	let mut remaining = target;
+	for &k in order {
+		if remaining == 0 {
+			break;
+		}
+		let copies = max_copies.min(remaining / counts[k]);
+		if copies > 0 {
+			taken.push((k, copies));
+			remaining -= copies * counts[k];
+		}
+	}
+	if remaining == 0 { Some(taken) } else { None }
+}
+
+// The left key of each matching right row. Keys are taken in a random
+// order while they fit the target. When duplicate left keys make that
+// miss the target, keys are taken from the most duplicated down, which
+// leaves the small keys to make up the rest
+fn plan_matches<R: Rng>(keys: &LeftKeys, num_left_rows: usize, spec: &RightTableSpec, rng: &mut R) -> Result<Vec<usize>, String> {
+	let mut order: Vec<usize> = (0..keys.len()).collect();
+	order.shuffle(rng);
+	let fill = |target: usize, max_copies: usize| {
+		fill_target(&order, &keys.counts, target, max_copies).or_else(|| {
+			let mut largest_first = order.clone();
+			largest_first.sort_by_key(|k| std::cmp::Reverse(keys.counts[*k]));
+			fill_target(&largest_first, &keys.counts, target, max_copies)
+		})
+	};
+
+	match spec.target {
+		MatchTarget::LeftRows(share) => {
+			let target = (num_left_rows as f64 * share).round() as usize;
+			let chosen: Vec<usize> = match fill(target, 1) {
+				None => return Err(format!("No set of left keys covers exactly {} left rows", target)),
+				Some(taken) => taken.iter().map(|t| t.0).collect(),
+			};
+			if chosen.is_empty() {
+				return Ok(Vec::new());
+			}
+			spread_rows(&chosen, chosen.len() * spec.fanout, &spec.foreign_keys, rng)
+		},
+		MatchTarget::RightRows(share) => {
+			let target = (spec.num_rows as f64 * share).round() as usize;
+			let num_keys = target.div_ceil(spec.fanout);
+			if num_keys > keys.len() {
+				return Err(format!("{} matching right rows need {} left keys, but there are {}", target, num_keys, keys.len()));
+			}
+			if num_keys == 0 {
+				return Ok(Vec::new());
+			}
+			spread_rows(&order[..num_keys], target, &spec.foreign_keys, rng)
+		},
+		MatchTarget::OutputRows(target) => {
+			// Output sizes only add up for an even fan-out
+			if spec.foreign_keys != (KeyDistribution::Sequential { distinct: None }) {
+				return Err("An exact output size needs sequential foreign keys".to_owned());
+			}
+			match fill(target, spec.fanout) {
+				None => Err(format!("Cannot output exactly {} rows with a fan-out of {} per left key", target, spec.fanout)),
+				Some(taken) => Ok(taken.iter().flat_map(|&(k, copies)| std::iter::repeat_n(k, copies)).collect()),
+			}
+		},
+	}
+}
+
+// Gives hot_key_share of the matching rows the first planned key,
+// while every other planned key keeps at least one row, so the same
+// left rows match. When the other keys do not fit in the rows left
+// over, this takes more matching rows than planned
+fn add_hot_key(matches: &[usize], hot_key_share: f64) -> Result<Vec<usize>, String> {
+	let hot_key = matches[0];
+	let mut seen: HashSet<usize> = HashSet::from([hot_key]);
+	let (firsts, extras): (Vec<usize>, Vec<usize>) = matches.iter()
+		.filter(|k| **k != hot_key)
+		.partition(|k| seen.insert(**k));
+
+	let mut total = matches.len();
+	let mut hot_rows = ((total as f64) * hot_key_share).floor() as usize;
+	if hot_rows + firsts.len() > total {
+		if hot_key_share >= 1.0 {
+			return Err(format!("A hot key with all matching rows leaves none for the other {} matched keys", firsts.len()));
+		}
+		// The fewest rows in which the others fit next to the hot key
+		total = ((firsts.len() as f64) / (1.0 - hot_key_share)).ceil() as usize;
+		hot_rows = total - firsts.len();
+	}
+
+	let mut planned: Vec<usize> = vec![hot_key; hot_rows];
+	planned.extend(firsts);
+	let room = total - planned.len();
+	planned.extend(extras.into_iter().take(room));
+	planned.resize(total, hot_key);
+	Ok(planned)
+}
+
+// Join key columns are given as (left column, right column) pairs.
+// A composite key matches only when every pair matches, so each
+// non-matching right row copies the leading key columns of some random
+// left row and only breaks the match on the last key column. This way
+// the match target is still exact, yet the join cannot get away with
+// comparing just a prefix of the key.
+//
+// Left keys may repeat, and each matched left key gets fanout right
+// rows, so the join is many-to-many in general. Which keys match is
+// random; foreign_keys other than sequential make the matching right
+// rows reference a few of the matched keys more than the others.
+//
+// A hot_key_share of the matching rows all take the key of a single
+// left key, which makes that key a heavy hitter on the right. For a
+// share of the left rows the other matched keys keep a row each, and
+// otherwise the hot key may take the rows of a few of them.
+//
+// Returns the table along with the size of its join with the left table
+pub fn generate_right_table<R: Rng>(left_table: &[Record], spec: &RightTableSpec, rng: &mut R) -> Result<(Vec<Record>, JoinCardinality), String> {
+	assert!(!left_table.is_empty());
+	assert!(!spec.key_columns.is_empty());
+	assert!(spec.fanout > 0, "Matched keys need at least one right row");
+	if !(0.0..=1.0).contains(&spec.hot_key_share) {
+		return Err(format!("The hot key cannot take a share of {} of the matching rows", spec.hot_key_share));
+	}
+	check_key_columns(spec.key_columns.iter().map(|p| p.0), left_table[0].get_num_columns())?;
+	check_key_columns(spec.key_columns.iter().map(|p| p.1), spec.num_cols)?;
+	let key_columns: &[(usize, usize)] = &spec.key_columns;
+
+	let keys = LeftKeys::new(left_table, key_columns);
+	let mut matches: Vec<usize> = plan_matches(&keys, left_table.len(), spec, rng)?;
+	let hot_rows: usize = ((matches.len() as f64) * spec.hot_key_share).floor() as usize;
+	if hot_rows > 0 {
+		if let MatchTarget::LeftRows(_) = spec.target {
+			matches = add_hot_key(&matches, spec.hot_key_share)?;
+		} else {
+			let hot_key = matches[0];
+			for k in matches.iter_mut().take(hot_rows) {
+				*k = hot_key;
+			}
+		}
+	}
+	if matches.len() > spec.num_rows {
+		return Err(format!("{} right rows cannot hold the {} matching rows", spec.num_rows, matches.len()));
+	}
+
+	let cardinality = JoinCardinality {
+		output_rows: matches.iter().map(|k| keys.counts[*k]).sum(),
+		matched_left_rows: matches.iter().collect::<HashSet<_>>().into_iter().map(|k| keys.counts[*k]).sum(),
+		matched_right_rows: matches.len(),
+	};
+	if let MatchTarget::OutputRows(target) = spec.target {
+		if cardinality.output_rows != target {
+			return Err(format!("The hot key changes the output from {} to {} rows", target, cardinality.output_rows));
+		}
+	}
+	// The last key column is the one which breaks non-matches
+	let (leading_columns, last_column) = key_columns.split_at(key_columns.len() - 1);
+	let (last_left_col, last_right_col) = last_column[0];
+
+	// Construct the missing value picker
+	let left_col_set: HashSet<i32> = left_table.iter().map(|lr| *lr.get_column(last_left_col)).collect();
 	let mut mvp = MissingValuePicker::new(left_col_set);
-	
-  // Generate a random right table
-	let mut right_table: Vec<Record> = generate_table(num_rows, num_cols);
-
-  // Fill in enough matching values in the right table join column
-  // to achieve the desired level of selectivity
-  for i in 0..rounded_matches {
-    let value: i32 = *left_table.get(i).unwrap().get_column(left_col);
-    let right_record: &mut Record = &mut right_table[i];
-    right_record.set_column(right_col, value);
-		assert!(*right_table.get(i).unwrap().get_column(right_col) == value);
-  }
+
+	// Generate a random right table
+	let mut right_table: Vec<Record> = generate_table(spec.num_rows, spec.num_cols, rng);
+
+	// Fill in the planned matching keys
+	for (right_record, k) in right_table.iter_mut().zip(matches.iter()) {
+		let left_record = &left_table[keys.records[*k]];
+		for &(left_col, right_col) in key_columns {
+			right_record.set_column(right_col, *left_record.get_column(left_col));
+		}
+	}
 
 	// Now fill in the remaing values from the missing value picker
-	for i in rounded_matches..num_rows {
-    // Set the join column value on the right table
-    // to be some value not in the left table join column
-    let value: i32 = mvp.next();
-		let right_record: &mut Record = &mut right_table[i];
-    right_record.set_column(right_col, value);
-		assert!(*right_table.get(i).unwrap().get_column(right_col) == value);
-	}
-
-  // Rerandomize right table so join results are not just
-  // at the top of the table
-	right_table.shuffle(&mut thread_rng());
-	
-	right_table
+	for right_record in right_table.iter_mut().skip(matches.len()) {
+		// Leading key columns agree with some left record
+		let left_record: &Record = left_table.choose(rng).unwrap();
+		for &(left_col, right_col) in leading_columns {
+			right_record.set_column(right_col, *left_record.get_column(left_col));
+		}
+
+		// Set the last join column value on the right table
+		// to be some value not in the left table join column
+		let value: i32 = mvp.next(rng);
+		right_record.set_column(last_right_col, value);
+		assert!(*right_record.get_column(last_right_col) == value);
+	}
+
+	// Rerandomize right table so join results are not just
+	// at the top of the table
+	right_table.shuffle(rng);
+
+	Ok((right_table, cardinality))
 }
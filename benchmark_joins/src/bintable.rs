@@ -0,0 +1,671 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::cmp::min;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem::{align_of, size_of};
+use std::slice;
+
+use memmap2::{Mmap, MmapMut, MmapOptions};
+
+use crate::record::{Record, RECORD_BYTES};
+use crate::schema::{Column, ColumnType, Schema, Value};
+
+// Binary tables, all little-endian:
+//
+//   magic          8 bytes, JOINTBL\0 or JOINREC\0
+//   version        u32
+//   num_columns    u32
+//   num_rows       u64
+//   schema         per column: type u8, name length u16, UTF-8 name
+//   padding        zeros up to a multiple of DATA_ALIGN bytes
+//   rows           laid out as below
+//   checksum       u64, FNV-1a of every byte before it
+//
+// Columnar tables (JOINTBL\0, .bin) store per column num_rows values
+// of its type, i32, i64 or f64, zero-padded to a multiple of 8 bytes,
+// then its NULL bitmap of num_rows.div_ceil(64) u64 words. Bit i % 64
+// of word i / 64 is set when row i is NULL, whose value is then 0.
+// Tables of only i32 columns are memory-mapped and read in place
+// as columns. Record tables (JOINREC\0, .rec) store num_rows records
+// of RECORD_BYTES each, laid out like Record in memory so that row
+// operators can map them and read them in place too, which columns
+// cannot give them. Both layouts share the header and checksum, and
+// differ only in the rows. Utf8 columns hold ids which
+// only mean something in the process that wrote them, so neither
+// layout stores them
+pub const MAGIC: &[u8; 8] = b"JOINTBL\0";
+pub const RECORDS_MAGIC: &[u8; 8] = b"JOINREC\0";
+pub const VERSION: u32 = 2;
+pub const EXTENSION: &str = "bin";
+pub const RECORDS_EXTENSION: &str = "rec";
+
+const TYPE_I32: u8 = 0;
+const TYPE_I64: u8 = 1;
+const TYPE_F64: u8 = 2;
+const VALUE_BYTES: u64 = 4;
+const WIDE_VALUE_BYTES: u64 = 8;
+const WORD_BYTES: u64 = 8;
+const DATA_ALIGN: usize = 64;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// Bytes read or written at a time
+const IO_BUFFER: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+  Columns,
+  Records,
+}
+
+// FNV-1a hash of every byte of a binary table before its checksum
+#[derive(Clone, Copy)]
+struct Checksum(u64);
+
+impl Checksum {
+  fn new() -> Checksum {
+    Checksum(FNV_OFFSET)
+  }
+
+  fn update(&mut self, bytes: &[u8]) {
+    for b in bytes {
+      self.0 ^= *b as u64;
+      self.0 = self.0.wrapping_mul(FNV_PRIME);
+    }
+  }
+
+  fn to_le_bytes(self) -> [u8; 8] {
+    self.0.to_le_bytes()
+  }
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+  std::path::Path::new(path).extension().is_some_and(|e| e == extension)
+}
+
+// A path ending in .bin
+pub fn is_binary_path(path: &str) -> bool {
+  has_extension(path, EXTENSION)
+}
+
+// A path ending in .rec
+pub fn is_record_path(path: &str) -> bool {
+  has_extension(path, RECORDS_EXTENSION)
+}
+
+// Layout of a binary table from its magic bytes,
+// or None for other files such as CSV tables
+pub fn binary_layout(path: &str) -> Option<Layout> {
+  let mut magic = [0u8; 8];
+  let mut f = File::open(path).ok()?;
+  f.read_exact(&mut magic).ok()?;
+  match &magic {
+    MAGIC => Some(Layout::Columns),
+    RECORDS_MAGIC => Some(Layout::Records),
+    _ => None,
+  }
+}
+
+// Whether the file is a binary table of either layout
+pub fn is_binary_table(path: &str) -> bool {
+  binary_layout(path).is_some()
+}
+
+// Whether record table rows can be read in place as Records,
+// which holds on 64-bit little-endian targets
+pub fn can_map_records() -> bool {
+  cfg!(target_endian = "little")
+    && size_of::<Record>() == RECORD_BYTES
+    && DATA_ALIGN.is_multiple_of(align_of::<Record>())
+}
+
+// Whether the columns of a columnar table of this schema can be
+// read in place as i32 slices, which holds on little-endian targets
+// when every column is an i32
+pub fn can_map_columns(schema: &Schema) -> bool {
+  cfg!(target_endian = "little") && schema.is_int32()
+}
+
+// The default col1, col2, ... column names of generated tables
+pub fn default_column_names(num_columns: usize) -> Vec<String> {
+  (1..=num_columns).map(|c| format!("col{}", c)).collect()
+}
+
+fn type_code(column: &Column) -> Result<u8, Box<dyn Error>> {
+  match column.column_type {
+    ColumnType::Int32 => Ok(TYPE_I32),
+    ColumnType::Int64 => Ok(TYPE_I64),
+    ColumnType::Float64 => Ok(TYPE_F64),
+    ColumnType::Utf8 => Err(format!("Column {:?} is utf8, which binary tables cannot store", column.name).into()),
+  }
+}
+
+fn column_type(code: u8) -> Option<ColumnType> {
+  match code {
+    TYPE_I32 => Some(ColumnType::Int32),
+    TYPE_I64 => Some(ColumnType::Int64),
+    TYPE_F64 => Some(ColumnType::Float64),
+    _ => None,
+  }
+}
+
+fn value_bytes(column_type: ColumnType) -> u64 {
+  match column_type {
+    ColumnType::Int32 => VALUE_BYTES,
+    _ => WIDE_VALUE_BYTES,
+  }
+}
+
+// Bytes of a column's values, padded so that its NULL bitmap
+// starts on a word
+fn values_bytes(column_type: ColumnType, num_rows: usize) -> u64 {
+  (num_rows as u64 * value_bytes(column_type)).next_multiple_of(WORD_BYTES)
+}
+
+fn null_bitmap_bytes(num_rows: usize) -> u64 {
+  num_rows.div_ceil(64) as u64 * WORD_BYTES
+}
+
+// Offset of each column's values from the first row, and
+// of the end of the last column
+fn column_offsets(schema: &Schema, num_rows: usize) -> Vec<u64> {
+  let mut offsets: Vec<u64> = vec![0];
+  for c in schema.columns() {
+    offsets.push(offsets[offsets.len() - 1] + values_bytes(c.column_type, num_rows) + null_bitmap_bytes(num_rows));
+  }
+  offsets
+}
+
+fn header(layout: Layout, schema: &Schema, num_rows: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+  let mut bytes: Vec<u8> = Vec::new();
+  bytes.extend_from_slice(match layout {
+    Layout::Columns => MAGIC,
+    Layout::Records => RECORDS_MAGIC,
+  });
+  bytes.extend_from_slice(&VERSION.to_le_bytes());
+  bytes.extend_from_slice(&(schema.num_columns() as u32).to_le_bytes());
+  bytes.extend_from_slice(&(num_rows as u64).to_le_bytes());
+  for column in schema.columns() {
+    bytes.push(type_code(column)?);
+    bytes.extend_from_slice(&(column.name.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(column.name.as_bytes());
+  }
+  bytes.resize(bytes.len().div_ceil(DATA_ALIGN) * DATA_ALIGN, 0);
+  Ok(bytes)
+}
+
+// Values of one column of the records, as stored in the file,
+// with NULLs as 0. Their bits are set by BinaryTableWriter
+pub fn encode_column(schema: &Schema, records: &[Record], column: usize) -> Vec<u8> {
+  let column_type = schema.column_type(column);
+  let mut bytes: Vec<u8> = Vec::with_capacity(records.len() * value_bytes(column_type) as usize);
+  if column_type == ColumnType::Int32 {
+    let slot = schema.slots(column).start;
+    for r in records {
+      bytes.extend_from_slice(&r.get_column(slot).to_le_bytes());
+    }
+    return bytes;
+  }
+  for r in records {
+    match r.get_value(schema, column) {
+      Value::Int64(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+      Value::Float64(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+      _ => bytes.extend_from_slice(&[0; WIDE_VALUE_BYTES as usize]),
+    }
+  }
+  bytes
+}
+
+// Records as stored in a record table
+pub fn encode_records(records: &[Record]) -> Vec<u8> {
+  let mut bytes: Vec<u8> = Vec::with_capacity(records.len() * RECORD_BYTES);
+  for r in records {
+    r.write_bytes(&mut bytes);
+  }
+  bytes
+}
+
+// Writes a table whose number of rows is known up front. Rows may
+// be written in any order as blocks of columns, so that a generator
+// can fill the columns a chunk of rows at a time. The file starts
+// zeroed, so rows are not NULL unless written as records with NULLs.
+// The checksum is computed on finish by reading the file back
+pub struct BinaryTableWriter {
+  file: File,
+  path: String,
+  schema: Schema,
+  num_rows: usize,
+  data_start: u64,
+  column_offsets: Vec<u64>,
+}
+
+impl BinaryTableWriter {
+  pub fn create(path: &str, schema: &Schema, num_rows: usize) -> Result<BinaryTableWriter, Box<dyn Error>> {
+    let header = header(Layout::Columns, schema, num_rows)?;
+    let data_start = header.len() as u64;
+    let column_offsets = column_offsets(schema, num_rows);
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.write_all(&header)?;
+    file.set_len(data_start + column_offsets[schema.num_columns()])?;
+    Ok(BinaryTableWriter {
+      file,
+      path: path.to_owned(),
+      schema: schema.clone(),
+      num_rows,
+      data_start,
+      column_offsets,
+    })
+  }
+
+  // Writes encoded values of a column, starting at first_row
+  pub fn write_column_block(&mut self, column: usize, first_row: usize, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    if column >= self.schema.num_columns() {
+      return Err(format!("{:?} has no column {}", self.path, column).into());
+    }
+    let width = value_bytes(self.schema.column_type(column));
+    let rows = bytes.len() / width as usize;
+    if first_row + rows > self.num_rows {
+      return Err(format!("Rows {}..{} of column {} are outside of {:?}", first_row, first_row + rows, column, self.path).into());
+    }
+    let offset = self.data_start + self.column_offsets[column] + first_row as u64 * width;
+    self.file.seek(SeekFrom::Start(offset))?;
+    self.file.write_all(bytes)?;
+    Ok(())
+  }
+
+  pub fn write_records(&mut self, first_row: usize, records: &[Record]) -> Result<(), Box<dyn Error>> {
+    for column in 0..self.schema.num_columns() {
+      let bytes = encode_column(&self.schema, records, column);
+      self.write_column_block(column, first_row, &bytes)?;
+      self.write_nulls(column, first_row, records)?;
+    }
+    Ok(())
+  }
+
+  // Sets the NULL bits of the records which are NULL in the column.
+  // The bytes at either end may be shared with neighbouring blocks,
+  // so the bitmap is read, updated and written back
+  fn write_nulls(&mut self, column: usize, first_row: usize, records: &[Record]) -> Result<(), Box<dyn Error>> {
+    let slot = self.schema.slots(column).start;
+    if !records.iter().any(|r| r.is_null(slot)) {
+      return Ok(());
+    }
+    let first_byte = first_row / 8;
+    let mut bitmap = vec![0u8; (first_row + records.len()).div_ceil(8) - first_byte];
+    let offset = self.data_start
+      + self.column_offsets[column]
+      + values_bytes(self.schema.column_type(column), self.num_rows)
+      + first_byte as u64;
+    self.file.seek(SeekFrom::Start(offset))?;
+    self.file.read_exact(&mut bitmap)?;
+    for (i, _) in records.iter().enumerate().filter(|(_, r)| r.is_null(slot)) {
+      let bit = first_row + i - first_byte * 8;
+      bitmap[bit / 8] |= 1 << (bit % 8);
+    }
+    self.file.seek(SeekFrom::Start(offset))?;
+    self.file.write_all(&bitmap)?;
+    Ok(())
+  }
+
+  pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+    self.file.seek(SeekFrom::Start(0))?;
+    let mut checksum = Checksum::new();
+    let mut buf = vec![0u8; IO_BUFFER];
+    loop {
+      let n = self.file.read(&mut buf)?;
+      if n == 0 {
+        break;
+      }
+      checksum.update(&buf[..n]);
+    }
+    self.file.write_all(&checksum.to_le_bytes())?;
+    self.file.flush()?;
+    Ok(())
+  }
+}
+
+pub fn write_binary_table(path: &str, schema: &Schema, records: &[Record]) -> Result<(), Box<dyn Error>> {
+  let mut writer = BinaryTableWriter::create(path, schema, records.len())?;
+  writer.write_records(0, records)?;
+  writer.finish()
+}
+
+// Writes a record table front to back, hashing
+// the checksum along the way
+pub struct RecordTableWriter {
+  out: BufWriter<File>,
+  path: String,
+  num_rows: usize,
+  rows_written: usize,
+  checksum: Checksum,
+}
+
+impl RecordTableWriter {
+  pub fn create(path: &str, schema: &Schema, num_rows: usize) -> Result<RecordTableWriter, Box<dyn Error>> {
+    let header = header(Layout::Records, schema, num_rows)?;
+    let mut out = BufWriter::with_capacity(IO_BUFFER, File::create(path)?);
+    out.write_all(&header)?;
+    let mut checksum = Checksum::new();
+    checksum.update(&header);
+    Ok(RecordTableWriter {
+      out,
+      path: path.to_owned(),
+      num_rows,
+      rows_written: 0,
+      checksum,
+    })
+  }
+
+  // Appends rows encoded by encode_records
+  pub fn write_encoded(&mut self, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    let rows = bytes.len() / RECORD_BYTES;
+    if !bytes.len().is_multiple_of(RECORD_BYTES) || self.rows_written + rows > self.num_rows {
+      return Err(format!("{} bytes of records do not fit in {:?}", bytes.len(), self.path).into());
+    }
+    self.out.write_all(bytes)?;
+    self.checksum.update(bytes);
+    self.rows_written += rows;
+    Ok(())
+  }
+
+  pub fn write_records(&mut self, records: &[Record]) -> Result<(), Box<dyn Error>> {
+    self.write_encoded(&encode_records(records))
+  }
+
+  pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+    if self.rows_written != self.num_rows {
+      return Err(format!("Wrote {} of the {} rows of {:?}", self.rows_written, self.num_rows, self.path).into());
+    }
+    self.out.write_all(&self.checksum.to_le_bytes())?;
+    self.out.flush()?;
+    Ok(())
+  }
+}
+
+pub fn write_record_table(path: &str, schema: &Schema, records: &[Record]) -> Result<(), Box<dyn Error>> {
+  let mut writer = RecordTableWriter::create(path, schema, records.len())?;
+  for block in records.chunks(IO_BUFFER / RECORD_BYTES) {
+    writer.write_records(block)?;
+  }
+  writer.finish()
+}
+
+// Reads exactly buf.len() bytes, adding them to the checksum
+struct ChecksumReader {
+  reader: BufReader<File>,
+  checksum: Checksum,
+}
+
+impl ChecksumReader {
+  fn read(&mut self, buf: &mut [u8]) -> Result<(), Box<dyn Error>> {
+    self.reader.read_exact(buf)?;
+    self.checksum.update(buf);
+    Ok(())
+  }
+
+  fn read_u16(&mut self) -> Result<u16, Box<dyn Error>> {
+    let mut b = [0u8; 2];
+    self.read(&mut b)?;
+    Ok(u16::from_le_bytes(b))
+  }
+
+  fn read_u32(&mut self) -> Result<u32, Box<dyn Error>> {
+    let mut b = [0u8; 4];
+    self.read(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+  }
+
+  fn read_u64(&mut self) -> Result<u64, Box<dyn Error>> {
+    let mut b = [0u8; 8];
+    self.read(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+  }
+}
+
+struct Header {
+  layout: Layout,
+  schema: Schema,
+  num_rows: usize,
+  // Bytes before the first row
+  data_start: u64,
+}
+
+fn read_header(reader: &mut ChecksumReader, path: &str) -> Result<Header, Box<dyn Error>> {
+  let mut magic = [0u8; 8];
+  reader.read(&mut magic)?;
+  let layout = match &magic {
+    MAGIC => Layout::Columns,
+    RECORDS_MAGIC => Layout::Records,
+    _ => return Err(format!("{:?} is not a binary table", path).into()),
+  };
+  let version = reader.read_u32()?;
+  if version != VERSION {
+    return Err(format!("{:?} has version {}, expected {}", path, version, VERSION).into());
+  }
+  let num_columns = reader.read_u32()? as usize;
+  let num_rows = reader.read_u64()? as usize;
+
+  let mut columns: Vec<Column> = Vec::with_capacity(num_columns);
+  let mut data_start: u64 = 8 + 4 + 4 + 8;
+  for _ in 0..num_columns {
+    let mut code = [0u8; 1];
+    reader.read(&mut code)?;
+    let column_type = match column_type(code[0]) {
+      Some(t) => t,
+      None => return Err(format!("{:?} has a column of unknown type {}", path, code[0]).into()),
+    };
+    let mut name = vec![0u8; reader.read_u16()? as usize];
+    reader.read(&mut name)?;
+    data_start += 1 + 2 + name.len() as u64;
+    columns.push(Column::new(&String::from_utf8(name)?, column_type));
+  }
+  let types: Vec<ColumnType> = columns.iter().map(|c| c.column_type).collect();
+  if !Schema::fits(&types) {
+    return Err(format!("{:?} has more columns than a record holds", path).into());
+  }
+  let schema = Schema::new(columns);
+
+  let padded = (data_start as usize).div_ceil(DATA_ALIGN) * DATA_ALIGN;
+  let mut padding = vec![0u8; padded - data_start as usize];
+  reader.read(&mut padding)?;
+  data_start = padded as u64;
+  Ok(Header { layout, schema, num_rows, data_start })
+}
+
+fn open(path: &str) -> Result<ChecksumReader, Box<dyn Error>> {
+  Ok(ChecksumReader {
+    reader: BufReader::with_capacity(IO_BUFFER, File::open(path)?),
+    checksum: Checksum::new(),
+  })
+}
+
+// Header of a binary table of the given layout, after checking
+// that the file holds all of its rows and its checksum, with the
+// file to map. The checksum itself is not checked, since that
+// takes reading the whole file
+fn map_header(path: &str, layout: Layout) -> Result<(Header, File), Box<dyn Error>> {
+  let mut reader = open(path)?;
+  let header = read_header(&mut reader, path)?;
+  if header.layout != layout {
+    return Err(format!("{:?} is not a {:?} binary table", path, layout).into());
+  }
+  let rows_bytes = match layout {
+    Layout::Columns => column_offsets(&header.schema, header.num_rows)[header.schema.num_columns()],
+    Layout::Records => (header.num_rows * RECORD_BYTES) as u64,
+  };
+  let file = reader.reader.into_inner();
+  if file.metadata()?.len() < header.data_start + rows_bytes + WORD_BYTES {
+    return Err(format!("{:?} is shorter than its {} rows", path, header.num_rows).into());
+  }
+  Ok((header, file))
+}
+
+// A binary table's schema and records, after checking its
+// header and checksum
+pub fn read_binary_table(path: &str) -> Result<(Schema, Vec<Record>), Box<dyn Error>> {
+  let mut reader = open(path)?;
+  let header = read_header(&mut reader, path)?;
+  let schema = &header.schema;
+  let mut buf = vec![0u8; IO_BUFFER];
+
+  let records: Vec<Record> = match header.layout {
+    Layout::Columns => {
+      // Fill in the records a column at a time, then their NULLs
+      let mut records: Vec<Record> = vec![Record::new(&vec![0; schema.num_slots()]); header.num_rows];
+      for column in 0..schema.num_columns() {
+        let column_type = schema.column_type(column);
+        let width = value_bytes(column_type) as usize;
+        for block in records.chunks_mut(IO_BUFFER / width) {
+          let bytes = &mut buf[..block.len() * width];
+          reader.read(bytes)?;
+          for (r, value) in block.iter_mut().zip(bytes.chunks_exact(width)) {
+            match column_type {
+              ColumnType::Int64 => r.set_value(schema, column, &Value::Int64(i64::from_le_bytes(value.try_into().unwrap()))),
+              ColumnType::Float64 => r.set_value(schema, column, &Value::Float64(f64::from_le_bytes(value.try_into().unwrap()))),
+              _ => r.set_column(schema.slots(column).start, i32::from_le_bytes(value.try_into().unwrap())),
+            }
+          }
+        }
+        let padding = values_bytes(column_type, header.num_rows) as usize - header.num_rows * width;
+        reader.read(&mut buf[..padding])?;
+
+        for block in records.chunks_mut(IO_BUFFER * 8) {
+          let bytes = &mut buf[..block.len().div_ceil(8)];
+          reader.read(bytes)?;
+          for (i, r) in block.iter_mut().enumerate() {
+            if bytes[i / 8] & (1 << (i % 8)) != 0 {
+              r.set_value(schema, column, &Value::Null);
+            }
+          }
+        }
+        let padding = null_bitmap_bytes(header.num_rows) as usize - header.num_rows.div_ceil(8);
+        reader.read(&mut buf[..padding])?;
+      }
+      records
+    },
+    Layout::Records => {
+      let mut records: Vec<Record> = Vec::with_capacity(header.num_rows);
+      while records.len() < header.num_rows {
+        let n = min(IO_BUFFER / RECORD_BYTES, header.num_rows - records.len());
+        let bytes = &mut buf[..n * RECORD_BYTES];
+        reader.read(bytes)?;
+        records.extend(bytes.chunks_exact(RECORD_BYTES).map(Record::from_bytes));
+      }
+      records
+    },
+  };
+
+  let computed = reader.checksum.to_le_bytes();
+  let mut checksum = [0u8; 8];
+  reader.reader.read_exact(&mut checksum)?;
+  if checksum != computed {
+    return Err(format!("Checksum of {:?} does not match its contents", path).into());
+  }
+  Ok((header.schema, records))
+}
+
+// The columns of a columnar table of only i32 columns, memory-mapped
+// read-only. Pages are read in on first touch and shared through the
+// page cache, so a scan of a few columns reads only those.
+// Mapping reads just the header, so the checksum is not checked
+pub struct MappedColumns {
+  map: Mmap,
+  num_rows: usize,
+  // Offset in the map of each column's values
+  offsets: Vec<usize>,
+  // Offset in the map of each column's NULL bitmap,
+  // or None when the column has no NULLs
+  nulls: Vec<Option<usize>>,
+}
+
+impl MappedColumns {
+  pub fn open(path: &str) -> Result<(Schema, MappedColumns), Box<dyn Error>> {
+    let (header, file) = map_header(path, Layout::Columns)?;
+    if !can_map_columns(&header.schema) {
+      return Err(format!("Only tables of i32 columns can be mapped on little-endian targets, not {:?}", path).into());
+    }
+    // Safety: the map is read-only, so it only changes if
+    // another process truncates or rewrites the file
+    let map = unsafe { MmapOptions::new().map(&file)? };
+
+    let num_rows = header.num_rows;
+    let column_offsets = column_offsets(&header.schema, num_rows);
+    let offsets: Vec<usize> = column_offsets[..header.schema.num_columns()].iter()
+      .map(|offset| (header.data_start + offset) as usize)
+      .collect();
+    let mut columns = MappedColumns { map, num_rows, offsets, nulls: Vec::new() };
+    columns.nulls = columns.offsets.iter()
+      .map(|offset| offset + values_bytes(ColumnType::Int32, num_rows) as usize)
+      .map(|offset| Some(offset).filter(|o| columns.words(*o).iter().any(|w| *w != 0)))
+      .collect();
+    Ok((header.schema, columns))
+  }
+
+  pub fn num_rows(&self) -> usize {
+    self.num_rows
+  }
+
+  pub fn num_columns(&self) -> usize {
+    self.offsets.len()
+  }
+
+  // Values of a column, where NULLs hold 0
+  pub fn column(&self, column: usize) -> &[i32] {
+    // Safety: the values are in bounds as checked by map_header, and
+    // aligned since the map is page aligned, the data starts at a
+    // multiple of DATA_ALIGN and every column at a multiple of 8
+    unsafe { slice::from_raw_parts(self.map.as_ptr().add(self.offsets[column]) as *const i32, self.num_rows) }
+  }
+
+  // NULL bitmap of a column, empty when it has no NULLs
+  pub fn null_words(&self, column: usize) -> &[u64] {
+    match self.nulls[column] {
+      Some(offset) => self.words(offset),
+      None => &[],
+    }
+  }
+
+  fn words(&self, offset: usize) -> &[u64] {
+    // Safety: as for column, and any bit pattern is a valid u64
+    unsafe { slice::from_raw_parts(self.map.as_ptr().add(offset) as *const u64, self.num_rows.div_ceil(64)) }
+  }
+}
+
+// The rows of a record table, memory-mapped copy-on-write. Pages are
+// read in on first touch and shared through the page cache, and writes
+// such as sorting copy only the pages they touch, never the file.
+// Mapping reads just the header, so the checksum is not checked
+pub struct MappedRecords {
+  map: MmapMut,
+  offset: usize,
+  len: usize,
+}
+
+impl MappedRecords {
+  pub fn open(path: &str) -> Result<(Schema, MappedRecords), Box<dyn Error>> {
+    if !can_map_records() {
+      return Err("Record tables can only be mapped on 64-bit little-endian targets".into());
+    }
+    let (header, file) = map_header(path, Layout::Records)?;
+    // Safety: the map is private, so it only changes if
+    // another process truncates or rewrites the file
+    let map = unsafe { MmapOptions::new().map_copy(&file)? };
+    let offset = header.data_start as usize;
+    Ok((header.schema, MappedRecords { map, offset, len: header.num_rows }))
+  }
+
+  pub fn as_slice(&self) -> &[Record] {
+    // Safety: the rows are in bounds as checked by map_header,
+    // aligned since the map is page aligned and offset a multiple
+    // of DATA_ALIGN, and laid out like Record, any of whose bit
+    // patterns is valid. A corrupt tail only makes the record
+    // panic when read
+    unsafe { slice::from_raw_parts(self.map.as_ptr().add(self.offset) as *const Record, self.len) }
+  }
+
+  pub fn as_mut_slice(&mut self) -> &mut [Record] {
+    // Safety: as for as_slice
+    unsafe { slice::from_raw_parts_mut(self.map.as_mut_ptr().add(self.offset) as *mut Record, self.len) }
+  }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::columnartable::ColumnarTable;
+use crate::join::{JoinAlgos, JoinIndex, JoinKind, JoinParams, NULL_ROW};
+use crate::key::JoinKeys;
+use crate::radixjoin::{DEFAULT_RADIX_BITS, MAX_RADIX_PASSES};
+
+// Common interface of joins over columnar tables. They match the
+// key columns a column at a time into a join index, and only then
+// gather the output columns from the rows it names
+pub trait ColumnarJoin {
+	// Which algorithm this operator implements
+	fn name(&self) -> JoinAlgos;
+
+	// Parameters the operator was created with
+	fn parameters(&self) -> JoinParams {
+		JoinParams::default()
+	}
+
+	// Row ids of the joined left and right rows
+	fn join_index(&mut self, keys: &JoinKeys) -> JoinIndex;
+
+	// Join the left and right tables on every pair of key columns
+	fn equi_join_on(&mut self, keys: &JoinKeys) -> ColumnarTable;
+}
+
+// Output columns of a join index: the left columns followed by
+// the right ones, or only the left ones for semi and anti joins
+pub fn gather_join_index(left: &ColumnarTable, right: &ColumnarTable, index: &JoinIndex, kind: JoinKind) -> ColumnarTable {
+	match kind.emits_pairs() {
+		true => left.gather(&index.left).merge(right.gather(&index.right)),
+		false => left.gather(&index.left),
+	}
+}
+
+// The key columns of one side, read a column at a time into a hash
+// per row. A single slot key hashes to its own value, so its low bits
+// pick the radix partition as in RadixJoin, and equal hashes are
+// equal keys. Composite keys are mixed, and compared slot by slot
+struct KeyColumns<'a> {
+	columns: Vec<&'a [i32]>,
+	hashes: Vec<u64>,
+	// Whether each row's key has no NULL slot, or None if no slot has NULLs
+	valid: Option<Vec<bool>>,
+}
+
+impl<'a> KeyColumns<'a> {
+	fn new(table: &'a ColumnarTable, slots: &[usize]) -> Self {
+		let columns: Vec<&[i32]> = slots.iter().map(|s| table.column(*s)).collect();
+		let mut hashes: Vec<u64> = columns[0].iter().map(|v| *v as u32 as u64).collect();
+		for column in &columns[1..] {
+			for (h, v) in hashes.iter_mut().zip(column.iter()) {
+				*h = (h.rotate_left(5) ^ (*v as u32 as u64)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+			}
+		}
+		let valid = match slots.iter().any(|s| table.has_nulls(*s)) {
+			true => Some((0..table.get_num_records()).map(|row| !slots.iter().any(|s| table.is_null(*s, row))).collect()),
+			false => None,
+		};
+		Self {
+			columns,
+			hashes,
+			valid,
+		}
+	}
+
+	fn is_null(&self, row: u32) -> bool {
+		self.valid.as_ref().is_some_and(|v| !v[row as usize])
+	}
+
+	// Whether the left key of row equals the right key of other_row
+	fn matches(&self, row: u32, other: &KeyColumns, other_row: u32) -> bool {
+		let (row, other_row) = (row as usize, other_row as usize);
+		self.hashes[row] == other.hashes[other_row] && (self.columns.len() == 1
+			|| self.columns.iter().zip(&other.columns).all(|(l, r)| l[row] == r[other_row]))
+	}
+}
+
+// Hash table over some rows of a side, chaining the rows of each
+// hash through next, in the order the rows were given
+struct HashIndex<'a> {
+	rows: &'a [u32],
+	heads: HashMap<u64, u32>,
+	next: Vec<u32>,
+}
+
+impl<'a> HashIndex<'a> {
+	fn build(keys: &KeyColumns, rows: &'a [u32]) -> Self {
+		let mut heads: HashMap<u64, u32> = HashMap::with_capacity(rows.len());
+		let mut next: Vec<u32> = vec![NULL_ROW; rows.len()];
+		for (position, &row) in rows.iter().enumerate().rev() {
+			if let Some(head) = heads.insert(keys.hashes[row as usize], position as u32) {
+				next[position] = head;
+			}
+		}
+		Self {
+			rows,
+			heads,
+			next,
+		}
+	}
+
+	// Rows whose key hashes to hash
+	fn candidates(&self, hash: u64) -> impl Iterator<Item = u32> + '_ {
+		let mut position = self.heads.get(&hash).copied().unwrap_or(NULL_ROW);
+		std::iter::from_fn(move || {
+			if position == NULL_ROW {
+				return None;
+			}
+			let row = self.rows[position as usize];
+			position = self.next[position as usize];
+			Some(row)
+		})
+	}
+}
+
+// Probes the hash index with the given left rows, appending the
+// output of the join kind to index. Right rows which found a partner
+// are marked in matched, when given, for the outer joins
+fn probe<I: Iterator<Item = u32>>(left_rows: I,
+																	left_keys: &KeyColumns,
+																	right_keys: &KeyColumns,
+																	table: &HashIndex,
+																	kind: JoinKind,
+																	mut matched: Option<&mut [bool]>,
+																	index: &mut JoinIndex) {
+	for l in left_rows {
+		let mut found = false;
+		if !left_keys.is_null(l) {
+			for r in table.candidates(left_keys.hashes[l as usize]) {
+				if !left_keys.matches(l, right_keys, r) {
+					continue;
+				}
+				found = true;
+				if !kind.emits_pairs() {
+					break;
+				}
+				index.push(l, r);
+				if let Some(matched) = matched.as_deref_mut() {
+					matched[r as usize] = true;
+				}
+			}
+		}
+		match kind {
+			JoinKind::LeftSemi if found => index.left.push(l),
+			JoinKind::LeftAnti if !found => index.left.push(l),
+			_ if !found && kind.keeps_unmatched_left() => index.push(l, NULL_ROW),
+			_ => (),
+		}
+	}
+}
+
+// Row ids of a table, which must fit in a u32 below NULL_ROW
+fn row_ids(table: &ColumnarTable) -> std::ops::Range<u32> {
+	if table.get_num_records() >= NULL_ROW as usize {
+		panic!("Columnar joins support up to {:?} rows, got {:?}", NULL_ROW - 1, table.get_num_records());
+	}
+	0..table.get_num_records() as u32
+}
+
+pub struct ColumnarHashJoin<'a> {
+	left: &'a ColumnarTable,
+	right: &'a ColumnarTable,
+	kind: JoinKind,
+}
+
+impl<'a> ColumnarHashJoin<'a> {
+	pub fn new(left: &'a ColumnarTable, right: &'a ColumnarTable) -> Self {
+		Self::with_kind(left, right, JoinKind::Inner)
+	}
+
+	pub fn with_kind(left: &'a ColumnarTable, right: &'a ColumnarTable, kind: JoinKind) -> Self {
+		Self {
+			left,
+			right,
+			kind,
+		}
+	}
+}
+
+impl<'a> ColumnarJoin for ColumnarHashJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::SimpleHashJoin
+	}
+
+	fn parameters(&self) -> JoinParams {
+		JoinParams {
+			join_kind: self.kind,
+			..JoinParams::default()
+		}
+	}
+
+	// Builds on the right rows and probes with the left ones, as SimpleHashJoin
+	fn join_index(&mut self, keys: &JoinKeys) -> JoinIndex {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
+		let left_keys = KeyColumns::new(self.left, keys.left_columns());
+		let right_keys = KeyColumns::new(self.right, keys.right_columns());
+
+		// NULL keys match nothing, so they are left out of the hash table
+		let right_rows: Vec<u32> = row_ids(self.right).filter(|r| !right_keys.is_null(*r)).collect();
+		let table = HashIndex::build(&right_keys, &right_rows);
+
+		let mut index = JoinIndex::new();
+		let mut matched: Vec<bool> = match self.kind.keeps_unmatched_right() {
+			true => vec![false; self.right.get_num_records()],
+			false => Vec::new(),
+		};
+		let marks = Some(matched.as_mut_slice()).filter(|m| !m.is_empty());
+		probe(row_ids(self.left), &left_keys, &right_keys, &table, self.kind, marks, &mut index);
+
+		if self.kind.keeps_unmatched_right() {
+			for r in row_ids(self.right).filter(|r| !matched[*r as usize]) {
+				index.push(NULL_ROW, r);
+			}
+		}
+		index
+	}
+
+	fn equi_join_on(&mut self, keys: &JoinKeys) -> ColumnarTable {
+		let index = self.join_index(keys);
+		gather_join_index(self.left, self.right, &index, self.kind)
+	}
+}
+
+// Splits the rows into one partition per combination of radix bits,
+// one pass per entry of radix_bits, as RadixJoin does its records.
+// Partition p is rows[offsets[p]..offsets[p + 1]]
+fn partition(keys: &KeyColumns, rows: Vec<u32>, radix_bits: &[u32]) -> (Vec<u32>, Vec<usize>) {
+	let mut rows = rows;
+	let mut offsets: Vec<usize> = vec![0, rows.len()];
+	let mut spare: Vec<u32> = vec![0; rows.len()];
+	let mut shift = 0;
+	for &bits in radix_bits {
+		let fanout = 1 << bits;
+		let partition_of = |row: u32| ((keys.hashes[row as usize] >> shift) as usize) & (fanout - 1);
+		let mut pass_offsets: Vec<usize> = Vec::with_capacity((offsets.len() - 1) * fanout + 1);
+		for q in 0..offsets.len() - 1 {
+			let part = &rows[offsets[q]..offsets[q + 1]];
+			let mut cursors: Vec<usize> = vec![0; fanout];
+			for &row in part {
+				cursors[partition_of(row)] += 1;
+			}
+			let mut position = offsets[q];
+			for cursor in cursors.iter_mut() {
+				pass_offsets.push(position);
+				position += std::mem::replace(cursor, position);
+			}
+			for &row in part {
+				let p = partition_of(row);
+				spare[cursors[p]] = row;
+				cursors[p] += 1;
+			}
+		}
+		pass_offsets.push(rows.len());
+		std::mem::swap(&mut rows, &mut spare);
+		offsets = pass_offsets;
+		shift += bits;
+	}
+	(rows, offsets)
+}
+
+pub struct ColumnarRadixJoin<'a> {
+	left: &'a ColumnarTable,
+	right: &'a ColumnarTable,
+	kind: JoinKind,
+	radix_bits: Vec<u32>,
+}
+
+impl<'a> ColumnarRadixJoin<'a> {
+	pub fn new(left: &'a ColumnarTable, right: &'a ColumnarTable) -> Self {
+		Self::with_kind(left, right, JoinKind::Inner)
+	}
+
+	// Supports inner, semi and anti joins
+	pub fn with_kind(left: &'a ColumnarTable, right: &'a ColumnarTable, kind: JoinKind) -> Self {
+		Self::with_radix_bits(left, right, kind, &DEFAULT_RADIX_BITS)
+	}
+
+	// Partitions in one pass per entry of radix_bits,
+	// each pass splitting on that many bits of the key
+	pub fn with_radix_bits(left: &'a ColumnarTable,
+												 right: &'a ColumnarTable,
+												 kind: JoinKind,
+												 radix_bits: &[u32]) -> Self {
+		if let Err(e) = JoinAlgos::RadixJoin.check_kind(kind) {
+			panic!("{}", e);
+		}
+		assert!(
+			!radix_bits.is_empty() && radix_bits.len() <= MAX_RADIX_PASSES,
+			"ColumnarRadixJoin makes 1 to {} passes, got {:?}", MAX_RADIX_PASSES, radix_bits);
+		assert!(
+			radix_bits.iter().all(|b| *b > 0) && radix_bits.iter().sum::<u32>() <= 24,
+			"ColumnarRadixJoin needs 1 to 24 bits in total and at least one per pass, got {:?}", radix_bits);
+		Self {
+			left,
+			right,
+			kind,
+			radix_bits: radix_bits.to_vec(),
+		}
+	}
+
+	pub fn get_radix_bits(&self) -> &[u32] {
+		&self.radix_bits
+	}
+}
+
+impl<'a> ColumnarJoin for ColumnarRadixJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::RadixJoin
+	}
+
+	fn parameters(&self) -> JoinParams {
+		JoinParams {
+			join_kind: self.kind,
+			radix_bits: self.radix_bits.clone(),
+			..JoinParams::default()
+		}
+	}
+
+	// Partitions the row ids of both sides, then joins each pair
+	// of partitions in parallel, building on the right partition
+	fn join_index(&mut self, keys: &JoinKeys) -> JoinIndex {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
+		let (left_keys, right_keys) = rayon::join(
+			|| KeyColumns::new(self.left, keys.left_columns()),
+			|| KeyColumns::new(self.right, keys.right_columns()));
+
+		// Left rows with NULL keys still go to the anti join output
+		let radix_bits = &self.radix_bits;
+		let ((left_rows, left_offsets), (right_rows, right_offsets)) = rayon::join(
+			|| partition(&left_keys, row_ids(self.left).collect(), radix_bits),
+			|| partition(&right_keys, row_ids(self.right).filter(|r| !right_keys.is_null(*r)).collect(), radix_bits));
+
+		let kind = self.kind;
+		let partitions: Vec<JoinIndex> = (0..left_offsets.len() - 1).into_par_iter()
+			.map(|p| {
+				let table = HashIndex::build(&right_keys, &right_rows[right_offsets[p]..right_offsets[p + 1]]);
+				let mut index = JoinIndex::new();
+				let rows = left_rows[left_offsets[p]..left_offsets[p + 1]].iter().copied();
+				probe(rows, &left_keys, &right_keys, &table, kind, None, &mut index);
+				index
+			})
+			.collect();
+
+		let mut index = JoinIndex {
+			left: Vec::with_capacity(partitions.iter().map(|p| p.len()).sum()),
+			right: Vec::new(),
+		};
+		index.right.reserve(if kind.emits_pairs() { index.left.capacity() } else { 0 });
+		for mut p in partitions {
+			index.append(&mut p);
+		}
+		index
+	}
+
+	fn equi_join_on(&mut self, keys: &JoinKeys) -> ColumnarTable {
+		let index = self.join_index(keys);
+		gather_join_index(self.left, self.right, &index, self.kind)
+	}
+}
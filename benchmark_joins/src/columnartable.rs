@@ -0,0 +1,208 @@
+use std::error::Error;
+
+use crate::bintable::{binary_layout, Layout, MappedColumns};
+use crate::join::NULL_ROW;
+use crate::record::Record;
+use crate::rows::Rows;
+use crate::schema::Schema;
+use crate::table::{read_table, SimpleTable};
+
+// A table stored column by column rather than record by record:
+// slot i of every row lives in columns[i], so an operator reading
+// a key column touches nothing else. Columns without NULLs leave
+// their null bitmap empty, and have no width cap unlike records
+pub struct ColumnarTable {
+  schema: Schema,
+  num_records: usize,
+  columns: ColumnStorage,
+}
+
+// Columns owned by the table, or mapped from a columnar
+// binary table of only i32 columns and read in place
+enum ColumnStorage {
+  Owned { values: Vec<Vec<i32>>, nulls: Vec<Vec<u64>> },
+  Mapped(MappedColumns),
+}
+
+impl ColumnarTable {
+  // Loads a table in any format SimpleTable::new reads
+  pub fn new(filepath: &str) -> ColumnarTable {
+    match ColumnarTable::try_new(filepath) {
+      Err(e) => panic!("{}", e),
+      Ok(table) => table,
+    }
+  }
+
+  // Same as new, but returns load errors rather than panicking.
+  // Columnar binary tables of only i32 columns are mapped rather
+  // than read
+  pub fn try_new(filepath: &str) -> Result<ColumnarTable, Box<dyn Error>> {
+    if binary_layout(filepath) == Some(Layout::Columns) {
+      if let Ok((schema, columns)) = MappedColumns::open(filepath) {
+        return Ok(ColumnarTable {
+          schema,
+          num_records: columns.num_rows(),
+          columns: ColumnStorage::Mapped(columns),
+        });
+      }
+    }
+    let (schema, records) = read_table(filepath)?;
+    Ok(ColumnarTable::from_records(schema, &records))
+  }
+
+  // Splits records, which must have as many
+  // slots as the schema, into columns
+  pub fn from_records(schema: Schema, records: &[Record]) -> ColumnarTable {
+    let width = schema.num_slots();
+    if let Some(r) = records.iter().find(|r| r.get_num_columns() != width) {
+      panic!("Record has {:?} columns, but the schema takes {:?}", r.get_num_columns(), width);
+    }
+    let values: Vec<Vec<i32>> = (0..width)
+      .map(|i| records.iter().map(|r| *r.get_column(i)).collect())
+      .collect();
+    let nulls: Vec<Vec<u64>> = (0..width)
+      .map(|i| match records.iter().any(|r| r.is_null(i)) {
+        true => null_bitmap(records.len(), |row| records[row].is_null(i)),
+        false => Vec::new(),
+      })
+      .collect();
+    ColumnarTable {
+      schema,
+      num_records: records.len(),
+      columns: ColumnStorage::Owned { values, nulls },
+    }
+  }
+
+  // Copies the records of a table
+  pub fn from_table(table: &SimpleTable) -> ColumnarTable {
+    ColumnarTable::from_records(table.schema().clone(), table.record_view())
+  }
+
+  pub fn get_num_records(&self) -> usize {
+    self.num_records
+  }
+
+  // Slots, which is the number of columns
+  // unless the schema has wide columns
+  pub fn get_num_columns_per_record(&self) -> usize {
+    match &self.columns {
+      ColumnStorage::Owned { values, .. } => values.len(),
+      ColumnStorage::Mapped(columns) => columns.num_columns(),
+    }
+  }
+
+  pub fn schema(&self) -> &Schema {
+    &self.schema
+  }
+
+  // Values of slot i, where NULLs hold 0
+  pub fn column(&self, i: usize) -> &[i32] {
+    match &self.columns {
+      ColumnStorage::Owned { values, .. } => &values[i],
+      ColumnStorage::Mapped(columns) => columns.column(i),
+    }
+  }
+
+  // Whether the columns are mapped from a binary table
+  pub fn is_mapped(&self) -> bool {
+    matches!(self.columns, ColumnStorage::Mapped(_))
+  }
+
+  pub fn has_nulls(&self, i: usize) -> bool {
+    !self.null_words(i).is_empty()
+  }
+
+  pub fn is_null(&self, i: usize, row: usize) -> bool {
+    self.has_nulls(i) && self.null_words(i)[row / 64] & (1 << (row % 64)) != 0
+  }
+
+  fn null_words(&self, i: usize) -> &[u64] {
+    match &self.columns {
+      ColumnStorage::Owned { nulls, .. } => &nulls[i],
+      ColumnStorage::Mapped(columns) => columns.null_words(i),
+    }
+  }
+
+  // The columns and NULL bitmaps, copied if they are mapped
+  fn into_owned(self) -> (Vec<Vec<i32>>, Vec<Vec<u64>>) {
+    match self.columns {
+      ColumnStorage::Owned { values, nulls } => (values, nulls),
+      ColumnStorage::Mapped(columns) => (
+        (0..columns.num_columns()).map(|i| columns.column(i).to_vec()).collect(),
+        (0..columns.num_columns()).map(|i| columns.null_words(i).to_vec()).collect(),
+      ),
+    }
+  }
+
+  // Rows at the given row ids, where NULL_ROW gives a row of
+  // NULLs, as the outer joins pad with. Copies a column at a time
+  pub fn gather(&self, rows: &[u32]) -> ColumnarTable {
+    let padded = rows.contains(&NULL_ROW);
+    let width = self.get_num_columns_per_record();
+    let values: Vec<Vec<i32>> = (0..width)
+      .map(|i| self.column(i))
+      .map(|column| match padded {
+        true => rows.iter().map(|&r| if r == NULL_ROW { 0 } else { column[r as usize] }).collect(),
+        false => rows.iter().map(|&r| column[r as usize]).collect(),
+      })
+      .collect();
+    let nulls: Vec<Vec<u64>> = (0..width)
+      .map(|i| match padded || self.has_nulls(i) {
+        true => null_bitmap(rows.len(), |row| rows[row] == NULL_ROW || self.is_null(i, rows[row] as usize)),
+        false => Vec::new(),
+      })
+      .collect();
+    ColumnarTable {
+      schema: self.schema.clone(),
+      num_records: rows.len(),
+      columns: ColumnStorage::Owned { values, nulls },
+    }
+  }
+
+  // The columns of this table followed by those of
+  // another of as many rows, as join output is built
+  pub fn merge(self, other: ColumnarTable) -> ColumnarTable {
+    assert_eq!(self.num_records, other.num_records);
+    let schema = self.schema.merge(&other.schema);
+    let num_records = self.num_records;
+    let (mut values, mut nulls) = self.into_owned();
+    let (other_values, other_nulls) = other.into_owned();
+    values.extend(other_values);
+    nulls.extend(other_nulls);
+    ColumnarTable {
+      schema,
+      num_records,
+      columns: ColumnStorage::Owned { values, nulls },
+    }
+  }
+
+  // The rows as records, which they must fit in
+  pub fn to_records(&self) -> Vec<Record> {
+    self.to_rows().to_records()
+  }
+
+  pub fn to_rows(&self) -> Rows {
+    let width = self.get_num_columns_per_record();
+    let columns: Vec<&[i32]> = (0..width).map(|i| self.column(i)).collect();
+    let mut rows = Rows::with_capacity(width, self.num_records);
+    let mut values: Vec<i32> = vec![0; width];
+    for row in 0..self.num_records {
+      for (value, column) in values.iter_mut().zip(&columns) {
+        *value = column[row];
+      }
+      rows.push(&values);
+      for i in (0..width).filter(|i| self.is_null(*i, row)) {
+        rows.set_null(row, i);
+      }
+    }
+    rows
+  }
+}
+
+fn null_bitmap<F: Fn(usize) -> bool>(num_rows: usize, is_null: F) -> Vec<u64> {
+  let mut bitmap: Vec<u64> = vec![0; num_rows.div_ceil(64)];
+  for row in (0..num_rows).filter(|row| is_null(*row)) {
+    bitmap[row / 64] |= 1 << (row % 64);
+  }
+  bitmap
+}
@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::join::{JoinAlgos, JoinInto, JoinOperator, JoinOutput, JoinParams};
+use crate::key::{JoinKeys, KeyRef};
+use crate::record::Record;
+use crate::table::SimpleTable;
+
+// Memory budget used when none is given
+pub const DEFAULT_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+// Most partitions made in one pass, which bounds
+// the number of spill files open at the same time
+const MAX_PARTITIONS: usize = 256;
+
+// Recursion depth at which partitions are joined in memory
+// even if they are over budget. Repartitioning cannot split
+// a partition made of a single key, so it has to stop somewhere
+const MAX_DEPTH: usize = 4;
+
+// Bytes taken by a record held in memory
+const RECORD_BYTES: usize = size_of::<Record>();
+
+// Makes spill directories unique within the process
+static SPILL_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// Partition of a key at a given recursion depth. The depth seeds the
+// hash so that repartitioning splits records which collided before
+fn partition_of(key: &KeyRef, depth: usize, num_partitions: usize) -> usize {
+	let mut hasher = DefaultHasher::new();
+	depth.hash(&mut hasher);
+	key.hash(&mut hasher);
+	(hasher.finish() % num_partitions as u64) as usize
+}
+
+// Enough partitions that each should fit the budget
+fn num_partitions_for(num_records: usize, memory_budget: usize) -> usize {
+	let needed = (num_records * RECORD_BYTES).div_ceil(memory_budget.max(1));
+	(needed + 1).clamp(2, MAX_PARTITIONS)
+}
+
+// Records of a table written to disk as their column values
+// followed by their NULL bitmap and row id, all little-endian
+struct SpillFile {
+	path: PathBuf,
+	writer: BufWriter<File>,
+	num_records: usize,
+	bytes_written: u64,
+}
+
+impl SpillFile {
+	fn create(path: PathBuf) -> SpillFile {
+		let file = match File::create(&path) {
+			Err(e) => panic!("Could not create spill file {:?} {:?}", path, e),
+			Ok(f) => f,
+		};
+		SpillFile {
+			path,
+			writer: BufWriter::new(file),
+			num_records: 0,
+			bytes_written: 0,
+		}
+	}
+
+	fn write(&mut self, row_id: u32, record: &Record) {
+		let num_columns = record.get_num_columns();
+		let mut nulls: u32 = 0;
+		for i in 0..num_columns {
+			if record.is_null(i) {
+				nulls |= 1 << i;
+			}
+		}
+
+		let mut bytes: Vec<u8> = Vec::with_capacity(4 * (num_columns + 2));
+		for v in record.get_column_values() {
+			bytes.extend_from_slice(&v.to_le_bytes());
+		}
+		bytes.extend_from_slice(&nulls.to_le_bytes());
+		bytes.extend_from_slice(&row_id.to_le_bytes());
+
+		if let Err(e) = self.writer.write_all(&bytes) {
+			panic!("Could not write spill file {:?} {:?}", self.path, e);
+		}
+		self.num_records += 1;
+		self.bytes_written += bytes.len() as u64;
+	}
+
+	// Flushes the file, after which it can only be read
+	fn finish(mut self) -> SpilledPartition {
+		if let Err(e) = self.writer.flush() {
+			panic!("Could not flush spill file {:?} {:?}", self.path, e);
+		}
+		SpilledPartition {
+			path: self.path,
+			num_records: self.num_records,
+		}
+	}
+}
+
+// Directory holding the spill files of one join, removed once
+// dropped, so that it goes away even if the join panics
+struct SpillDir {
+	path: PathBuf,
+}
+
+impl SpillDir {
+	fn create(parent: &Path) -> SpillDir {
+		let path = parent.join(format!("grace_{}_{}", process::id(), SPILL_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)));
+		if let Err(e) = fs::create_dir_all(&path) {
+			panic!("Could not create spill directory {:?} {:?}", path, e);
+		}
+		SpillDir {
+			path,
+		}
+	}
+}
+
+impl Drop for SpillDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.path);
+	}
+}
+
+// A finished spill file, deleted once dropped
+struct SpilledPartition {
+	path: PathBuf,
+	num_records: usize,
+}
+
+impl SpilledPartition {
+	fn records(&self, num_columns: usize) -> SpillReader {
+		let file = match File::open(&self.path) {
+			Err(e) => panic!("Could not open spill file {:?} {:?}", self.path, e),
+			Ok(f) => f,
+		};
+		SpillReader {
+			reader: BufReader::new(file),
+			buffer: vec![0; 4 * (num_columns + 2)],
+			num_columns,
+		}
+	}
+}
+
+impl Drop for SpilledPartition {
+	fn drop(&mut self) {
+		let _ = fs::remove_file(&self.path);
+	}
+}
+
+// Reads back the records of a spill file and their row ids one at a time
+struct SpillReader {
+	reader: BufReader<File>,
+	buffer: Vec<u8>,
+	num_columns: usize,
+}
+
+impl Iterator for SpillReader {
+	type Item = (u32, Record);
+
+	fn next(&mut self) -> Option<(u32, Record)> {
+		match self.reader.read_exact(&mut self.buffer) {
+			Err(e) if e.kind() == ErrorKind::UnexpectedEof => return None,
+			Err(e) => panic!("Could not read spill file {:?}", e),
+			Ok(_) => {},
+		}
+
+		let words: Vec<u32> = self.buffer
+			.chunks_exact(4)
+			.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+			.collect();
+		let fields: Vec<i32> = words[..self.num_columns].iter().map(|w| *w as i32).collect();
+		let nulls = words[self.num_columns];
+
+		let mut record = Record::new(&fields);
+		for i in 0..self.num_columns {
+			if nulls & (1 << i) != 0 {
+				record.set_null(i);
+			}
+		}
+		Some((words[self.num_columns + 1], record))
+	}
+}
+
+// Hash join which works under a memory budget. When the right (build)
+// table does not fit, both tables are hash partitioned on the join key,
+// so matching records always land in the same partition. The first
+// build partition stays in memory, unless it goes over budget, and is
+// joined while partitioning the left table (the "hybrid" part); the
+// others are spilled to files under the spill directory and joined one
+// partition at a time. Partitions still over budget are recursively
+// repartitioned with a different hash. Tables are partitioned in one
+// pass as they are read, so mapped record tables are read from disk
+// and partitioned without ever being held in memory whole
+pub struct GraceHashJoin<'a> {
+	left: &'a mut SimpleTable,
+	right: &'a mut SimpleTable,
+	memory_budget: usize,
+	spill_dir: PathBuf,
+	bytes_spilled: u64,
+}
+
+impl<'a> GraceHashJoin<'a> {
+
+	pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable, memory_budget: usize) -> Self {
+		Self::with_spill_dir(left, right, memory_budget, &std::env::temp_dir())
+	}
+
+	// Spill files are written to a fresh directory under spill_dir
+	pub fn with_spill_dir(left: &'a mut SimpleTable,
+												right: &'a mut SimpleTable,
+												memory_budget: usize,
+												spill_dir: &Path) -> Self {
+		Self {
+			left,
+			right,
+			memory_budget,
+			spill_dir: spill_dir.to_owned(),
+			bytes_spilled: 0,
+		}
+	}
+
+	pub fn get_memory_budget(&self) -> usize {
+		self.memory_budget
+	}
+
+	fn spill_file(&self, dir: &Path, side: &str, depth: usize, partition: usize) -> SpillFile {
+		SpillFile::create(dir.join(format!("{}_{}_{}.bin", side, depth, partition)))
+	}
+
+	// In-memory hash join of a build partition with a stream of probe
+	// records, each record coming with its row id
+	fn join_in_memory<'r, B: Iterator<Item = (u32, &'r Record)>, I: Iterator<Item = (u32, Record)>, O: JoinOutput>(
+		keys: &JoinKeys,
+		build: B,
+		probe: I,
+		join_result: &mut O) {
+		let mut hash_table: HashMap<KeyRef, Vec<(u32, &Record)>> = HashMap::new();
+		for (id, r) in build.filter(|(_, r)| !keys.right_key(r).is_null()) {
+			hash_table.entry(keys.right_key(r)).or_default().push((id, r));
+		}
+
+		for (left_id, left_record) in probe {
+			if let Some(right_record_matches) = hash_table.get(&keys.left_key(&left_record)) {
+				for (right_id, right_record) in right_record_matches {
+					join_result.pair(left_id, &left_record, *right_id, right_record);
+				}
+			}
+		}
+	}
+
+	// Joins a pair of spilled partitions, repartitioning
+	// them first if the build side is over budget
+	fn join_spilled<O: JoinOutput>(&mut self,
+																 keys: &JoinKeys,
+																 dir: &Path,
+																 left_partition: SpilledPartition,
+																 right_partition: SpilledPartition,
+																 depth: usize,
+																 join_result: &mut O) {
+		let left_columns = self.left.get_num_columns_per_record();
+		let right_columns = self.right.get_num_columns_per_record();
+
+		// Nothing can match an empty side
+		if left_partition.num_records == 0 || right_partition.num_records == 0 {
+			return;
+		}
+
+		let fits = right_partition.num_records * RECORD_BYTES <= self.memory_budget;
+		if fits || depth >= MAX_DEPTH {
+			let build: Vec<(u32, Record)> = right_partition.records(right_columns).collect();
+			Self::join_in_memory(keys, build.iter().map(|(id, r)| (*id, r)), left_partition.records(left_columns), join_result);
+			return;
+		}
+
+		// Split both sides again with this depth's hash
+		let num_partitions = num_partitions_for(right_partition.num_records, self.memory_budget);
+		let mut right_files: Vec<SpillFile> = (0..num_partitions)
+			.map(|p| self.spill_file(dir, "right", depth, p))
+			.collect();
+		for (id, record) in right_partition.records(right_columns) {
+			right_files[partition_of(&keys.right_key(&record), depth, num_partitions)].write(id, &record);
+		}
+		let mut left_files: Vec<SpillFile> = (0..num_partitions)
+			.map(|p| self.spill_file(dir, "left", depth, p))
+			.collect();
+		for (id, record) in left_partition.records(left_columns) {
+			left_files[partition_of(&keys.left_key(&record), depth, num_partitions)].write(id, &record);
+		}
+		drop(left_partition);
+		drop(right_partition);
+
+		self.bytes_spilled += right_files.iter().chain(left_files.iter()).map(|f| f.bytes_written).sum::<u64>();
+		let partitions: Vec<(SpilledPartition, SpilledPartition)> = left_files.into_iter()
+			.zip(right_files)
+			.map(|(l, r)| (l.finish(), r.finish()))
+			.collect();
+		for (l, r) in partitions {
+			self.join_spilled(keys, dir, l, r, depth + 1, join_result);
+		}
+	}
+}
+
+impl<'a> JoinOperator for GraceHashJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::GraceHashJoin
+	}
+
+	fn parameters(&self) -> JoinParams {
+		JoinParams {
+			memory_budget_bytes: self.memory_budget,
+			..JoinParams::default()
+		}
+	}
+
+	fn bytes_spilled(&self) -> u64 {
+		self.bytes_spilled
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> JoinInto for GraceHashJoin<'a> {
+	fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
+		let right_size = self.right.get_num_records();
+		self.bytes_spilled = 0;
+
+		// Whole build side fits, so nothing needs to be spilled
+		if right_size * RECORD_BYTES <= self.memory_budget {
+			let build = self.right.record_iterator().enumerate().map(|(id, r)| (id as u32, r));
+			let probe = self.left.record_iterator().cloned().enumerate().map(|(id, r)| (id as u32, r));
+			Self::join_in_memory(keys, build, probe, join_result);
+			return;
+		}
+
+		let spill_dir = SpillDir::create(&self.spill_dir);
+		let dir = &spill_dir.path;
+
+		// Partition the right table, keeping partition 0 in memory
+		// unless it turns out over budget, when it is spilled too
+		let num_partitions = num_partitions_for(right_size, self.memory_budget);
+		let mut in_memory: Vec<(u32, Record)> = Vec::new();
+		let mut hybrid = true;
+		let mut right_files: Vec<SpillFile> = (0..num_partitions)
+			.map(|p| self.spill_file(dir, "right", 0, p))
+			.collect();
+		for (id, record) in self.right.record_iterator().enumerate() {
+			match partition_of(&keys.right_key(record), 0, num_partitions) {
+				0 if hybrid => {
+					in_memory.push((id as u32, record.clone()));
+					if in_memory.len() * RECORD_BYTES > self.memory_budget {
+						for (id, record) in in_memory.drain(..) {
+							right_files[0].write(id, &record);
+						}
+						hybrid = false;
+					}
+				},
+				p => right_files[p].write(id as u32, record),
+			}
+		}
+
+		// Partition the left table, probing partition 0 right away
+		// if it stayed in memory
+		let mut left_files: Vec<SpillFile> = (0..num_partitions)
+			.map(|p| self.spill_file(dir, "left", 0, p))
+			.collect();
+		{
+			let mut hash_table: HashMap<KeyRef, Vec<(u32, &Record)>> = HashMap::with_capacity(in_memory.len());
+			for (id, r) in in_memory.iter().filter(|(_, r)| !keys.right_key(r).is_null()) {
+				hash_table.entry(keys.right_key(r)).or_default().push((*id, r));
+			}
+			for (id, record) in self.left.record_iterator().enumerate() {
+				let left_key = keys.left_key(record);
+				match partition_of(&left_key, 0, num_partitions) {
+					0 if hybrid => {
+						if let Some(right_record_matches) = hash_table.get(&left_key) {
+							for (right_id, right_record) in right_record_matches {
+								join_result.pair(id as u32, record, *right_id, right_record);
+							}
+						}
+					},
+					p => left_files[p].write(id as u32, record),
+				}
+			}
+		}
+		drop(in_memory);
+
+		// Join the spilled partitions one at a time. Partition 0 is
+		// empty on both sides unless it was spilled
+		self.bytes_spilled += right_files.iter().chain(left_files.iter()).map(|f| f.bytes_written).sum::<u64>();
+		let partitions: Vec<(SpilledPartition, SpilledPartition)> = left_files.into_iter()
+			.zip(right_files)
+			.map(|(l, r)| (l.finish(), r.finish()))
+			.collect();
+		for (l, r) in partitions {
+			self.join_spilled(keys, dir, l, r, 1, join_result);
+		}
+	}
+}
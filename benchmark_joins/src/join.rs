@@ -1,11 +1,15 @@
-use std::{collections::HashMap};
+use std::{collections::{HashMap, HashSet}};
+use std::mem::size_of;
+use std::sync::Mutex;
 use serde::{Serialize, Deserialize};
 use strum_macros::EnumIter;
 
+use crate::aggregate::{Aggregate, AggregateOutput};
+use crate::key::{JoinKeys, KeyRef};
 use crate::record::Record;
 use crate::table::SimpleTable;
 
-#[derive(EnumIter, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(EnumIter, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum JoinAlgos {
   NLJoin,
   BNLJoin,
@@ -14,49 +18,597 @@ pub enum JoinAlgos {
   PNLJoin,
   ParallelSimpleHashJoin,
   ParallelUnaryLeapFrogJoin,
+  BandJoin,
+  IEJoin,
+  GraceHashJoin,
+
+  // Operators defined outside of this crate, keyed by
+  // their own name. Not part of the built-in algorithms,
+  // so it is skipped when iterating over JoinAlgos
+  #[strum(disabled)]
+  Custom(String),
+}
+
+impl JoinAlgos {
+  // Join kinds the operators of an algorithm produce, the one place
+  // the registry and the runner check kinds against. Custom
+  // operators are taken to check their kinds themselves
+  pub fn supported_kinds(&self) -> &'static [JoinKind] {
+    use JoinKind::*;
+    match self {
+      JoinAlgos::BNLJoin | JoinAlgos::PNLJoin | JoinAlgos::GraceHashJoin | JoinAlgos::BandJoin | JoinAlgos::IEJoin => &[Inner],
+      JoinAlgos::RadixJoin => &[Inner, LeftSemi, LeftAnti],
+      _ => &[Inner, LeftOuter, RightOuter, FullOuter, LeftSemi, LeftAnti],
+    }
+  }
+
+  pub fn check_kind(&self, kind: JoinKind) -> Result<(), String> {
+    match self.supported_kinds().contains(&kind) {
+      true => Ok(()),
+      false => Err(format!("{:?} does not support {:?} joins", self, kind)),
+    }
+  }
+}
+
+// Which records a join emits
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum JoinKind {
+  // Only matching pairs
+  #[default]
+  Inner,
+  // Matching pairs, plus unmatched left
+  // records padded with NULLs on the right
+  LeftOuter,
+  // Matching pairs, plus unmatched right
+  // records padded with NULLs on the left
+  RightOuter,
+  // Union of left and right outer
+  FullOuter,
+  // Left records which have at least one match,
+  // emitted once and without the right columns
+  LeftSemi,
+  // Left records which have no match
+  LeftAnti,
+}
+
+impl JoinKind {
+  // Whether matches are emitted as merged left and right records
+  pub fn emits_pairs(&self) -> bool {
+    !matches!(self, JoinKind::LeftSemi | JoinKind::LeftAnti)
+  }
+
+  pub fn keeps_unmatched_left(&self) -> bool {
+    matches!(self, JoinKind::LeftOuter | JoinKind::FullOuter)
+  }
+
+  pub fn keeps_unmatched_right(&self) -> bool {
+    matches!(self, JoinKind::RightOuter | JoinKind::FullOuter)
+  }
+}
+
+// Tuning knobs a join operator was created with.
+// Operators which have no knobs leave these zeroed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct JoinParams {
+  pub left_block_size: usize,
+  pub right_block_size: usize,
+  #[serde(default)]
+  pub join_kind: JoinKind,
+  // Bytes an operator may hold in memory before spilling to disk
+  #[serde(default)]
+  pub memory_budget_bytes: usize,
+  // Bits of the key each radix partitioning pass splits on
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub radix_bits: Vec<u32>,
+  // Whether radix partitioning builds histograms and
+  // scatters with every thread, and whether it stages
+  // records in software write-combining buffers
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub parallel_partitioning: bool,
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub write_combining: bool,
+  // Whether heavy keys of the right table are detected
+  // and their pairs spread over all of the workers
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub skew_handling: bool,
+}
+
+// Row id standing for the NULL padding of outer joins
+pub const NULL_ROW: u32 = u32::MAX;
+
+// Output of a join as row ids rather than records: output row i
+// joins left row left[i] with right row right[i], either of which
+// may be NULL_ROW. Semi and anti joins leave right empty, since
+// they emit left rows only
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JoinIndex {
+  pub left: Vec<u32>,
+  pub right: Vec<u32>,
+}
+
+impl JoinIndex {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn len(&self) -> usize {
+    self.left.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.left.is_empty()
+  }
+
+  pub fn push(&mut self, left_row: u32, right_row: u32) {
+    self.left.push(left_row);
+    self.right.push(right_row);
+  }
+
+  // Appends the rows of another index
+  pub fn append(&mut self, other: &mut JoinIndex) {
+    self.left.append(&mut other.left);
+    self.right.append(&mut other.right);
+  }
+
+  // Output rows as (left, right) row id pairs,
+  // with NULL_ROW on the right for semi and anti joins
+  pub fn pairs(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let right = self.right.iter().copied().chain(std::iter::repeat(NULL_ROW));
+    self.left.iter().copied().zip(right)
+  }
+}
+
+// Where an operator puts its output. Operators hand over each output
+// row as the records it joins, with their row ids, i.e. their indexes
+// in the tables' record views, so that an output may merge the records
+// or keep only the row ids. Parallel operators split the output into
+// one per worker and absorb them back in order
+pub trait JoinOutput: Send + Sync + Sized {
+  // Whether the output merges the records it is handed, which
+  // operators that copy records anyway can hand it directly
+  const MERGES_RECORDS: bool = false;
+
+  // A left and a right record which match
+  fn pair(&mut self, left_id: u32, left: &Record, right_id: u32, right: &Record);
+
+  // An unmatched left record of an outer join
+  fn left_padded(&mut self, left_id: u32, left: &Record);
+
+  // An unmatched right record of an outer join
+  fn right_padded(&mut self, right_id: u32, right: &Record);
+
+  // A left record of a semi or anti join
+  fn left_only(&mut self, left_id: u32, left: &Record);
+
+  // Empty output like this one, for a worker to fill
+  fn split(&self) -> Self;
+
+  // Appends the output of a worker
+  fn absorb(&mut self, other: Self);
+}
+
+// Output as merged records, NULL padded for outer joins
+pub struct RecordOutput {
+  records: Vec<Record>,
+  left_nulls: Record,
+  right_nulls: Record,
+}
+
+impl RecordOutput {
+  pub fn new(left_width: usize, right_width: usize) -> Self {
+    Self::with_capacity(left_width, right_width, 0)
+  }
+
+  pub fn with_capacity(left_width: usize, right_width: usize, capacity: usize) -> Self {
+    Self {
+      records: Vec::with_capacity(capacity),
+      left_nulls: Record::nulls(left_width),
+      right_nulls: Record::nulls(right_width),
+    }
+  }
+
+  pub fn into_records(self) -> Vec<Record> {
+    self.records
+  }
+}
+
+impl JoinOutput for RecordOutput {
+  const MERGES_RECORDS: bool = true;
+
+  fn pair(&mut self, _: u32, left: &Record, _: u32, right: &Record) {
+    self.records.push(Record::merge(left, right));
+  }
+
+  fn left_padded(&mut self, _: u32, left: &Record) {
+    self.records.push(Record::merge(left, &self.right_nulls));
+  }
+
+  fn right_padded(&mut self, _: u32, right: &Record) {
+    self.records.push(Record::merge(&self.left_nulls, right));
+  }
+
+  fn left_only(&mut self, _: u32, left: &Record) {
+    self.records.push(left.clone());
+  }
+
+  fn split(&self) -> Self {
+    Self::new(self.left_nulls.get_num_columns(), self.right_nulls.get_num_columns())
+  }
+
+  fn absorb(&mut self, mut other: Self) {
+    self.records.append(&mut other.records);
+  }
+}
+
+// Receives the output of a streamed join, one batch at a time
+pub type RecordSink<'s> = dyn FnMut(&[Record]) + Send + 's;
+
+// Output handed to a sink in batches of merged records as the join
+// runs, rather than kept. Workers of parallel operators fill batches
+// of their own and take turns at the sink, so the batches of parallel
+// operators come in no set order
+pub struct BatchOutput<'s, S: FnMut(&[Record]) + Send> {
+  output: RecordOutput,
+  batch_size: usize,
+  sink: &'s Mutex<S>,
+}
+
+impl<'s, S: FnMut(&[Record]) + Send> BatchOutput<'s, S> {
+  pub fn new(left_width: usize, right_width: usize, batch_size: usize, sink: &'s Mutex<S>) -> Self {
+    assert!(batch_size > 0, "Batches need room for at least one record");
+    Self {
+      output: RecordOutput::with_capacity(left_width, right_width, batch_size),
+      batch_size,
+      sink,
+    }
+  }
+
+  // Hands over the last batch, which may not be full
+  pub fn finish(mut self) {
+    self.flush();
+  }
+
+  fn flush(&mut self) {
+    if self.output.records.is_empty() {
+      return;
+    }
+    let mut sink = self.sink.lock().unwrap();
+    (*sink)(&self.output.records);
+    self.output.records.clear();
+  }
+
+  fn flush_if_full(&mut self) {
+    if self.output.records.len() >= self.batch_size {
+      self.flush();
+    }
+  }
+}
+
+impl<'s, S: FnMut(&[Record]) + Send> JoinOutput for BatchOutput<'s, S> {
+  const MERGES_RECORDS: bool = true;
+
+  fn pair(&mut self, left_id: u32, left: &Record, right_id: u32, right: &Record) {
+    self.output.pair(left_id, left, right_id, right);
+    self.flush_if_full();
+  }
+
+  fn left_padded(&mut self, left_id: u32, left: &Record) {
+    self.output.left_padded(left_id, left);
+    self.flush_if_full();
+  }
+
+  fn right_padded(&mut self, right_id: u32, right: &Record) {
+    self.output.right_padded(right_id, right);
+    self.flush_if_full();
+  }
+
+  fn left_only(&mut self, left_id: u32, left: &Record) {
+    self.output.left_only(left_id, left);
+    self.flush_if_full();
+  }
+
+  fn split(&self) -> Self {
+    Self::new(
+      self.output.left_nulls.get_num_columns(),
+      self.output.right_nulls.get_num_columns(),
+      self.batch_size,
+      self.sink)
+  }
+
+  fn absorb(&mut self, other: Self) {
+    other.finish();
+  }
+}
+
+// Runs join into batches of up to batch_size records for sink,
+// then hands over the last batch
+pub(crate) fn join_batches<F>(widths: (usize, usize), batch_size: usize, sink: &mut RecordSink, join: F)
+where F: FnOnce(&mut BatchOutput<&mut RecordSink>) {
+  let sink = Mutex::new(sink);
+  let mut join_result = BatchOutput::new(widths.0, widths.1, batch_size, &sink);
+  join(&mut join_result);
+  join_result.finish();
+}
+
+impl JoinOutput for JoinIndex {
+  fn pair(&mut self, left_id: u32, _: &Record, right_id: u32, _: &Record) {
+    self.push(left_id, right_id);
+  }
+
+  fn left_padded(&mut self, left_id: u32, _: &Record) {
+    self.push(left_id, NULL_ROW);
+  }
+
+  fn right_padded(&mut self, right_id: u32, _: &Record) {
+    self.push(NULL_ROW, right_id);
+  }
+
+  fn left_only(&mut self, left_id: u32, _: &Record) {
+    self.left.push(left_id);
+  }
+
+  fn split(&self) -> Self {
+    JoinIndex::new()
+  }
+
+  fn absorb(&mut self, mut other: Self) {
+    self.append(&mut other);
+  }
+}
+
+// Output as the number of records the join emits, which are never merged
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CountOutput {
+  count: usize,
+}
+
+impl CountOutput {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn count(&self) -> usize {
+    self.count
+  }
+}
+
+impl JoinOutput for CountOutput {
+  fn pair(&mut self, _: u32, _: &Record, _: u32, _: &Record) {
+    self.count += 1;
+  }
+
+  fn left_padded(&mut self, _: u32, _: &Record) {
+    self.count += 1;
+  }
+
+  fn right_padded(&mut self, _: u32, _: &Record) {
+    self.count += 1;
+  }
+
+  fn left_only(&mut self, _: u32, _: &Record) {
+    self.count += 1;
+  }
+
+  fn split(&self) -> Self {
+    CountOutput::new()
+  }
+
+  fn absorb(&mut self, other: Self) {
+    self.count += other.count;
+  }
+}
+
+// Row id of a record of the view, found from its address
+// so that operators can keep plain references to records
+pub(crate) fn row_id(records: &[Record], record: &Record) -> u32 {
+  let offset = (record as *const Record as usize).wrapping_sub(records.as_ptr() as usize) / size_of::<Record>();
+  debug_assert!(offset < records.len(), "Record is not in the view");
+  offset as u32
+}
+
+// Row ids of a table's records, which must fit in a u32 below NULL_ROW
+pub(crate) fn check_row_ids(table: &SimpleTable) {
+  if table.get_num_records() >= NULL_ROW as usize {
+    panic!("Join indexes support up to {:?} rows, got {:?}", NULL_ROW - 1, table.get_num_records());
+  }
+}
+
+// What every join operator, equi or theta, reports about itself
+pub trait JoinOperator {
+  // Which algorithm this operator implements
+  fn name(&self) -> JoinAlgos;
+
+  // Parameters the operator was created with
+  fn parameters(&self) -> JoinParams {
+    JoinParams::default()
+  }
+
+  // Which records the operator emits
+  fn join_kind(&self) -> JoinKind {
+    JoinKind::Inner
+  }
+
+  // Bytes written to disk by the last join, for
+  // operators which spill when out of memory
+  fn bytes_spilled(&self) -> u64 {
+    0
+  }
+
+  // The left and right tables
+  fn tables(&self) -> (&SimpleTable, &SimpleTable);
+}
+
+// Columns per record of an operator's left and right tables
+pub(crate) fn record_widths<J: JoinOperator + ?Sized>(join: &J) -> (usize, usize) {
+  let (left, right) = join.tables();
+  (left.get_num_columns_per_record(), right.get_num_columns_per_record())
+}
+
+// Common interface of every equi-join operator, so that the
+// runner and tests can drive any of them the same way. It comes
+// with JoinInto, which is what operators implement
+pub trait EquiJoin: JoinOperator {
+  // Join the left and right tables on
+  // left[left_col] == right[right_col]
+  fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
+    self.equi_join_on(&JoinKeys::single(left_col, right_col))
+  }
+
+  // Join the left and right tables on every pair of key columns
+  fn equi_join_on(&mut self, keys: &JoinKeys) -> Vec<Record>;
+
+  // Same join, as the row ids of the joined records rather than the
+  // merged records, see materialize. Row ids index the tables as the
+  // join leaves them, which operators that sort their tables change
+  fn join_index_on(&mut self, keys: &JoinKeys) -> JoinIndex;
+
+  // Same join, handing the merged records to sink in batches of
+  // up to batch_size as they are produced, rather than collecting them
+  fn equi_join_batches(&mut self, keys: &JoinKeys, batch_size: usize, sink: &mut RecordSink);
+
+  // Number of records the same join emits, counted without merging them
+  fn count_on(&mut self, keys: &JoinKeys) -> usize;
+
+  // Same join reduced to an aggregate per join key as it runs, see
+  // AggregateOutput, whose records it returns in key order
+  fn aggregate_on(&mut self, keys: &JoinKeys, aggregate: &Aggregate) -> Vec<Record>;
+}
+
+// Operators which hand each output row to a JoinOutput as they find
+// it. Every EquiJoin method is join_into with a different output
+pub trait JoinInto: JoinOperator {
+  // Join the left and right tables on every pair of key columns
+  fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O);
+}
+
+impl<J: JoinInto> EquiJoin for J {
+  fn equi_join_on(&mut self, keys: &JoinKeys) -> Vec<Record> {
+    let (left_width, right_width) = record_widths(self);
+    let mut join_result = RecordOutput::new(left_width, right_width);
+    self.join_into(keys, &mut join_result);
+    join_result.into_records()
+  }
+
+  fn join_index_on(&mut self, keys: &JoinKeys) -> JoinIndex {
+    let (left, right) = self.tables();
+    check_row_ids(left);
+    check_row_ids(right);
+    let mut join_index = JoinIndex::new();
+    self.join_into(keys, &mut join_index);
+    join_index
+  }
+
+  fn equi_join_batches(&mut self, keys: &JoinKeys, batch_size: usize, sink: &mut RecordSink) {
+    join_batches(record_widths(self), batch_size, sink, |join_result| self.join_into(keys, join_result));
+  }
+
+  fn count_on(&mut self, keys: &JoinKeys) -> usize {
+    let mut count = CountOutput::new();
+    self.join_into(keys, &mut count);
+    count.count()
+  }
+
+  fn aggregate_on(&mut self, keys: &JoinKeys, aggregate: &Aggregate) -> Vec<Record> {
+    let (left, right) = self.tables();
+    let mut join_result = AggregateOutput::new(aggregate, keys, left.schema(), right.schema(), self.join_kind());
+    self.join_into(keys, &mut join_result);
+    join_result.into_records()
+  }
 }
 
 
 pub struct NestedLoopsJoin<'a> {
   left: &'a mut SimpleTable,
   right: &'a mut SimpleTable,
+  kind: JoinKind,
 }
 
 impl<'a> NestedLoopsJoin<'a> {
   
   pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+    Self::with_kind(left, right, JoinKind::Inner)
+  }
+
+  pub fn with_kind(left: &'a mut SimpleTable, right: &'a mut SimpleTable, kind: JoinKind) -> Self {
     Self {
       left,
-      right
+      right,
+      kind,
     }
   }
 
-  pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
+  // Joins every pair of records for which matches holds, so
+  // the same loops serve equi-joins and theta joins
+  pub(crate) fn join_where<F: Fn(&Record, &Record) -> bool, O: JoinOutput>(&mut self, matches: F, join_result: &mut O) {
     // Number of records in left and right tables
     let left_size = self.left.get_num_records();
     let right_size = self.right.get_num_records();
 
-    // Since this is a primary-key foreign-key equijoin
-    // we know the the join will be no larger than left table
-    let mut join_result = Vec::with_capacity(left_size);
+    // Right records which found a partner, for outer joins
+    let mut right_matched = vec![false; right_size];
 
-    for _l in 0..left_size {
+    for l in 0..left_size {
       let left_record = self.left.read_next_record();
+      let mut left_matched = false;
 
-      for _r in 0..right_size {
+      for (r, matched) in right_matched.iter_mut().enumerate() {
         let right_record = self.right.read_next_record();
 
-        if left_record.get_column(left_col) == right_record.get_column(right_col) {
+        if matches(left_record, right_record) {
           // Join condition is met ==> new record 
-          let join_record = Record::merge(left_record, right_record);
-          join_result.push(join_record);
+          if self.kind.emits_pairs() {
+            join_result.pair(l as u32, left_record, r as u32, right_record);
+          }
+          left_matched = true;
+          *matched = true;
         }
       }
       self.right.rewind();
+
+      match self.kind {
+        JoinKind::LeftSemi if left_matched => join_result.left_only(l as u32, left_record),
+        JoinKind::LeftAnti if !left_matched => join_result.left_only(l as u32, left_record),
+        _ if !left_matched && self.kind.keeps_unmatched_left() => join_result.left_padded(l as u32, left_record),
+        _ => {},
+      }
     }
     self.left.rewind();
 
-    join_result
+    if self.kind.keeps_unmatched_right() {
+      for (r, (right_record, matched)) in self.right.record_iterator().zip(right_matched).enumerate() {
+        if !matched {
+          join_result.right_padded(r as u32, right_record);
+        }
+      }
+    }
+  }
+}
+
+impl<'a> JoinOperator for NestedLoopsJoin<'a> {
+  fn name(&self) -> JoinAlgos {
+    JoinAlgos::NLJoin
+  }
+
+  fn parameters(&self) -> JoinParams {
+    JoinParams {
+      join_kind: self.kind,
+      ..JoinParams::default()
+    }
+  }
+
+  fn join_kind(&self) -> JoinKind {
+    self.kind
+  }
+
+  fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+    (self.left, self.right)
+  }
+}
+
+impl<'a> JoinInto for NestedLoopsJoin<'a> {
+  fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+    let keys = &keys.resolve(self.left.schema(), self.right.schema());
+    self.join_where(|l, r| keys.matches(l, r), join_result);
   }
 }
 
@@ -84,7 +636,17 @@ impl<'a> BlockNL<'a> {
     intermidate as usize
   }
 
-  pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
+  pub fn get_left_block_size(&self) -> usize {
+    self.l_block_sz
+  }
+
+  pub fn get_right_block_size(&self) -> usize {
+    self.r_block_sz
+  }
+
+  // Joins every pair of records for which matches holds, so
+  // the same loops serve equi-joins and theta joins
+  pub(crate) fn join_where<F: Fn(&Record, &Record) -> bool, O: JoinOutput>(&mut self, matches: F, join_result: &mut O) {
     // Number of records in left and right tables
     let left_size = self.left.get_num_records();
     let right_size = self.right.get_num_records();
@@ -93,23 +655,20 @@ impl<'a> BlockNL<'a> {
     let effective_left_num_blocks = self.get_effective_num_blocks(left_size, self.l_block_sz);
     let effective_right_num_blocks = self.get_effective_num_blocks(right_size, self.r_block_sz);
 
-    // Since this is a primary-key foreign-key equijoin
-    // we know the the join will be no larger than left table
-    let mut join_result = Vec::with_capacity(left_size);
-
-    for _l in 0..effective_left_num_blocks  {
+    for l in 0..effective_left_num_blocks  {
       let left_block = self.left.read_next_block(self.l_block_sz);
+      let left_start = l * self.l_block_sz;
 
-      for _r in 0..effective_right_num_blocks {
+      for r in 0..effective_right_num_blocks {
         let right_block = self.right.read_next_block(self.r_block_sz);
+        let right_start = r * self.r_block_sz;
 
-        for left_record in left_block {
-          for right_record in right_block {
+        for (i, left_record) in left_block.iter().enumerate() {
+          for (j, right_record) in right_block.iter().enumerate() {
 
-            if left_record.get_column(left_col) == right_record.get_column(right_col) {
+            if matches(left_record, right_record) {
               // Join condition is met ==> new record 
-              let join_record = Record::merge(left_record, right_record);
-              join_result.push(join_record);
+              join_result.pair((left_start + i) as u32, left_record, (right_start + j) as u32, right_record);
             }
           }
         }
@@ -117,80 +676,150 @@ impl<'a> BlockNL<'a> {
       self.right.rewind();
     }
     self.left.rewind();
+  }
+}
 
-    join_result
+impl<'a> JoinOperator for BlockNL<'a> {
+  fn name(&self) -> JoinAlgos {
+    JoinAlgos::BNLJoin
   }
 
-  pub fn get_left_block_size(&self) -> usize {
-    return self.l_block_sz;
+  fn parameters(&self) -> JoinParams {
+    JoinParams {
+      left_block_size: self.l_block_sz,
+      right_block_size: self.r_block_sz,
+      ..JoinParams::default()
+    }
   }
 
-  pub fn get_right_block_size(&self) -> usize {
-    return self.r_block_sz;
+  fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+    (self.left, self.right)
+  }
+}
+
+impl<'a> JoinInto for BlockNL<'a> {
+  fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+    let keys = &keys.resolve(self.left.schema(), self.right.schema());
+    self.join_where(|l, r| keys.matches(l, r), join_result);
   }
 }
 
 pub struct SimpleHashJoin<'a> {
   left: &'a mut SimpleTable,
   right: &'a mut SimpleTable,
+  kind: JoinKind,
 }
 
 impl<'a> SimpleHashJoin<'a> {
   
   pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+    Self::with_kind(left, right, JoinKind::Inner)
+  }
+
+  pub fn with_kind(left: &'a mut SimpleTable, right: &'a mut SimpleTable, kind: JoinKind) -> Self {
     Self {
       left,
-      right
+      right,
+      kind,
     }
   }
+}
 
-  pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
-    // Number of records in left and right tables
-    let left_size = self.left.get_num_records();
-    let right_size = self.right.get_num_records();
+impl<'a> JoinOperator for SimpleHashJoin<'a> {
+  fn name(&self) -> JoinAlgos {
+    JoinAlgos::SimpleHashJoin
+  }
 
-    // Since this is a primary-key foreign-key equijoin
-    // we know the the join will be no larger than left table
-    let mut join_result = Vec::with_capacity(left_size);
+  fn parameters(&self) -> JoinParams {
+    JoinParams {
+      join_kind: self.kind,
+      ..JoinParams::default()
+    }
+  }
 
-    let mut hash_table: HashMap<&i32, Vec<&Record>> = HashMap::with_capacity(right_size);
+  fn join_kind(&self) -> JoinKind {
+    self.kind
+  }
+
+  fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+    (self.left, self.right)
+  }
+}
+
+impl<'a> JoinInto for SimpleHashJoin<'a> {
+  fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+    let keys = &keys.resolve(self.left.schema(), self.right.schema());
+
+    // Number of records in right table
+    let right_size = self.right.get_num_records();
+
+    let mut hash_table: HashMap<KeyRef, Vec<&Record>> = HashMap::with_capacity(right_size);
 
     // Get the right table's view of its records
     let right_records = self.right.record_view();
     assert!(right_size == right_records.len());
 
     // Now we build the hash table on the smaller table
-    // since this results in the fewest operations during join
+    // since this results in the fewest operations during join.
+    // NULL keys match nothing, so they are left out, and left
+    // records with NULL keys then find no partner
     for r in right_records {
-      let right_column_value = r.get_column(right_col);
+      let right_key = keys.right_key(r);
+      if right_key.is_null() {
+        continue;
+      }
 
-      // Insert map from the hash of right join column value the record itself
-      hash_table.entry(right_column_value).or_insert(Vec::new()).push(r);
+      // Insert map from the hash of right join key the record itself
+      hash_table.entry(right_key).or_default().push(r);
     }
 
-    for _l in 0..left_size {
-      let left_record = self.left.read_next_record();
-      let left_column_value = left_record.get_column(left_col);
+    // The right keys which found a partner, only needed for outer joins
+    let mut matched_keys: HashSet<KeyRef> = HashSet::new();
+
+    // Probe with every left record. The probe keys live as long
+    // as the hash table, so the left table is read through an iterator
+    for (l, left_record) in self.left.record_iterator().enumerate() {
+      let left_key = keys.left_key(left_record);
       
-      match hash_table.get(left_column_value) {
+      match hash_table.get_key_value(&left_key) {
         // If hash table doesn't have this value, 
         // we know for sure that this record does not 
         // participate in the join
-        None => continue,
+        None => {
+          if self.kind == JoinKind::LeftAnti {
+            join_result.left_only(l as u32, left_record);
+          } else if self.kind.keeps_unmatched_left() {
+            join_result.left_padded(l as u32, left_record);
+          }
+        },
+
+        // Semi joins only need to know that a match exists
+        Some(_) if !self.kind.emits_pairs() => {
+          if self.kind == JoinKind::LeftSemi {
+            join_result.left_only(l as u32, left_record);
+          }
+        },
 
         // But if there are some matches for the value,
         // then we know they ALL participate in the join
-        Some(right_record_matches) => {
+        Some((key, right_record_matches)) => {
           for right_record in right_record_matches {
-            let join_record: Record = Record::merge(left_record, right_record);
-            join_result.push(join_record);
+            join_result.pair(l as u32, left_record, row_id(right_records, right_record), right_record);
+          }
+          if self.kind.keeps_unmatched_right() {
+            matched_keys.insert(*key);
           }
         }
       };
     }
 
-    self.left.rewind();
-
-    join_result
+    // Every right record whose key was never probed is unmatched
+    if self.kind.keeps_unmatched_right() {
+      for (r, right_record) in right_records.iter().enumerate() {
+        if !matched_keys.contains(&keys.right_key(right_record)) {
+          join_result.right_padded(r as u32, right_record);
+        }
+      }
+    }
   }
 }
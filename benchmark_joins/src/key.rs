@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use crate::record::Record;
+use crate::schema::Schema;
+
+// The pairs of columns a join compares, i.e. the join
+// holds when left[left_columns[i]] == right[right_columns[i]]
+// for every i. A single pair is the usual one-column equi-join.
+// As in SQL, a key with a NULL column matches nothing
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinKeys {
+	left_columns: Vec<usize>,
+	right_columns: Vec<usize>,
+}
+
+impl JoinKeys {
+	pub fn new(column_pairs: &[(usize, usize)]) -> Self {
+		if column_pairs.is_empty() {
+			panic!("Join needs at least one pair of columns");
+		}
+
+		Self {
+			left_columns: column_pairs.iter().map(|p| p.0).collect(),
+			right_columns: column_pairs.iter().map(|p| p.1).collect(),
+		}
+	}
+
+	pub fn single(left_col: usize, right_col: usize) -> Self {
+		Self::new(&[(left_col, right_col)])
+	}
+
+	pub fn left_columns(&self) -> &[usize] {
+		&self.left_columns
+	}
+
+	pub fn right_columns(&self) -> &[usize] {
+		&self.right_columns
+	}
+
+	pub fn num_columns(&self) -> usize {
+		self.left_columns.len()
+	}
+
+	// Keys on the record slots of the tables' columns, which is what
+	// the operators compare. Wide columns become a pair per slot, and
+	// all Int32 tables keep the same keys. Joined columns must have
+	// the same type
+	pub fn resolve(&self, left: &Schema, right: &Schema) -> JoinKeys {
+		if left.is_int32() && right.is_int32() {
+			return self.clone();
+		}
+
+		let mut pairs: Vec<(usize, usize)> = Vec::new();
+		for (&l, &r) in self.left_columns.iter().zip(self.right_columns.iter()) {
+			let (left_type, right_type) = (left.column_type(l), right.column_type(r));
+			if left_type != right_type {
+				panic!("Cannot join {} column {:?} with {} column {:?}", left_type, l, right_type, r);
+			}
+			pairs.extend(left.slots(l).zip(right.slots(r)));
+		}
+		JoinKeys::new(&pairs)
+	}
+
+	pub fn left_key<'a>(&'a self, record: &'a Record) -> KeyRef<'a> {
+		KeyRef::new(record, &self.left_columns)
+	}
+
+	pub fn right_key<'a>(&'a self, record: &'a Record) -> KeyRef<'a> {
+		KeyRef::new(record, &self.right_columns)
+	}
+
+	// Whether the left and right records satisfy the join
+	pub fn matches(&self, left: &Record, right: &Record) -> bool {
+		let (left_key, right_key) = (self.left_key(left), self.right_key(right));
+		!left_key.is_null() && !right_key.is_null() && left_key == right_key
+	}
+
+	// Lexicographic order of the left key against the right key
+	pub fn compare(&self, left: &Record, right: &Record) -> Ordering {
+		self.left_key(left).cmp(&self.right_key(right))
+	}
+}
+
+// Borrowed view of the join key columns of a record. Hashing and
+// comparing walk the columns in place, so composite keys can be
+// used in hash tables without copying them out of the record.
+// Keys from either side of the join compare by value, and a NULL
+// column equals only NULL and sorts after every value. Operators
+// skip NULL keys themselves, since they must never match
+#[derive(Debug, Clone, Copy)]
+pub struct KeyRef<'a> {
+	record: &'a Record,
+	columns: &'a [usize],
+}
+
+impl<'a> KeyRef<'a> {
+	pub fn new(record: &'a Record, columns: &'a [usize]) -> Self {
+		Self {
+			record,
+			columns,
+		}
+	}
+
+	pub fn values(&self) -> impl Iterator<Item = &'a i32> + 'a {
+		let record = self.record;
+		self.columns.iter().map(move |c| record.get_column(*c))
+	}
+
+	// Whether any column of the key is NULL
+	pub fn is_null(&self) -> bool {
+		self.record.has_nulls() && self.columns.iter().any(|c| self.record.is_null(*c))
+	}
+
+	// Whether neither key can have a NULL column,
+	// so that the values alone decide equality and order
+	fn without_nulls(&self, other: &Self) -> bool {
+		!self.record.has_nulls() && !other.record.has_nulls()
+	}
+
+	// Values paired with whether they are NULL, which comes first
+	// so that NULLs sort last
+	fn nullable_values(&self) -> impl Iterator<Item = (bool, i32)> + 'a {
+		let record = self.record;
+		self.columns.iter().map(move |c| (record.is_null(*c), *record.get_column(*c)))
+	}
+
+	// Value used to pick radix partitions. A single column keeps
+	// its own value, so its low bits stay the partition number.
+	// Composite keys are mixed so that every column contributes
+	pub fn radix_value(&self) -> i32 {
+		if self.columns.len() == 1 {
+			return *self.record.get_column(self.columns[0]);
+		}
+
+		let mut h: u32 = 0;
+		for v in self.values() {
+			h = (h.rotate_left(5) ^ (*v as u32)).wrapping_mul(0x9E37_79B9);
+		}
+		(h ^ (h >> 16)) as i32
+	}
+}
+
+impl<'a> Hash for KeyRef<'a> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		// NULL columns hold 0, so equal keys hash alike
+		for v in self.values() {
+			v.hash(state);
+		}
+	}
+}
+
+impl<'a> PartialEq for KeyRef<'a> {
+	fn eq(&self, other: &Self) -> bool {
+		if self.columns.len() != other.columns.len() {
+			return false;
+		}
+		if self.without_nulls(other) {
+			return self.values().eq(other.values());
+		}
+		self.nullable_values().eq(other.nullable_values())
+	}
+}
+
+impl<'a> Eq for KeyRef<'a> {}
+
+impl<'a> Ord for KeyRef<'a> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		if self.without_nulls(other) {
+			return self.values().cmp(other.values());
+		}
+		self.nullable_values().cmp(other.nullable_values())
+	}
+}
+
+impl<'a> PartialOrd for KeyRef<'a> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
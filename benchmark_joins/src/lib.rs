@@ -1,7 +1,23 @@
 pub mod readtable;
+pub mod bintable;
+pub mod arrowtable;
 pub mod record;
+pub mod schema;
+pub mod rows;
+pub mod widetable;
+pub mod widejoin;
 pub mod runner;
 pub mod table;
+pub mod columnartable;
+pub mod columnarjoin;
 pub mod join;
+pub mod aggregate;
+pub mod plan;
+pub mod materialize;
 pub mod radixjoin;
 pub mod parjoin;
+pub mod registry;
+pub mod key;
+pub mod thetajoin;
+pub mod gracejoin;
+pub mod skew;
@@ -1,6 +1,16 @@
-use joinlib::runner::run_one_join;
+use joinlib::aggregate::Aggregate;
+use joinlib::runner::run_registered_join;
+use joinlib::runner::JoinCondition;
 use joinlib::runner::JoinRunResult;
+use joinlib::runner::OutputMode;
+use joinlib::runner::RunOptions;
 use joinlib::join::JoinAlgos;
+use joinlib::join::JoinKind;
+use joinlib::join::JoinParams;
+use joinlib::key::JoinKeys;
+use joinlib::materialize::Projection;
+use joinlib::registry::JoinRegistry;
+use joinlib::thetajoin::ThetaPredicate;
 use std::path::Path;
 use std::fs::OpenOptions;
 use std::io::BufReader;
@@ -9,12 +19,63 @@ use std::process;
 
 fn main() {
 
-	let args: Vec<String> = env::args().collect();
-	if args.len() != 7+1 {
-		println!("Expected [left_table] [right_tables] [json_outfile] [left_block_size] [right_block_size] [join_algo] [num_trials]");
+	// Options like --memory-budget=BYTES may follow the positional arguments
+	let (options, args): (Vec<String>, Vec<String>) = env::args().partition(|a| a.starts_with("--"));
+	if args.len() != 7+1 && args.len() != 8+1 {
+		println!("Expected [left_table] [right_tables] [json_outfile] [left_block_size] [right_block_size] [join_algo[:inner|left|right|full|semi|anti]] [num_trials] [join_columns|theta_predicate]? [--memory-budget=BYTES]? [--radix-bits=BITS,...]? [--parallel-partitioning]? [--write-combining]? [--skew-handling]? [--wide]? [--columnar]? [--late-materialization]? [--project=COLUMNS:COLUMNS]? [--batch-size=RECORDS]? [--count]? [--aggregate=FUNCTION[:COLUMN]]? [--distinct]? [--output-table=PATH]?");
 		process::exit(1);
 	}
 
+	// Parse options
+	let mut memory_budget_bytes: usize = 0;
+	let mut radix_bits: Vec<u32> = Vec::new();
+	let mut parallel_partitioning = false;
+	let mut write_combining = false;
+	let mut skew_handling = false;
+	let mut run_options = RunOptions::default();
+	for option in options.iter() {
+		match option.split_once('=') {
+			Some(("--memory-budget", v)) => memory_budget_bytes = v.parse().unwrap(),
+			// Bits of each radix pass separated by , e.g. 5,5
+			Some(("--radix-bits", v)) => radix_bits = v.split(',').map(|b| b.parse().unwrap()).collect(),
+			// Where to write the join output of the last trial, as
+			// CSV, .bin, .rec, .arrow or .parquet by its extension
+			Some(("--output-table", v)) => run_options.output_table = Some(v.to_owned()),
+			// Columns to materialize of each side, e.g. 0,2:* for columns
+			// 0 and 2 of the left table and all of the right table
+			Some(("--project", v)) => run_options.projection = match v.parse::<Projection>() {
+				Err(e) => panic!("Could not parse projection {:?}", e),
+				Ok(p) => Some(p),
+			},
+			// Stream the join output in batches of this many records,
+			// which are counted and dropped rather than collected
+			Some(("--batch-size", v)) => run_options.batch_size = Some(v.parse().unwrap()),
+			// Aggregate the join output per join key rather than keep it,
+			// e.g. sum:3 for the sum of output column 3, or count
+			Some(("--aggregate", v)) => run_options.output_mode = match v.parse::<Aggregate>() {
+				Err(e) => panic!("Could not parse aggregate {:?}", e),
+				Ok(a) => OutputMode::Aggregate(a),
+			},
+			// Only count the join output
+			None if option == "--count" => run_options.output_mode = OutputMode::Count,
+			// Run a semi or anti join as an inner join, then deduplicate
+			// its left rows, to compare the semi and anti joins against
+			None if option == "--distinct" => run_options.output_mode = OutputMode::Distinct,
+			None if option == "--parallel-partitioning" => parallel_partitioning = true,
+			None if option == "--write-combining" => write_combining = true,
+			None if option == "--skew-handling" => skew_handling = true,
+			// Tables wider than a record, joined through their key columns
+			None if option == "--wide" => run_options.wide = true,
+			// Tables stored column by column, for hash and radix
+			None if option == "--columnar" => run_options.columnar = true,
+			// Join into row ids, then build the output records apart
+			None if option == "--late-materialization" => {
+				run_options.projection.get_or_insert_with(Projection::default);
+			},
+			_ => panic!("Unrecognized option {:?}", option),
+		}
+	}
+
 	// Parse left table
 	let left_table_name: &str = args.get(1).unwrap();
 
@@ -31,6 +92,43 @@ fn main() {
 	let raw_join_algo: &str = args.get(6).unwrap();
 	let num_trials: i8 = args.get(7).unwrap().parse().unwrap();
 
+	// Parse join columns as left:right pairs separated
+	// by , e.g. 5:5,6:6, defaulting to column 5 on both sides.
+	// Bands like 5:5~100 and inequalities like 5<5 or 5>=5,6<6
+	// are theta join predicates instead
+	let raw_join_columns: &str = args.get(8).map(|a| a.as_str()).unwrap_or("5:5");
+	let join_condition = if raw_join_columns.contains(['<', '>', '~']) {
+		match raw_join_columns.parse::<ThetaPredicate>() {
+			Err(e) => panic!("Could not parse theta predicate {:?}", e),
+			Ok(predicate) => JoinCondition::Theta(predicate),
+		}
+	} else {
+		let mut join_columns: Vec<(usize, usize)> = Vec::new();
+		for pair in raw_join_columns.split(',') {
+			let (l, r) = match pair.split_once(':') {
+				None => panic!("Expected left:right join columns, got {:?}", pair),
+				Some(cols) => cols,
+			};
+			join_columns.push((l.parse().unwrap(), r.parse().unwrap()));
+		}
+		JoinCondition::Keys(JoinKeys::new(&join_columns))
+	};
+
+	// Outer, semi and anti joins are requested with a suffix, e.g. hash:left
+	let (raw_join_algo, raw_join_kind) = match raw_join_algo.split_once(':') {
+		None => (raw_join_algo, "inner"),
+		Some((algo, kind)) => (algo, kind),
+	};
+	let join_kind = match raw_join_kind {
+		"inner" => JoinKind::Inner,
+		"left" => JoinKind::LeftOuter,
+		"right" => JoinKind::RightOuter,
+		"full" => JoinKind::FullOuter,
+		"semi" => JoinKind::LeftSemi,
+		"anti" => JoinKind::LeftAnti,
+		_ => panic!("Unrecognized join kind {:?}", raw_join_kind),
+	};
+
 	// Match raw join algo to actual join algo
 	let join_algo = match raw_join_algo {
 		"nl" => JoinAlgos::NLJoin,
@@ -40,15 +138,25 @@ fn main() {
 		"pnl" => JoinAlgos::PNLJoin,
 		"psh" => JoinAlgos::ParallelSimpleHashJoin,
 		"pulf" => JoinAlgos::ParallelUnaryLeapFrogJoin,
+		"band" => JoinAlgos::BandJoin,
+		"iejoin" => JoinAlgos::IEJoin,
+		"grace" => JoinAlgos::GraceHashJoin,
 		_ => panic!("Unrecognized join algo {:?}", raw_join_algo),
 	};
 
+	// Reject options which cannot be combined before running anything
+	if let Err(e) = run_options.check(&join_algo, &join_condition, join_kind) {
+		println!("{}", e);
+		process::exit(1);
+	}
+
 	// Fetch array of results from outfile
 	let outpath = Path::new(args.get(3).unwrap());
 	let mut results: Vec<JoinRunResult>;
 	{
 		let outfile = match OpenOptions::new()
 			.create(true)
+			.truncate(false)
 			.read(true)
 			.write(true)
 			.open(outpath) {
@@ -62,28 +170,57 @@ fn main() {
 	}
 
 	// Profile our joins on the input tables
+	let registry = JoinRegistry::new();
+	let params = JoinParams {
+		left_block_size,
+		right_block_size,
+		join_kind,
+		memory_budget_bytes,
+		radix_bits,
+		parallel_partitioning,
+		write_combining,
+		skew_handling,
+	};
 
-	println!("");
+	println!();
 	println!("Left table 1 of 1...");
 
 	for (i, rtn) in right_table_names.iter().enumerate() {
 		println!("\tRight table {:?} of {:?}...", i+1, right_table_names.len());
 
+		// With several right tables, each output is named after its right table
+		let output_path = run_options.output_table.as_ref().map(|path| {
+			if right_table_names.len() == 1 {
+				return path.clone();
+			}
+			let path = Path::new(path);
+			let stem = Path::new(rtn).file_stem().unwrap().to_string_lossy();
+			let mut name = format!("{}_{}", path.file_stem().unwrap().to_string_lossy(), stem);
+			if let Some(extension) = path.extension() {
+				name.push('.');
+				name.push_str(&extension.to_string_lossy());
+			}
+			path.with_file_name(name).to_string_lossy().into_owned()
+		});
+
 		for trial in 1..=num_trials {
 			println!("\t\tTrial {:?} of {:?}...", trial, num_trials);
+			let trial_options = RunOptions {
+				output_table: output_path.clone().filter(|_| trial == num_trials),
+				..run_options.clone()
+			};
 
 			// Run the join and get its results
-			let mut r = run_one_join(
-				left_table_name, 
-				&rtn, 
-				5, 
-				5, 
-				left_block_size, 
-				right_block_size,
-				&join_algo
+			let mut r = run_registered_join(
+				&registry,
+				(left_table_name, rtn),
+				&join_condition,
+				&params,
+				&join_algo,
+				&trial_options
 			);
 			// Set the trial number
-			r.trial_number = trial.clone();
+			r.trial_number = trial;
 			
 			results.push(r);
 		}
@@ -0,0 +1,172 @@
+use std::str::FromStr;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSliceMut;
+
+use crate::join::{JoinIndex, JoinKind, NULL_ROW};
+use crate::record::{Record, M};
+use crate::schema::Schema;
+use crate::table::SimpleTable;
+
+// Columns of each side to keep, by index in that side's schema.
+// None keeps every column, as the eager joins do
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projection {
+  pub left: Option<Vec<usize>>,
+  pub right: Option<Vec<usize>>,
+}
+
+impl Projection {
+  pub fn new(left: &[usize], right: &[usize]) -> Self {
+    Self {
+      left: Some(left.to_vec()),
+      right: Some(right.to_vec()),
+    }
+  }
+
+  // Whether every column of both sides is kept
+  pub fn keeps_all(&self) -> bool {
+    self.left.is_none() && self.right.is_none()
+  }
+
+  // Schema of the materialized records, which have
+  // no right columns for semi and anti joins
+  pub fn schema(&self, left: &Schema, right: &Schema, kind: JoinKind) -> Schema {
+    let left = project_schema(left, &self.left);
+    match kind {
+      JoinKind::LeftSemi | JoinKind::LeftAnti => left,
+      _ => left.merge(&project_schema(right, &self.right)),
+    }
+  }
+}
+
+// Columns of each side separated by :, each side a list of column
+// indexes separated by , or * for all of them, e.g. 0,2:* or 1:
+impl FromStr for Projection {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let side = |columns: &str| -> Result<Option<Vec<usize>>, String> {
+      match columns.trim() {
+        "*" => Ok(None),
+        "" => Ok(Some(Vec::new())),
+        columns => columns.split(',')
+          .map(|c| c.trim().parse::<usize>().map_err(|e| format!("{:?} is not a column index, {}", c, e)))
+          .collect::<Result<Vec<_>, _>>()
+          .map(Some),
+      }
+    };
+    match s.split_once(':') {
+      None => Err(format!("Expected left:right projected columns, got {:?}", s)),
+      Some((left, right)) => Ok(Projection {
+        left: side(left)?,
+        right: side(right)?,
+      }),
+    }
+  }
+}
+
+fn project_schema(schema: &Schema, columns: &Option<Vec<usize>>) -> Schema {
+  match columns {
+    None => schema.clone(),
+    Some(columns) => schema.project(columns),
+  }
+}
+
+// Record slots of the kept columns
+fn projected_slots(schema: &Schema, columns: &Option<Vec<usize>>) -> Vec<usize> {
+  match columns {
+    None => (0..schema.num_slots()).collect(),
+    Some(columns) => columns.iter().flat_map(|c| schema.slots(*c)).collect(),
+  }
+}
+
+// Record of a row id, or the padding for NULL_ROW
+fn row_or<'a>(records: &'a [Record], row: u32, nulls: &'a Record) -> &'a Record {
+  match row {
+    NULL_ROW => nulls,
+    row => &records[row as usize],
+  }
+}
+
+// Left records of a semi or anti join, from the index of an inner
+// join on the same keys: the baseline the semi and anti operators are
+// compared against. The left row ids are deduplicated by sorting, then
+// the left rows among them are kept for semi joins and the others for
+// anti joins, in left table order
+pub fn distinct_left(index: &JoinIndex, left: &SimpleTable, kind: JoinKind) -> Vec<Record> {
+  let mut rows: Vec<u32> = index.left.clone();
+  rows.par_sort_unstable();
+  rows.dedup();
+  let records = left.record_view();
+  match kind {
+    JoinKind::LeftSemi => rows.iter().map(|r| records[*r as usize].clone()).collect(),
+    JoinKind::LeftAnti => {
+      let mut matched = rows.iter().peekable();
+      (0..).zip(records)
+        .filter(|(row, _)| matched.next_if(|m| **m == *row).is_none())
+        .map(|(_, r)| r.clone())
+        .collect()
+    },
+    _ => panic!("Only semi and anti joins keep distinct left records, not {:?}", kind),
+  }
+}
+
+// Builds the output records of a join index taken from tables as the
+// join left them. Rows of the index are merged like the eager joins
+// merge records, NULL_ROW giving NULL padding, but only the projected
+// columns are copied. Semi and anti joins output left records only
+pub fn materialize(
+  index: &JoinIndex,
+  left: &SimpleTable,
+  right: &SimpleTable,
+  kind: JoinKind,
+  projection: &Projection) -> Vec<Record> {
+  let left_records = left.record_view();
+  let right_records = right.record_view();
+  let left_only = !kind.emits_pairs();
+
+  // Without a projection the records are merged whole, as eagerly
+  if projection.keeps_all() {
+    let left_nulls = Record::nulls(left.get_num_columns_per_record());
+    let right_nulls = Record::nulls(right.get_num_columns_per_record());
+    return (0..index.len()).into_par_iter()
+      .map(|i| match left_only {
+        true => left_records[index.left[i] as usize].clone(),
+        false => Record::merge(
+          row_or(left_records, index.left[i], &left_nulls),
+          row_or(right_records, index.right[i], &right_nulls)),
+      })
+      .collect();
+  }
+
+  let left_slots = projected_slots(left.schema(), &projection.left);
+  let right_slots = match left_only {
+    true => Vec::new(),
+    false => projected_slots(right.schema(), &projection.right),
+  };
+  let width = left_slots.len() + right_slots.len();
+  if width > M {
+    panic!("Projection takes {:?} slots, but max support is {:?}", width, M);
+  }
+
+  (0..index.len()).into_par_iter()
+    .map(|i| {
+      let mut record = Record::nulls(width);
+      let sides = [
+        (index.left[i], left_records, &left_slots, 0),
+        (index.right.get(i).copied().unwrap_or(NULL_ROW), right_records, &right_slots, left_slots.len()),
+      ];
+      for (row, records, slots, offset) in sides {
+        if row == NULL_ROW {
+          continue;
+        }
+        let source = &records[row as usize];
+        for (j, &slot) in slots.iter().enumerate().filter(|(_, slot)| !source.is_null(**slot)) {
+          record.set_column(offset + j, *source.get_column(slot));
+        }
+      }
+      record
+    })
+    .collect()
+}
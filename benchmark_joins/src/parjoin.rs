@@ -1,10 +1,12 @@
-use std::{collections::HashMap};
+use std::{cmp::Ordering, collections::{HashMap, HashSet}};
+use crate::join::{row_id, JoinAlgos, JoinInto, JoinKind, JoinOperator, JoinOutput, JoinParams};
+use crate::key::{JoinKeys, KeyRef};
 use crate::table::SimpleTable;
 use crate::record::Record;
-use itertools::Itertools;
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use crate::skew::{detect_heavy_keys, join_heavy_keys};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
 
-const CHUNK_SIZE: usize = 4_300;
+pub(crate) const CHUNK_SIZE: usize = 4_300;
 
 pub struct ParallelNestedLoopsJoin<'a> {
 	left: &'a mut SimpleTable,
@@ -19,28 +21,43 @@ impl<'a> ParallelNestedLoopsJoin<'a> {
 			right
 		}
 	}
+}
 
-	pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
-		// Number of records in left tables
-		let left_size = self.left.get_num_records();
+impl<'a> JoinOperator for ParallelNestedLoopsJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::PNLJoin
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> JoinInto for ParallelNestedLoopsJoin<'a> {
+	fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
 
-		self.left.record_par_iterator()
+		// Each chunk of left records fills its own output
+		let intermediate_join_results: Vec<O> = self.left.record_par_iterator()
+		.enumerate()
 		.chunks(CHUNK_SIZE)
 		.map(|left_records| {
-			let mut intermediate_join_result = Vec::with_capacity(left_size);
-			for lr in left_records {
-				for rr in self.right.record_iterator() {
-					if lr.get_column(left_col) == rr.get_column(right_col) {
+			let mut intermediate_join_result = join_result.split();
+			for (l, lr) in left_records {
+				for (r, rr) in self.right.record_iterator().enumerate() {
+					if keys.matches(lr, rr) {
 						// Join condition is met ==> new record 
-						let join_record = Record::merge(lr, rr);
-						intermediate_join_result.push(join_record);
+						intermediate_join_result.pair(l as u32, lr, r as u32, rr);
 					}
 				}
 			}
 			intermediate_join_result
 		})
-		.flatten()
-		.collect()
+		.collect();
+
+		for intermediate_join_result in intermediate_join_results {
+			join_result.absorb(intermediate_join_result);
+		}
 	}
 }
 
@@ -48,55 +65,150 @@ impl<'a> ParallelNestedLoopsJoin<'a> {
 pub struct ParallelSimpleHashJoin<'a> {
 	left: &'a mut SimpleTable,
 	right: &'a mut SimpleTable,
+	kind: JoinKind,
+	skew_handling: bool,
 }
 
 impl<'a> ParallelSimpleHashJoin<'a> {
 	
 	pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+		Self::with_kind(left, right, JoinKind::Inner)
+	}
+
+	pub fn with_kind(left: &'a mut SimpleTable, right: &'a mut SimpleTable, kind: JoinKind) -> Self {
 		Self {
 			left,
-			right
+			right,
+			kind,
+			skew_handling: false,
+		}
+	}
+
+	// Samples the right table for heavy keys, whose
+	// pairs are then spread over all of the workers
+	pub fn with_skew_handling(left: &'a mut SimpleTable, right: &'a mut SimpleTable, kind: JoinKind) -> Self {
+		Self {
+			skew_handling: true,
+			..Self::with_kind(left, right, kind)
+		}
+	}
+}
+
+impl<'a> JoinOperator for ParallelSimpleHashJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::ParallelSimpleHashJoin
+	}
+
+	fn parameters(&self) -> JoinParams {
+		JoinParams {
+			join_kind: self.kind,
+			skew_handling: self.skew_handling,
+			..JoinParams::default()
 		}
 	}
 
-	pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
+	fn join_kind(&self) -> JoinKind {
+		self.kind
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> JoinInto for ParallelSimpleHashJoin<'a> {
+	fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
+
 		// Number of records in left and right tables
 		let right_size = self.right.get_num_records();
 
-		let mut hash_table: HashMap<&i32, Vec<&Record>> = HashMap::with_capacity(right_size);
+		let mut hash_table: HashMap<KeyRef, Vec<&Record>> = HashMap::with_capacity(right_size);
 
 		let right_records = self.right.record_view();
 		assert!(right_size == right_records.len());
 
 		// Now we build the hash table on the smaller table
-		// since this results in the fewest operations during join
+		// since this results in the fewest operations during join.
+		// NULL keys match nothing, so they are left out
 		for r in right_records {
-			let right_column_value = r.get_column(right_col);
+			let right_key = keys.right_key(r);
+			if right_key.is_null() {
+				continue;
+			}
 
-			// Map right join column value the record itself
-			hash_table.entry(right_column_value).or_insert(Vec::new()).push(r);
+			// Map right join key the record itself
+			hash_table.entry(right_key).or_default().push(r);
 		}
 		
-		self.left.record_par_iterator()
+		let kind = self.kind;
+
+		// Left records with heavy keys are joined on their own afterwards
+		let heavy_keys: HashSet<KeyRef> = match self.skew_handling {
+			true => detect_heavy_keys(right_records, keys.right_columns()),
+			false => HashSet::new(),
+		};
+
+		let intermediate_join_results: Vec<O> = self.left.record_par_iterator()
+			.enumerate()
 			.chunks(CHUNK_SIZE)
-			// Map each left record chunk to group of joined records [R_1, ..., R_k]
-			.map(|left_records| -> Vec<Record> {
-				left_records.iter()
-				.map(|lr| -> Vec<Record> {
-					hash_table
-						.get(lr.get_column(left_col)).unwrap_or(&Vec::new())
-						.iter()
-						.map(|rr| Record::merge(&lr, rr))
-						.collect_vec()
-				})
-				.flatten()
-				.collect()
+			// Map each left record chunk to its own output
+			.map(|left_records| -> O {
+				let mut intermediate_join_result = join_result.split();
+				for (l, lr) in left_records {
+					if !heavy_keys.is_empty() && heavy_keys.contains(&keys.left_key(lr)) {
+						continue;
+					}
+					let l = l as u32;
+					match hash_table.get(&keys.left_key(lr)) {
+						Some(_) if kind == JoinKind::LeftSemi => intermediate_join_result.left_only(l, lr),
+						Some(_) if kind == JoinKind::LeftAnti => {},
+						Some(right_record_matches) => {
+							for rr in right_record_matches {
+								intermediate_join_result.pair(l, lr, row_id(right_records, rr), rr);
+							}
+						},
+						None if kind == JoinKind::LeftAnti => intermediate_join_result.left_only(l, lr),
+						None if kind.keeps_unmatched_left() => intermediate_join_result.left_padded(l, lr),
+						None => {},
+					}
+				}
+				intermediate_join_result
 			})
-			// Flatten group of joined records into one [R_1, ..., R_k, ..., R_n]
-			.flatten()
-			
-			// Collect as vec
-			.collect()
+			.collect();
+		for intermediate_join_result in intermediate_join_results {
+			join_result.absorb(intermediate_join_result);
+		}
+
+		if !heavy_keys.is_empty() {
+			let left_records = self.left.record_view();
+			let heavy_left: Vec<&Record> = left_records.par_iter()
+				.filter(|lr| heavy_keys.contains(&keys.left_key(lr)))
+				.collect();
+			join_heavy_keys(keys, (left_records, right_records), &heavy_left, &hash_table, kind, join_result);
+		}
+
+		// A right record is unmatched when no left record shares its key
+		if kind.keeps_unmatched_right() {
+			let left_keys: HashSet<KeyRef> = self.left.record_par_iterator()
+				.map(|lr| keys.left_key(lr))
+				.filter(|key| !key.is_null())
+				.collect();
+			let unmatched_right: Vec<O> = right_records.par_iter()
+				.enumerate()
+				.chunks(CHUNK_SIZE)
+				.map(|right_chunk| {
+					let mut unmatched = join_result.split();
+					for (r, rr) in right_chunk.into_iter().filter(|(_, rr)| !left_keys.contains(&keys.right_key(rr))) {
+						unmatched.right_padded(r as u32, rr);
+					}
+					unmatched
+				})
+				.collect();
+			for unmatched in unmatched_right {
+				join_result.absorb(unmatched);
+			}
+		}
 	}
 }
 
@@ -104,30 +216,36 @@ impl<'a> ParallelSimpleHashJoin<'a> {
 pub struct ParallelUnaryLeapFrogJoin<'a> {
 	left: &'a mut SimpleTable,
 	right: &'a mut SimpleTable,
+	kind: JoinKind,
 }
 
 impl<'a> ParallelUnaryLeapFrogJoin<'a> {
 	
 	pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+		Self::with_kind(left, right, JoinKind::Inner)
+	}
+
+	pub fn with_kind(left: &'a mut SimpleTable, right: &'a mut SimpleTable, kind: JoinKind) -> Self {
 		Self {
 			left,
-			right
+			right,
+			kind,
 		}
 	}
 
-	fn get_run_length(&self, table: &[Record], column: usize, mut i: usize) -> usize {
+	fn get_run_length(&self, table: &[Record], columns: &[usize], mut i: usize) -> usize {
 		// Determine the span, or "run length", of the table starting
 		// at index i such that the for the next span-1 elements, the 
-		// values of the table at the column are equivalent to that of 
-		// table[i] at the column
+		// values of the table at the key columns are equivalent to 
+		// that of table[i] at the key columns
 		let mut run_length = 1;
 
 		// Run throught the table starting at i, stopping if 
 		// 1) we reach end of the table, OR
-		// 2) we encounter a value not equal to table[i][column]
+		// 2) we encounter a key not equal to table[i][columns]
 		while i < table.len() - 1 {
 			i += 1;
-			if table[i].get_column(column) != table[i - 1].get_column(column) {
+			if KeyRef::new(&table[i], columns) != KeyRef::new(&table[i - 1], columns) {
 				break;
 			}
 			run_length += 1;
@@ -135,22 +253,45 @@ impl<'a> ParallelUnaryLeapFrogJoin<'a> {
 		
 		run_length
 	}
+}
+
+impl<'a> JoinOperator for ParallelUnaryLeapFrogJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::ParallelUnaryLeapFrogJoin
+	}
+
+	fn parameters(&self) -> JoinParams {
+		JoinParams {
+			join_kind: self.kind,
+			..JoinParams::default()
+		}
+	}
+
+	fn join_kind(&self) -> JoinKind {
+		self.kind
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> JoinInto for ParallelUnaryLeapFrogJoin<'a> {
+	// Sorts both tables, so row ids index them as sorted by the join
+	fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
 
-	pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
 		// Number of records in left and right tables
 		let left_size = self.left.get_num_records();
 		let right_size = self.right.get_num_records();
 
-		// Final tuples emitted by the join
-		let mut join_results = Vec::with_capacity(left_size);
-
 		// Sort the tables asynchronously and in parallel
 		let mut tables = vec![
-			(&mut self.left, left_col), 
-			(&mut self.right, right_col)
+			(&mut self.left, keys.left_columns()), 
+			(&mut self.right, keys.right_columns())
 		];
 		tables.par_iter_mut().for_each(|tup| {
-			tup.0.sort_by(tup.1);
+			tup.0.sort_by_columns(tup.1);
 		});
 
 		let left_record_view = self.left.record_view();
@@ -162,33 +303,77 @@ impl<'a> ParallelUnaryLeapFrogJoin<'a> {
 		while l < left_size && r < right_size {
 
 			// Run lengths
-			let left_run_length = self.get_run_length(left_record_view, left_col, l);
-			let right_run_length = self.get_run_length(right_record_view, right_col, r);
+			let left_run_length = self.get_run_length(left_record_view, keys.left_columns(), l);
+			let right_run_length = self.get_run_length(right_record_view, keys.right_columns(), r);
 			
-			// Join column values
-			let left_value = left_record_view[l].get_column(left_col);
-			let right_value = right_record_view[r].get_column(right_col);
+			// Compare join keys lexicographically. NULL keys sort
+			// last and match nothing, so a NULL run is unmatched
+			let order = if keys.left_key(&left_record_view[l]).is_null() {
+				Ordering::Less
+			} else if keys.right_key(&right_record_view[r]).is_null() {
+				Ordering::Greater
+			} else {
+				keys.compare(&left_record_view[l], &right_record_view[r])
+			};
 
-			if left_value == right_value {
+			if order == Ordering::Equal {
 				// Collect the runs that match
-				for ll in l..l+left_run_length {
-					for rr in r..r+right_run_length {
-						let join_record: Record = Record::merge(&left_record_view[ll], &right_record_view[rr]);
-						join_results.push(join_record);
+				if self.kind == JoinKind::LeftSemi {
+					for (i, left_record) in left_record_view.iter().enumerate().skip(l).take(left_run_length) {
+						join_result.left_only(i as u32, left_record);
+					}
+				}
+				else if self.kind.emits_pairs() {
+					for (i, left_record) in left_record_view.iter().enumerate().skip(l).take(left_run_length) {
+						for (j, right_record) in right_record_view.iter().enumerate().skip(r).take(right_run_length) {
+							join_result.pair(i as u32, left_record, j as u32, right_record);
+						}
 					}
 				}
 				// Move forward by the run length
 				l += left_run_length;
 				r += right_run_length;
 			}
-			else if left_value < right_value {
+			else if order == Ordering::Less {
+				// Left run has no partner on the right
+				if self.kind == JoinKind::LeftAnti {
+					for (i, left_record) in left_record_view.iter().enumerate().skip(l).take(left_run_length) {
+						join_result.left_only(i as u32, left_record);
+					}
+				}
+				else if self.kind.keeps_unmatched_left() {
+					for (i, left_record) in left_record_view.iter().enumerate().skip(l).take(left_run_length) {
+						join_result.left_padded(i as u32, left_record);
+					}
+				}
 				l += left_run_length;
 			}
 			else {
+				// Right run has no partner on the left
+				if self.kind.keeps_unmatched_right() {
+					for (j, right_record) in right_record_view.iter().enumerate().skip(r).take(right_run_length) {
+						join_result.right_padded(j as u32, right_record);
+					}
+				}
 				r += right_run_length;
 			}
 		}
 
-		join_results
+		// Whatever remains on either side is unmatched
+		if self.kind == JoinKind::LeftAnti {
+			for (i, left_record) in left_record_view.iter().enumerate().skip(l) {
+				join_result.left_only(i as u32, left_record);
+			}
+		}
+		else if self.kind.keeps_unmatched_left() {
+			for (i, left_record) in left_record_view.iter().enumerate().skip(l) {
+				join_result.left_padded(i as u32, left_record);
+			}
+		}
+		if self.kind.keeps_unmatched_right() {
+			for (j, right_record) in right_record_view.iter().enumerate().skip(r) {
+				join_result.right_padded(j as u32, right_record);
+			}
+		}
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,372 @@
+use std::cmp::Ordering;
+use std::error::Error;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSliceMut;
+
+use crate::aggregate::{hash_aggregate, Aggregate};
+use crate::join::{JoinAlgos, JoinParams};
+use crate::key::JoinKeys;
+use crate::materialize::Projection;
+use crate::record::Record;
+use crate::registry::JoinRegistry;
+use crate::schema::{Schema, Value};
+use crate::table::SimpleTable;
+use crate::thetajoin::ThetaPredicate;
+
+// Comparison of a column with an operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+impl CompareOp {
+  pub fn holds(&self, ordering: Ordering) -> bool {
+    match self {
+      CompareOp::Eq => ordering == Ordering::Equal,
+      CompareOp::Ne => ordering != Ordering::Equal,
+      CompareOp::Lt => ordering == Ordering::Less,
+      CompareOp::Le => ordering != Ordering::Greater,
+      CompareOp::Gt => ordering == Ordering::Greater,
+      CompareOp::Ge => ordering != Ordering::Less,
+    }
+  }
+}
+
+// What a column is compared with
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+  Value(Value),
+  Column(usize),
+}
+
+// Condition a filter keeps records by. As in SQL, a comparison with
+// NULL is unknown, as is the negation of unknown, and only records
+// for which the condition holds are kept
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+  // column op operand
+  Compare {
+    column: usize,
+    op: CompareOp,
+    operand: Operand,
+  },
+  IsNull(usize),
+  And(Box<Predicate>, Box<Predicate>),
+  Or(Box<Predicate>, Box<Predicate>),
+  Not(Box<Predicate>),
+}
+
+impl Predicate {
+  pub fn compare(column: usize, op: CompareOp, value: Value) -> Self {
+    Predicate::Compare {
+      column,
+      op,
+      operand: Operand::Value(value),
+    }
+  }
+
+  pub fn compare_columns(column: usize, op: CompareOp, other: usize) -> Self {
+    Predicate::Compare {
+      column,
+      op,
+      operand: Operand::Column(other),
+    }
+  }
+
+  pub fn and(self, other: Predicate) -> Self {
+    Predicate::And(Box::new(self), Box::new(other))
+  }
+
+  pub fn or(self, other: Predicate) -> Self {
+    Predicate::Or(Box::new(self), Box::new(other))
+  }
+
+  // Whether the condition holds for a record of the schema,
+  // or None when it is unknown
+  pub fn eval(&self, schema: &Schema, record: &Record) -> Option<bool> {
+    match self {
+      Predicate::Compare { column, op, operand } => {
+        let value = record.get_value(schema, *column);
+        let ordering = match operand {
+          Operand::Value(other) => compare_values(&value, other),
+          Operand::Column(other) => compare_values(&value, &record.get_value(schema, *other)),
+        };
+        ordering.map(|o| op.holds(o))
+      },
+      Predicate::IsNull(column) => Some(record.is_null(schema.slots(*column).start)),
+      Predicate::And(a, b) => match (a.eval(schema, record), b.eval(schema, record)) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+      },
+      Predicate::Or(a, b) => match (a.eval(schema, record), b.eval(schema, record)) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+      },
+      Predicate::Not(a) => a.eval(schema, record).map(|holds| !holds),
+    }
+  }
+}
+
+// Order of two values, or None when either is NULL. Integers of
+// either width compare exactly, other numbers as f64s, and strings
+// as strings, not by their interned ids
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+  let integer = |v: &Value| match v {
+    Value::Int32(v) => *v as i64,
+    Value::Int64(v) => *v,
+    _ => unreachable!(),
+  };
+  let float = |v: &Value| match v {
+    Value::Float64(v) => *v,
+    v => integer(v) as f64,
+  };
+  match (a, b) {
+    (Value::Null, _) | (_, Value::Null) => None,
+    (Value::Utf8(a), Value::Utf8(b)) => Some(a.cmp(b)),
+    (Value::Utf8(_), _) | (_, Value::Utf8(_)) => panic!("Cannot compare {:?} with {:?}", a, b),
+    (Value::Int32(_) | Value::Int64(_), Value::Int32(_) | Value::Int64(_)) => Some(integer(a).cmp(&integer(b))),
+    _ => float(a).partial_cmp(&float(b)),
+  }
+}
+
+// A column to sort by. NULLs sort after every value, so
+// they come first when sorting in descending order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+  pub column: usize,
+  pub descending: bool,
+}
+
+impl SortKey {
+  pub fn asc(column: usize) -> Self {
+    Self {
+      column,
+      descending: false,
+    }
+  }
+
+  pub fn desc(column: usize) -> Self {
+    Self {
+      column,
+      descending: true,
+    }
+  }
+}
+
+// Records of the table for which the predicate holds, in table order
+pub fn filter(table: &SimpleTable, predicate: &Predicate) -> SimpleTable {
+  let schema = table.schema();
+  let records: Vec<Record> = table.record_par_iterator()
+    .filter(|r| predicate.eval(schema, r) == Some(true))
+    .cloned()
+    .collect();
+  SimpleTable::from_records(schema.clone(), records)
+}
+
+// The given columns of every record, in that order
+pub fn project(table: &SimpleTable, columns: &[usize]) -> SimpleTable {
+  let schema = table.schema().project(columns);
+  let slots: Vec<usize> = columns.iter().flat_map(|c| table.schema().slots(*c)).collect();
+  let records: Vec<Record> = table.record_par_iterator()
+    .map(|source| {
+      let mut record = Record::nulls(slots.len());
+      for (i, &slot) in slots.iter().enumerate().filter(|(_, slot)| !source.is_null(**slot)) {
+        record.set_column(i, *source.get_column(slot));
+      }
+      record
+    })
+    .collect();
+  SimpleTable::from_records(schema, records)
+}
+
+// Records sorted by the keys, the first deciding unless equal. Sorting
+// is stable, and compares values, so strings sort alphabetically
+pub fn sort(table: &SimpleTable, keys: &[SortKey]) -> SimpleTable {
+  let schema = table.schema();
+  let records = table.record_view();
+  let mut rows: Vec<(Vec<Value>, usize)> = (0..records.len()).into_par_iter()
+    .map(|i| (keys.iter().map(|k| records[i].get_value(schema, k.column)).collect(), i))
+    .collect();
+  rows.par_sort_by(|(a, _), (b, _)| {
+    keys.iter()
+      .zip(a.iter().zip(b))
+      .map(|(key, (a, b))| {
+        let ordering = match (a.is_null(), b.is_null()) {
+          (false, false) => compare_values(a, b).unwrap_or(Ordering::Equal),
+          (a_null, b_null) => a_null.cmp(&b_null),
+        };
+        if key.descending { ordering.reverse() } else { ordering }
+      })
+      .find(|o| *o != Ordering::Equal)
+      .unwrap_or(Ordering::Equal)
+  });
+  let sorted: Vec<Record> = rows.into_par_iter().map(|(_, i)| records[i].clone()).collect();
+  SimpleTable::from_records(schema.clone(), sorted)
+}
+
+// The first count records
+pub fn limit(table: &SimpleTable, count: usize) -> SimpleTable {
+  let records = table.record_view();
+  SimpleTable::from_records(table.schema().clone(), records[..count.min(records.len())].to_vec())
+}
+
+// A query plan: a tree of operators whose leaves are tables. Each node
+// runs to completion and hands its parent a table, which is what the
+// join operators take, so any registered join can be a node. Columns
+// are indexes in a node's input, where a join's output has the left
+// columns then the right ones. Plans are built from the leaves up, e.g.
+// Plan::scan(a).join(Plan::scan(b), algo, keys, params).limit(10)
+#[derive(Debug)]
+pub enum Plan {
+  // Table read from a file in any format SimpleTable::new reads
+  Scan(String),
+  // Table built in memory
+  Records {
+    schema: Schema,
+    records: Vec<Record>,
+  },
+  Filter {
+    input: Box<Plan>,
+    predicate: Predicate,
+  },
+  Project {
+    input: Box<Plan>,
+    columns: Vec<usize>,
+  },
+  // Group by columns then aggregates, see hash_aggregate
+  Aggregate {
+    input: Box<Plan>,
+    group_by: Vec<usize>,
+    aggregates: Vec<Aggregate>,
+  },
+  Sort {
+    input: Box<Plan>,
+    keys: Vec<SortKey>,
+  },
+  Limit {
+    input: Box<Plan>,
+    count: usize,
+  },
+  // Equi-join of the kind in params, by the registered operator
+  Join {
+    left: Box<Plan>,
+    right: Box<Plan>,
+    algo: JoinAlgos,
+    keys: JoinKeys,
+    params: JoinParams,
+  },
+  ThetaJoin {
+    left: Box<Plan>,
+    right: Box<Plan>,
+    algo: JoinAlgos,
+    predicate: ThetaPredicate,
+    params: JoinParams,
+  },
+}
+
+impl Plan {
+  pub fn scan(filepath: &str) -> Plan {
+    Plan::Scan(filepath.to_owned())
+  }
+
+  pub fn records(schema: Schema, records: Vec<Record>) -> Plan {
+    Plan::Records { schema, records }
+  }
+
+  pub fn filter(self, predicate: Predicate) -> Plan {
+    Plan::Filter {
+      input: Box::new(self),
+      predicate,
+    }
+  }
+
+  pub fn project(self, columns: &[usize]) -> Plan {
+    Plan::Project {
+      input: Box::new(self),
+      columns: columns.to_vec(),
+    }
+  }
+
+  pub fn aggregate(self, group_by: &[usize], aggregates: &[Aggregate]) -> Plan {
+    Plan::Aggregate {
+      input: Box::new(self),
+      group_by: group_by.to_vec(),
+      aggregates: aggregates.to_vec(),
+    }
+  }
+
+  pub fn sort(self, keys: &[SortKey]) -> Plan {
+    Plan::Sort {
+      input: Box::new(self),
+      keys: keys.to_vec(),
+    }
+  }
+
+  pub fn limit(self, count: usize) -> Plan {
+    Plan::Limit {
+      input: Box::new(self),
+      count,
+    }
+  }
+
+  // This plan as the left side of a join
+  pub fn join(self, right: Plan, algo: JoinAlgos, keys: JoinKeys, params: JoinParams) -> Plan {
+    Plan::Join {
+      left: Box::new(self),
+      right: Box::new(right),
+      algo,
+      keys,
+      params,
+    }
+  }
+
+  pub fn theta_join(self, right: Plan, algo: JoinAlgos, predicate: ThetaPredicate, params: JoinParams) -> Plan {
+    Plan::ThetaJoin {
+      left: Box::new(self),
+      right: Box::new(right),
+      algo,
+      predicate,
+      params,
+    }
+  }
+
+  // Runs the plan, building its joins from the registry. Tables which
+  // cannot be read are errors, while plans which do not fit their
+  // inputs, e.g. with a column out of range, panic like the operators
+  pub fn execute(&self, registry: &JoinRegistry) -> Result<SimpleTable, Box<dyn Error>> {
+    let table = match self {
+      Plan::Scan(filepath) => SimpleTable::try_new(filepath)?,
+      Plan::Records { schema, records } => SimpleTable::from_records(schema.clone(), records.clone()),
+      Plan::Filter { input, predicate } => filter(&input.execute(registry)?, predicate),
+      Plan::Project { input, columns } => project(&input.execute(registry)?, columns),
+      Plan::Aggregate { input, group_by, aggregates } => {
+        let input = input.execute(registry)?;
+        let (schema, records) = hash_aggregate(input.schema(), input.record_view(), group_by, aggregates);
+        SimpleTable::from_records(schema, records)
+      },
+      Plan::Sort { input, keys } => sort(&input.execute(registry)?, keys),
+      Plan::Limit { input, count } => limit(&input.execute(registry)?, *count),
+      Plan::Join { left, right, algo, keys, params } => {
+        let (mut left, mut right) = (left.execute(registry)?, right.execute(registry)?);
+        let schema = Projection::default().schema(left.schema(), right.schema(), params.join_kind);
+        let records = registry.create(algo, &mut left, &mut right, params).equi_join_on(keys);
+        SimpleTable::from_records(schema, records)
+      },
+      Plan::ThetaJoin { left, right, algo, predicate, params } => {
+        let (mut left, mut right) = (left.execute(registry)?, right.execute(registry)?);
+        let schema = Projection::default().schema(left.schema(), right.schema(), params.join_kind);
+        let records = registry.create_theta(algo, &mut left, &mut right, params).theta_join(predicate);
+        SimpleTable::from_records(schema, records)
+      },
+    };
+    Ok(table)
+  }
+}
@@ -1,134 +1,429 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem::MaybeUninit;
 
-use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 use crate::{record::Record};
+use crate::join::{row_id, JoinAlgos, JoinInto, JoinKind, JoinOperator, JoinOutput, JoinParams, NULL_ROW};
+use crate::key::{JoinKeys, KeyRef};
+use crate::skew::{detect_heavy_keys, join_heavy_keys};
 use crate::table::SimpleTable;
 
+// Bits per partitioning pass used when none are given
+pub const DEFAULT_RADIX_BITS: [u32; 2] = [5, 5];
+
+// Most partitioning passes a join may make
+pub const MAX_RADIX_PASSES: usize = 3;
+
 pub struct RadixJoin<'a> {
 	left: &'a mut SimpleTable,
 	right: &'a mut SimpleTable,
+	kind: JoinKind,
+	radix_bits: Vec<u32>,
+	options: PartitionOptions,
 }
 
-// Leftmost bits
-fn h1_1(x: i32) -> i32 {
-	x & 0b11_111
+// Records buffered per partition when write combining
+const WRITE_COMBINE_RECORDS: usize = 8;
+
+// How the radix partitioning passes run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PartitionOptions {
+	// Build histograms and scatter with every thread,
+	// each working on its own chunk of the input
+	pub parallel: bool,
+	// Stage records in small per-partition buffers and copy
+	// them out once full, instead of writing them one at a time
+	pub write_combining: bool,
+	// Leave records with heavy keys out of the partitions, as they
+	// would all land in one, and spread their pairs over the workers
+	pub skew_handling: bool,
 }
 
-// Next leftmost bits
-fn h1_2(x: i32) -> i32 {
-	(x & 0b1_111_100_000) >> 5
+// What the passes move around: copies of the records, or references
+// to the records of a table's view when the join emits row ids
+pub(crate) trait RadixItem: Clone + Send + Sync {
+	fn record(&self) -> &Record;
+
+	// Row id of the record in view
+	fn row_id(&self, view: &[Record]) -> u32;
+}
+
+// Copies are no longer in the view, so they have no row id.
+// Only record output, which ignores row ids, partitions them
+impl RadixItem for Record {
+	fn record(&self) -> &Record {
+		self
+	}
+
+	fn row_id(&self, _: &[Record]) -> u32 {
+		NULL_ROW
+	}
 }
 
-fn partition(table: &mut SimpleTable, 
-									 col: usize, 
-									 num_first: i32, 
-									 num_second: i32) -> Vec<Vec<Vec<Record>>> {
+impl RadixItem for &Record {
+	fn record(&self) -> &Record {
+		self
+	}
 
-	let size = table.get_num_records();
+	fn row_id(&self, view: &[Record]) -> u32 {
+		row_id(view, self)
+	}
+}
+
+// Partitioned records, kept in one contiguous buffer.
+// Partition p is records[offsets[p]..offsets[p + 1]]
+pub(crate) struct RadixPartitions<T: RadixItem> {
+	records: Vec<T>,
+	offsets: Vec<usize>,
+}
 
-	// Don't know the rustian way to do this lol
-	let mut first_partitions: Vec<Vec<Record>> = Vec::with_capacity(num_first as usize);
-	for _i in 0..num_first {
-		first_partitions.push(Vec::with_capacity(size / (num_first as usize)));
+impl<T: RadixItem> RadixPartitions<T> {
+	pub(crate) fn num_partitions(&self) -> usize {
+		self.offsets.len() - 1
 	}
 
-	// Make first partitions
-	for _i in 0..size {
-		let record = table.read_next_record();
-		let part = h1_1(*record.get_column(col)) as usize;
-		first_partitions[part].push(record.clone());
+	pub(crate) fn get(&self, p: usize) -> &[T] {
+		&self.records[self.offsets[p]..self.offsets[p + 1]]
 	}
-	table.rewind();
+}
+
+// A partitioning pass, which looks at the given number
+// of bits of the key after skipping shift bits
+#[derive(Clone, Copy)]
+struct RadixPass<'a> {
+	columns: &'a [usize],
+	shift: u32,
+	bits: u32,
+}
 
+impl<'a> RadixPass<'a> {
+	fn fanout(&self) -> usize {
+		1 << self.bits
+	}
 
-	// Use first partitions to make second partitions
+	fn partition_of(&self, record: &Record) -> usize {
+		let x = KeyRef::new(record, self.columns).radix_value();
+		((x as u32 >> self.shift) & ((1 << self.bits) - 1)) as usize
+	}
+}
 
-	// Don't know the rustian way to do this lol
-	let mut result: Vec<Vec<Vec<Record>>> = Vec::with_capacity(num_first as usize);
+// Number of kept records of chunk going to each partition
+fn histogram<T: RadixItem, F: Fn(&Record) -> bool>(chunk: &[T], pass: RadixPass, keep: &F) -> Vec<usize> {
+	let mut counts = vec![0; pass.fanout()];
+	for item in chunk.iter().filter(|r| keep(r.record())) {
+		counts[pass.partition_of(item.record())] += 1;
+	}
+	counts
+}
 
-	// Result[0] is vector of second partitions that are in h1_1(x) = 0
-	// Result[0][0] is the records that are in h1_1(x) = 0 and h1_2(x) = 0
+// Copies each kept record of chunk into the slot of its partition,
+// which the histogram sized to fit exactly, so every slot gets written
+fn scatter<T: RadixItem, F: Fn(&Record) -> bool>(chunk: &[T],
+																								pass: RadixPass,
+																								mut slots: Vec<&mut [MaybeUninit<T>]>,
+																								write_combining: bool,
+																								keep: &F) {
+	let mut cursors = vec![0; slots.len()];
+	if !write_combining {
+		for item in chunk.iter().filter(|r| keep(r.record())) {
+			let p = pass.partition_of(item.record());
+			slots[p][cursors[p]].write(item.clone());
+			cursors[p] += 1;
+		}
+		return;
+	}
 
-	for partition in first_partitions {
-		let mut second_partitions: Vec<Vec<Record>> = Vec::with_capacity(num_first as usize);
-		for _i in 0..num_second {
-			second_partitions.push(Vec::with_capacity(size / ((num_first * num_second) as usize)));
+	let mut buffers: Vec<Vec<T>> = (0..slots.len())
+		.map(|_| Vec::with_capacity(WRITE_COMBINE_RECORDS))
+		.collect();
+	for item in chunk.iter().filter(|r| keep(r.record())) {
+		let p = pass.partition_of(item.record());
+		buffers[p].push(item.clone());
+		if buffers[p].len() == WRITE_COMBINE_RECORDS {
+			let end = cursors[p] + WRITE_COMBINE_RECORDS;
+			for (slot, item) in slots[p][cursors[p]..end].iter_mut().zip(buffers[p].drain(..)) {
+				slot.write(item);
+			}
+			cursors[p] = end;
+		}
+	}
+	for (p, buffer) in buffers.into_iter().enumerate() {
+		for (slot, item) in slots[p][cursors[p]..].iter_mut().zip(buffer) {
+			slot.write(item);
 		}
+	}
+}
+
+// One partitioning pass from source into dest. Every partition of
+// source is split on the bits of this pass, so partition q becomes
+// partitions q * 2^bits .. (q + 1) * 2^bits, written to the front
+// of dest, which must fit them. Returns their offsets
+fn partition_pass<T: RadixItem, F: Fn(&Record) -> bool + Sync>(source: &[T],
+																								 offsets: &[usize],
+																								 dest: &mut [MaybeUninit<T>],
+																								 pass: RadixPass,
+																								 options: &PartitionOptions,
+																								 keep: &F) -> Vec<usize> {
+	let fanout = pass.fanout();
+	let num_source = offsets.len() - 1;
+
+	// Split the source partitions into chunks, so that
+	// each thread has a chunk when running in parallel
+	let chunks_per_partition = match options.parallel {
+		true => (rayon::current_num_threads() / num_source).max(1),
+		false => 1,
+	};
+	let mut chunks: Vec<&[T]> = Vec::new();
+	let mut chunk_counts: Vec<usize> = Vec::with_capacity(num_source);
+	for q in 0..num_source {
+		let part = &source[offsets[q]..offsets[q + 1]];
+		let before = chunks.len();
+		chunks.extend(part.chunks(part.len().div_ceil(chunks_per_partition).max(1)));
+		chunk_counts.push(chunks.len() - before);
+	}
+
+	let histograms: Vec<Vec<usize>> = match options.parallel {
+		true => chunks.par_iter().map(|c| histogram(c, pass, keep)).collect(),
+		false => chunks.iter().map(|c| histogram(c, pass, keep)).collect(),
+	};
 
-		for record in partition {
-			let val = *record.get_column(col);
-			let part = h1_2(val) as usize;
-			second_partitions[part].push(record.clone());
+	// Prefix sum the histograms into a slot of dest per chunk and
+	// partition. Slots of a partition are next to each other, in
+	// the order of the chunks, so each partition stays contiguous
+	let mut slots: Vec<Vec<&mut [MaybeUninit<T>]>> = chunks.iter().map(|_| Vec::with_capacity(fanout)).collect();
+	let mut dest_offsets: Vec<usize> = Vec::with_capacity(num_source * fanout + 1);
+	let mut rest: &mut [MaybeUninit<T>] = dest;
+	let mut position = 0;
+	let mut first_chunk = 0;
+	for count in chunk_counts {
+		let chunk_range = first_chunk..first_chunk + count;
+		for p in 0..fanout {
+			dest_offsets.push(position);
+			for (histogram, chunk_slots) in histograms[chunk_range.clone()].iter().zip(&mut slots[chunk_range.clone()]) {
+				let (slot, tail) = std::mem::take(&mut rest).split_at_mut(histogram[p]);
+				chunk_slots.push(slot);
+				rest = tail;
+				position += histogram[p];
+			}
 		}
-		result.push(second_partitions);
+		first_chunk += count;
 	}
+	dest_offsets.push(position);
 
-	result
+	match options.parallel {
+		true => chunks.into_par_iter()
+			.zip(slots.into_par_iter())
+			.for_each(|(chunk, chunk_slots)| scatter(chunk, pass, chunk_slots, options.write_combining, keep)),
+		false => chunks.into_iter()
+			.zip(slots)
+			.for_each(|(chunk, chunk_slots)| scatter(chunk, pass, chunk_slots, options.write_combining, keep)),
+	}
+
+	dest_offsets
 }
 
+// Splits the kept records into one partition per combination of radix
+// bits, one pass per entry of radix_bits, each on the next bits of the
+// key. Passes scatter straight into the unused capacity of one of two
+// buffers, which they alternate between, so partitioning allocates
+// them and copies each item once per pass, however many partitions
+pub(crate) fn partition<T: RadixItem, F: Fn(&Record) -> bool + Sync>(source: &[T],
+																																		columns: &[usize],
+																																		radix_bits: &[u32],
+																																		options: &PartitionOptions,
+																																		keep: &F) -> RadixPartitions<T> {
+	let mut records: Vec<T> = Vec::with_capacity(source.len());
+	let first_pass = RadixPass { columns, shift: 0, bits: radix_bits[0] };
+	let mut offsets = partition_pass(source, &[0, source.len()], records.spare_capacity_mut(), first_pass, options, keep);
+	// Safety: the pass wrote every item up to its last offset
+	unsafe { records.set_len(offsets[offsets.len() - 1]) };
+
+	let mut spare: Vec<T> = Vec::new();
+	let mut shift = radix_bits[0];
+	for bits in &radix_bits[1..] {
+		spare.clear();
+		spare.reserve(records.len());
+		let pass = RadixPass { columns, shift, bits: *bits };
+		offsets = partition_pass(&records, &offsets, spare.spare_capacity_mut(), pass, options, &|_: &Record| true);
+		// Safety: as for the first pass
+		unsafe { spare.set_len(offsets[offsets.len() - 1]) };
+		std::mem::swap(&mut records, &mut spare);
+		shift += bits;
+	}
+
+	RadixPartitions {
+		records,
+		offsets,
+	}
+}
 
 impl<'a> RadixJoin<'a> {
 	
 	pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+		Self::with_kind(left, right, JoinKind::Inner)
+	}
+
+	// Supports inner, semi and anti joins
+	pub fn with_kind(left: &'a mut SimpleTable, right: &'a mut SimpleTable, kind: JoinKind) -> Self {
+		Self::with_radix_bits(left, right, kind, &DEFAULT_RADIX_BITS)
+	}
+
+	// Partitions in one pass per entry of radix_bits,
+	// each pass splitting on that many bits of the key
+	pub fn with_radix_bits(left: &'a mut SimpleTable,
+												 right: &'a mut SimpleTable,
+												 kind: JoinKind,
+												 radix_bits: &[u32]) -> Self {
+		Self::with_partitioning(left, right, kind, radix_bits, PartitionOptions::default())
+	}
+
+	// Same as with_radix_bits, also choosing how the passes run
+	pub fn with_partitioning(left: &'a mut SimpleTable,
+													 right: &'a mut SimpleTable,
+													 kind: JoinKind,
+													 radix_bits: &[u32],
+													 options: PartitionOptions) -> Self {
+		if let Err(e) = JoinAlgos::RadixJoin.check_kind(kind) {
+			panic!("{}", e);
+		}
+		assert!(
+			!radix_bits.is_empty() && radix_bits.len() <= MAX_RADIX_PASSES,
+			"RadixJoin makes 1 to {} passes, got {:?}", MAX_RADIX_PASSES, radix_bits);
+		assert!(
+			radix_bits.iter().all(|b| *b > 0) && radix_bits.iter().sum::<u32>() <= 24,
+			"RadixJoin needs 1 to 24 bits in total and at least one per pass, got {:?}", radix_bits);
 		Self {
 			left,
-			right
+			right,
+			kind,
+			radix_bits: radix_bits.to_vec(),
+			options,
 		}
 	}
 
-	pub fn equi_join(&mut self, left_col: usize, right_col: usize) -> Vec<Record> {
-		// TODO: potentially use a tuneable variable like these 
-		//  and define h1_1, h1_2 based on that
-		let first_bits = 5;
-		let second_bits = 5;
+	pub fn get_radix_bits(&self) -> &[u32] {
+		&self.radix_bits
+	}
+
+	pub fn get_partition_options(&self) -> PartitionOptions {
+		self.options
+	}
+
+	// Joins the items of the left and right record views
+	fn join_items<T: RadixItem, O: JoinOutput>(&self, keys: &JoinKeys, items: (&[T], &[T]), join_result: &mut O) {
+		let keys = &keys.resolve(self.left.schema(), self.right.schema());
+		let kind = self.kind;
+		let left_records = self.left.record_view();
+		let right_records = self.right.record_view();
 
-		let base: i32 = 2;
-		let num_first_partition= base.pow(first_bits);
-		let num_second_partition = base.pow(second_bits);
+		let heavy_keys: HashSet<KeyRef> = match self.options.skew_handling {
+			true => detect_heavy_keys(right_records, keys.right_columns()),
+			false => HashSet::new(),
+		};
 
-		let mut tables = vec![
-			(&mut self.left, left_col), 
-			(&mut self.right, right_col)
+		let tables = vec![
+			(items.0, keys.left_columns()), 
+			(items.1, keys.right_columns())
 		];
 
-		let partitions: Vec<Vec<Vec<Vec<Record>>>> = tables
-			.par_iter_mut()
+		let radix_bits = &self.radix_bits;
+		let options = &self.options;
+		let partitions: Vec<RadixPartitions<T>> = tables
+			.par_iter()
 			.map(|tup| {
-				partition(tup.0,  tup.1, num_first_partition, num_second_partition)
+				let light = |r: &Record| heavy_keys.is_empty() || !heavy_keys.contains(&KeyRef::new(r, tup.1));
+				partition(tup.0, tup.1, radix_bits, options, &light)
 			})
 			.collect();
 
 		let left_partitions = &partitions[0];
 		let right_partitions = &partitions[1];
 		
-		(0..left_partitions.len()).into_par_iter()
-		.map(|first| -> Vec<Record> {
-			(0..left_partitions[first].len()).into_par_iter()
-			.map(|second| -> Vec<Record> {
-				// Build hash table on right partition corresponding to [first][second]
-				let right_partition = &right_partitions[first][second];
-				let mut right_table = HashMap::<i32, Vec<&Record>>::new();
-				for record in right_partition {
-					let right_column_value = *record.get_column(right_col);
-					right_table.entry(right_column_value).or_insert(Vec::new()).push(record);
+		let partition_results: Vec<O> = (0..left_partitions.num_partitions()).into_par_iter()
+		.map(|p| -> O {
+			// Build hash table on right partition corresponding to p,
+			// leaving out NULL keys since they match nothing
+			let right_partition = right_partitions.get(p);
+			let mut right_table = HashMap::<KeyRef, Vec<&T>>::new();
+			for item in right_partition {
+				let right_key = keys.right_key(item.record());
+				if right_key.is_null() {
+					continue;
 				}
-				// Probe built hash table
-				left_partitions[first][second].iter()
-				.map(|lr| -> Vec<Record> {
-					right_table
-							.get(lr.get_column(left_col)).unwrap_or(&Vec::new())
-							.iter()
-							.map(|rr| Record::merge(&lr, rr))
-							.collect()
-				})
-				.flatten()
-				.collect()
-			})
-			.flatten()
-			.collect()
+				right_table.entry(right_key).or_default().push(item);
+			}
+			// Probe built hash table
+			let mut partition_result = join_result.split();
+			for item in left_partitions.get(p) {
+				let lr = item.record();
+				match right_table.get(&keys.left_key(lr)) {
+					Some(_) if kind == JoinKind::LeftSemi => partition_result.left_only(item.row_id(left_records), lr),
+					Some(_) if kind == JoinKind::LeftAnti => {},
+					Some(right_record_matches) => {
+						for rr in right_record_matches {
+							partition_result.pair(item.row_id(left_records), lr, rr.row_id(right_records), rr.record());
+						}
+					},
+					None if kind == JoinKind::LeftAnti => partition_result.left_only(item.row_id(left_records), lr),
+					None => {},
+				}
+			}
+			partition_result
 		})
-		.flatten()
-		.collect()
-	}	
-}
\ No newline at end of file
+		.collect();
+		for partition_result in partition_results {
+			join_result.absorb(partition_result);
+		}
+
+		if !heavy_keys.is_empty() {
+			let heavy_left: Vec<&Record> = left_records.par_iter()
+				.filter(|lr| heavy_keys.contains(&keys.left_key(lr)))
+				.collect();
+			let mut heavy_right: HashMap<KeyRef, Vec<&Record>> = HashMap::new();
+			for rr in right_records.iter().filter(|rr| heavy_keys.contains(&keys.right_key(rr))) {
+				heavy_right.entry(keys.right_key(rr)).or_default().push(rr);
+			}
+			join_heavy_keys(keys, (left_records, right_records), &heavy_left, &heavy_right, kind, join_result);
+		}
+	}
+}
+
+impl<'a> JoinOperator for RadixJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::RadixJoin
+	}
+
+	fn parameters(&self) -> JoinParams {
+		JoinParams {
+			join_kind: self.kind,
+			radix_bits: self.radix_bits.clone(),
+			parallel_partitioning: self.options.parallel,
+			write_combining: self.options.write_combining,
+			skew_handling: self.options.skew_handling,
+			..JoinParams::default()
+		}
+	}
+
+	fn join_kind(&self) -> JoinKind {
+		self.kind
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> JoinInto for RadixJoin<'a> {
+	// Partitions copies of the records for outputs which merge them,
+	// and otherwise references to the records, which are cheaper to copy
+	fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+		if O::MERGES_RECORDS {
+			self.join_items(keys, (self.left.record_view(), self.right.record_view()), join_result);
+		} else {
+			let left_records: Vec<&Record> = self.left.record_iterator().collect();
+			let right_records: Vec<&Record> = self.right.record_iterator().collect();
+			self.join_items(keys, (&left_records, &right_records), join_result);
+		}
+	}
+}
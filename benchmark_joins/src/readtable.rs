@@ -1,39 +1,264 @@
 use std::error::Error;
-use std::fs::File;
-use csv::StringRecord;
-
-pub fn fetch_records(file_path: &str) -> Result<Vec<Vec<i32>>, Box<dyn Error>> {
-  // Table to hold results
-  let mut raw_table: Vec<Vec<i32>> = Vec::new();
-
-  // File contain on-disk table
-  let file = File::open(file_path)?;
-
-  // Create CSV reader object
-  let mut rdr = csv::Reader::from_reader(file);
-
-  for result in rdr.records() {
-      // Holder of slots for the record
-      let mut raw_record: Vec<i32> = Vec::new();
-      
-      // Parse individual record fields as Ts
-      let string_record: StringRecord = result?;
-      for string_field in string_record.iter() {
-        match string_field.parse::<i32>() {
-          Ok(parsed_field) => {
-            // Case: success ==> push to our record
-            raw_record.push(parsed_field);
-          },
-          Err(error) => {
-            // Case: failure ==> die
-            panic!("Something went wrong in parsing {:?}", error)
-          }
-        }
-      }
-
-      // Add record to the table
-      raw_table.push(raw_record);
+use std::fmt;
+use std::str::FromStr;
+use csv::{ByteRecord, ReaderBuilder};
+
+use crate::record::{Record, M};
+use crate::rows::Rows;
+use crate::schema::{encode_value, Column, ColumnType, Schema, Value};
+
+// Why a CSV table could not be loaded. Lines count from 1 as in
+// editors, columns from 0 as in join columns
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvError {
+  // The file could not be opened or read
+  Io { file: String, reason: String },
+  // A field is not a value of its column's type
+  Parse { file: String, line: u64, column: usize, reason: String },
+  // A row has a different number of fields than the header or first row
+  Ragged { file: String, line: u64, expected: usize, found: usize },
+  // A selected column is not in the table
+  UnknownColumn { file: String, column: String },
+  // More columns than a record holds, counted in record slots
+  TooManyColumns { file: String, found: usize },
+  // Not one type per loaded column
+  TypeCount { file: String, expected: usize, found: usize },
+  // No header and no rows to take the columns from
+  Empty { file: String },
+}
+
+impl CsvError {
+  // Line of the row at fault, if the error is about a row
+  pub fn line(&self) -> Option<u64> {
+    match self {
+      CsvError::Parse { line, .. } | CsvError::Ragged { line, .. } => Some(*line),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for CsvError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CsvError::Io { file, reason } => write!(f, "{}: {}", file, reason),
+      CsvError::Parse { file, line, column, reason } => write!(f, "{}, line {}, column {}: {}", file, line, column, reason),
+      CsvError::Ragged { file, line, expected, found } => write!(f, "{}, line {}: expected {} fields, found {}", file, line, expected, found),
+      CsvError::UnknownColumn { file, column } => write!(f, "{}: no column {}", file, column),
+      CsvError::TooManyColumns { file, found } => write!(f, "{}: columns take {} record slots, but max support is {}", file, found, M),
+      CsvError::TypeCount { file, expected, found } => write!(f, "{}: {} types given for {} columns", file, found, expected),
+      CsvError::Empty { file } => write!(f, "{}: no header or rows", file),
+    }
+  }
+}
+
+impl Error for CsvError {}
+
+// A column to load, by index or by header name
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvColumn {
+  Index(usize),
+  Name(String),
+}
+
+// Numbers are indexes, anything else a name
+impl FromStr for CsvColumn {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.parse::<usize>() {
+      Ok(i) => Ok(CsvColumn::Index(i)),
+      Err(_) if !s.is_empty() => Ok(CsvColumn::Name(s.to_owned())),
+      Err(_) => Err("Expected a column index or name".to_owned()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvMode {
+  // Any bad row fails the load
+  Strict,
+  // Bad rows are skipped and reported
+  Lenient,
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+  pub delimiter: u8,
+  pub has_headers: bool,
+  // Columns to load, in this order, or all of them
+  pub columns: Option<Vec<CsvColumn>>,
+  // Types of the loaded columns, or all Int32
+  pub types: Option<Vec<ColumnType>>,
+  pub mode: CsvMode,
+}
+
+impl Default for CsvOptions {
+  fn default() -> Self {
+    CsvOptions {
+      delimiter: b',',
+      has_headers: true,
+      columns: None,
+      types: None,
+      mode: CsvMode::Strict,
+    }
   }
+}
+
+pub struct CsvTable {
+  // Header names and types of the loaded columns,
+  // named col1, col2, ... without a header
+  pub schema: Schema,
+  pub records: Vec<Record>,
+  // Errors of the rows skipped in lenient mode
+  pub skipped: Vec<CsvError>,
+}
+
+// Same as CsvTable, for tables too wide for a record
+pub struct CsvRows {
+  pub schema: Schema,
+  pub rows: Rows,
+  pub skipped: Vec<CsvError>,
+}
+
+// Parses the selected fields of a row into the slots of the schema,
+// listing the NULL slots, where empty fields are NULL. Int32 fields
+// skip the Value
+fn parse_row(row: &ByteRecord, selected: &[usize], schema: &Schema, values: &mut [i32], nulls: &mut Vec<usize>) -> Result<(), (usize, String)> {
+  nulls.clear();
+  for (i, &column) in selected.iter().enumerate() {
+    let text = std::str::from_utf8(&row[column]).map_err(|e| (column, e.to_string()))?.trim();
+    let slots = schema.slots(i);
+    match schema.column_type(i) {
+      ColumnType::Int32 if !text.is_empty() => {
+        values[slots.start] = text.parse().map_err(|e| (column, format!("{:?} is not an i32, {}", text, e)))?;
+      },
+      column_type => match encode_value(column_type, &column_type.parse(text).map_err(|e| (column, e))?) {
+        None => {
+          values[slots.clone()].fill(0);
+          nulls.extend(slots);
+        },
+        Some(encoded) => values[slots.clone()].copy_from_slice(&encoded[..slots.len()]),
+      },
+    }
+  }
+  Ok(())
+}
+
+// Loads a CSV table. Every row must have as many fields as the
+// header, or the first row without one, and every selected field
+// must be a value of its column's type, Int32 unless types are
+// given, or empty, for NULL. In strict mode the first row breaking
+// these rules fails the load, in lenient mode such rows are skipped
+// and their errors returned with the table
+pub fn read_csv_table(file_path: &str, options: &CsvOptions) -> Result<CsvTable, CsvError> {
+  let mut records: Vec<Record> = Vec::new();
+  let (schema, skipped) = read_csv(file_path, options, true, |values, nulls| {
+    let mut record = Record::new(values);
+    for &i in nulls {
+      record.set_null(i);
+    }
+    records.push(record);
+  })?;
+  Ok(CsvTable { schema, records, skipped })
+}
+
+// Loads a CSV table like read_csv_table, but into rows of any width
+pub fn read_csv_rows(file_path: &str, options: &CsvOptions) -> Result<CsvRows, CsvError> {
+  let mut rows: Option<Rows> = None;
+  let (schema, skipped) = read_csv(file_path, options, false, |values, nulls| {
+    let rows = rows.get_or_insert_with(|| Rows::new(values.len()));
+    rows.push(values);
+    for &i in nulls {
+      rows.set_null(rows.len() - 1, i);
+    }
+  })?;
+  let rows = rows.unwrap_or_else(|| Rows::new(schema.num_slots()));
+  Ok(CsvRows { schema, rows, skipped })
+}
+
+// Parses the rows of a CSV table, passing the slots and NULL
+// slots of each good row to push. Tables wider than a record
+// are only loaded when fixed_width is false
+fn read_csv<F: FnMut(&[i32], &[usize])>(file_path: &str, options: &CsvOptions, fixed_width: bool, mut push: F) -> Result<(Schema, Vec<CsvError>), CsvError> {
+  let file = file_path.to_owned();
+  let io_error = |e: csv::Error| CsvError::Io { file: file.clone(), reason: e.to_string() };
+  let mut reader = ReaderBuilder::new()
+    .delimiter(options.delimiter)
+    .has_headers(options.has_headers)
+    .flexible(true)
+    .from_path(file_path)
+    .map_err(io_error)?;
+
+  // Width of the table, from the header or the first row
+  let mut row = ByteRecord::new();
+  let header: Option<Vec<String>> = if options.has_headers {
+    Some(reader.headers().map_err(io_error)?.iter().map(|h| h.trim().to_owned()).collect())
+  } else {
+    None
+  };
+  let has_first_row = reader.read_byte_record(&mut row).map_err(io_error)?;
+  let width = match &header {
+    Some(names) if !names.is_empty() => names.len(),
+    _ if has_first_row => row.len(),
+    _ => return Err(CsvError::Empty { file }),
+  };
+
+  // Indexes of the loaded columns
+  let selected: Vec<usize> = match &options.columns {
+    None => (0..width).collect(),
+    Some(columns) => columns.iter()
+      .map(|c| match c {
+        CsvColumn::Index(i) if *i < width => Ok(*i),
+        CsvColumn::Name(n) => header.as_ref()
+          .and_then(|names| names.iter().position(|h| h == n))
+          .ok_or_else(|| CsvError::UnknownColumn { file: file.clone(), column: n.clone() }),
+        CsvColumn::Index(i) => Err(CsvError::UnknownColumn { file: file.clone(), column: i.to_string() }),
+      })
+      .collect::<Result<_, _>>()?,
+  };
+  let types: Vec<ColumnType> = match &options.types {
+    None => vec![ColumnType::Int32; selected.len()],
+    Some(types) if types.len() == selected.len() => types.clone(),
+    Some(types) => return Err(CsvError::TypeCount { file, expected: selected.len(), found: types.len() }),
+  };
+  if fixed_width && !Schema::fits(&types) {
+    return Err(CsvError::TooManyColumns { file, found: types.iter().map(|t| t.slots()).sum() });
+  }
+  let schema = Schema::new(selected.iter()
+    .zip(types)
+    .map(|(&i, column_type)| match &header {
+      Some(names) => Column::new(&names[i], column_type),
+      None => Column::new(&format!("col{}", i + 1), column_type),
+    })
+    .collect());
+
+  let mut skipped: Vec<CsvError> = Vec::new();
+  let mut values: Vec<i32> = vec![0; schema.num_slots()];
+  let mut nulls: Vec<usize> = Vec::new();
+  let mut more = has_first_row;
+  while more {
+    let line = row.position().map_or(0, |p| p.line());
+    let result = if row.len() != width {
+      Err(CsvError::Ragged { file: file.clone(), line, expected: width, found: row.len() })
+    } else {
+      parse_row(&row, &selected, &schema, &mut values, &mut nulls)
+        .map_err(|(column, reason)| CsvError::Parse { file: file.clone(), line, column, reason })
+    };
+    match result {
+      Ok(()) => push(&values, &nulls),
+      Err(e) if options.mode == CsvMode::Lenient => skipped.push(e),
+      Err(e) => return Err(e),
+    }
+    more = reader.read_byte_record(&mut row).map_err(io_error)?;
+  }
+
+  Ok((schema, skipped))
+}
 
-  Ok(raw_table)
-}
\ No newline at end of file
+// Values of every row of a CSV table, typed as the options say
+pub fn fetch_records(file_path: &str, options: &CsvOptions) -> Result<Vec<Vec<Value>>, Box<dyn Error>> {
+  let table = read_csv_table(file_path, options)?;
+  Ok(table.records.iter()
+    .map(|r| (0..table.schema.num_columns()).map(|i| r.get_value(&table.schema, i)).collect())
+    .collect())
+}
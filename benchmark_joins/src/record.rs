@@ -1,11 +1,24 @@
 use std::cmp::Ordering;
+use std::convert::TryInto;
 use std::fmt::Debug;
 use std::panic;
 
+use crate::schema::{decode_value, encode_value, Schema, Value};
+
 // Maximum fields in a record
 pub const M: usize = 20;
 
+// The null bitmap needs one bit per field
+const _: () = assert!(M <= 32);
+
+// Bytes of a record in record tables, which are laid out like
+// the struct on 64-bit little-endian targets: the fields, the
+// tail as a u64, the null bitmap and 4 bytes of padding
+pub const RECORD_BYTES: usize = M * 4 + 8 + 4 + 4;
+
+// C layout, so that record tables can be memory-mapped as records
 #[derive(Debug, Clone)]
+#[repr(C)]
 pub struct Record {
 	// Array of i32s whose capacity
 	// is preallocated to max of M
@@ -14,6 +27,10 @@ pub struct Record {
 	// The next index to insert 
 	// a new column value into
 	tail: usize,
+
+	// Bit i is set when column i is NULL,
+	// e.g. the padded side of an outer join
+	nulls: u32,
 }
 
 impl Record {
@@ -32,7 +49,21 @@ impl Record {
 		
 		Record {
 			fields,
-			tail: n
+			tail: n,
+			nulls: 0,
+		}
+	}
+
+	// Record with n columns which are all NULL
+	pub fn nulls(n: usize) -> Record {
+		if n > M {
+			panic!("Record input has {:?} columns, but max support is {:?}", n, M);
+		}
+
+		Record {
+			fields: [0; M],
+			tail: n,
+			nulls: ((1u64 << n) - 1) as u32,
 		}
 	}
 
@@ -48,18 +79,15 @@ impl Record {
 		let mut new_fields: [i32; M] = [0; M];
 
 		// Add first record
-		for i in 0..s1 {
-			new_fields[i] = r1.fields[i];
-		}
+		new_fields[..s1].copy_from_slice(&r1.fields[..s1]);
 
 		// Add second record
-		for i in s1..sn {
-			new_fields[i] = r2.fields[i - s1];
-		}
+		new_fields[s1..sn].copy_from_slice(&r2.fields[..s2]);
 
 		Record {
 			fields: new_fields,
-			tail: sn
+			tail: sn,
+			nulls: r1.nulls | (r2.nulls << s1),
 		}
 	}
 
@@ -76,7 +104,7 @@ impl Record {
 		&self.fields[i]
 	}
 
-	pub fn set_column(&mut self, i: usize, value: i32) -> () {
+	pub fn set_column(&mut self, i: usize, value: i32) {
 		// Capacity OOB
 		if i >= M {
 			panic!("OOB Capacity");
@@ -88,6 +116,40 @@ impl Record {
 
 		// Move ownership of `value` to Record
 		self.fields[i] = value;
+		self.nulls &= !(1 << i);
+	}
+
+	pub fn is_null(&self, i: usize) -> bool {
+		// Index OOB
+		if i >= self.tail {
+			panic!("OOB Index")
+		}
+
+		self.nulls & (1 << i) != 0
+	}
+
+	// Whether any column is NULL
+	pub fn has_nulls(&self) -> bool {
+		self.nulls != 0
+	}
+
+	pub fn set_null(&mut self, i: usize) {
+		// Index OOB
+		if i >= self.tail {
+			panic!("OOB Index")
+		}
+
+		self.fields[i] = 0;
+		self.nulls |= 1 << i;
+	}
+
+	// Value of column i, or None when it is NULL
+	pub fn get_nullable_column(&self, i: usize) -> Option<&i32> {
+		if self.is_null(i) {
+			None
+		} else {
+			Some(self.get_column(i))
+		}
 	}
 
 	pub fn get_column_values(&self) -> &[i32] {
@@ -97,23 +159,82 @@ impl Record {
 	pub fn get_num_columns(&self) -> usize {
 		self.tail
 	}
+
+	// Record of the schema holding the values, one per column
+	pub fn from_values(schema: &Schema, values: &[Value]) -> Record {
+		if values.len() != schema.num_columns() {
+			panic!("Record input has {:?} values, but the schema has {:?} columns", values.len(), schema.num_columns());
+		}
+
+		let mut record = Record::new(&vec![0; schema.num_slots()]);
+		for (column, value) in values.iter().enumerate() {
+			record.set_value(schema, column, value);
+		}
+		record
+	}
+
+	// Value of a column of the schema, read from its slots
+	pub fn get_value(&self, schema: &Schema, column: usize) -> Value {
+		let slots = schema.slots(column);
+		if self.is_null(slots.start) {
+			return Value::Null;
+		}
+		decode_value(schema.column_type(column), &self.get_column_values()[slots])
+	}
+
+	// Stores a value in the slots of a column of the schema. NULL
+	// sets every slot of the column NULL
+	pub fn set_value(&mut self, schema: &Schema, column: usize, value: &Value) {
+		let slots = schema.slots(column);
+		match encode_value(schema.column_type(column), value) {
+			None => slots.for_each(|i| self.set_null(i)),
+			Some(encoded) => slots.zip(encoded).for_each(|(i, v)| self.set_column(i, v)),
+		}
+	}
+
+	pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+		for f in self.fields.iter() {
+			out.extend_from_slice(&f.to_le_bytes());
+		}
+		out.extend_from_slice(&(self.tail as u64).to_le_bytes());
+		out.extend_from_slice(&self.nulls.to_le_bytes());
+		out.extend_from_slice(&[0; 4]);
+	}
+
+	pub(crate) fn from_bytes(bytes: &[u8]) -> Record {
+		assert_eq!(bytes.len(), RECORD_BYTES);
+		let mut fields: [i32; M] = [0; M];
+		for (f, b) in fields.iter_mut().zip(bytes.chunks_exact(4)) {
+			*f = i32::from_le_bytes(b.try_into().unwrap());
+		}
+		let tail = u64::from_le_bytes(bytes[M * 4..M * 4 + 8].try_into().unwrap()) as usize;
+		if tail > M {
+			panic!("Record input has {:?} columns, but max support is {:?}", tail, M);
+		}
+		Record {
+			fields,
+			tail,
+			nulls: u32::from_le_bytes(bytes[M * 4 + 8..M * 4 + 12].try_into().unwrap()),
+		}
+	}
 }
 
 impl Ord for Record {
 	fn cmp(&self, other: &Self) -> Ordering {
 			self.fields.cmp(&other.fields)
+				.then(self.nulls.cmp(&other.nulls))
 	}
 }
 
 impl PartialOrd for Record {
 	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-			Some(self.fields.cmp(&other.fields))
+			Some(self.cmp(other))
 	}
 }
 
 impl PartialEq for Record {
 	fn eq(&self, other: &Self) -> bool {
-			self.fields == other.fields
+			self.fields == other.fields && self.nulls == other.nulls
 	}
 }
 
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+use crate::columnarjoin::{ColumnarHashJoin, ColumnarJoin, ColumnarRadixJoin};
+use crate::columnartable::ColumnarTable;
+use crate::gracejoin::{GraceHashJoin, DEFAULT_MEMORY_BUDGET};
+use crate::join::{BlockNL, EquiJoin, JoinAlgos, JoinParams, NestedLoopsJoin, SimpleHashJoin};
+use crate::parjoin::{ParallelNestedLoopsJoin, ParallelSimpleHashJoin, ParallelUnaryLeapFrogJoin};
+use crate::radixjoin::{PartitionOptions, RadixJoin, DEFAULT_RADIX_BITS};
+use crate::table::SimpleTable;
+use crate::thetajoin::{IEJoin, SortBandJoin, ThetaJoin};
+
+// Builds a join operator over the left and right tables
+pub type JoinFactory = for<'a> fn(&'a mut SimpleTable, &'a mut SimpleTable, &JoinParams) -> Box<dyn EquiJoin + 'a>;
+
+// Builds a theta join operator over the left and right tables
+pub type ThetaJoinFactory = for<'a> fn(&'a mut SimpleTable, &'a mut SimpleTable, &JoinParams) -> Box<dyn ThetaJoin + 'a>;
+
+// Builds a join operator over the left and right columnar tables
+pub type ColumnarJoinFactory = for<'a> fn(&'a ColumnarTable, &'a ColumnarTable, &JoinParams) -> Box<dyn ColumnarJoin + 'a>;
+
+// Guards every operator against the join kinds it does not produce
+fn check_kind(algo: &JoinAlgos, params: &JoinParams) {
+	if let Err(e) = algo.check_kind(params.join_kind) {
+		panic!("{}", e);
+	}
+}
+
+// Maps each join algorithm to the factory which builds it.
+// The runner dispatches through this, so new operators only
+// need to be registered here to be benchmarked and tested
+pub struct JoinRegistry {
+	factories: HashMap<JoinAlgos, JoinFactory>,
+	theta_factories: HashMap<JoinAlgos, ThetaJoinFactory>,
+	columnar_factories: HashMap<JoinAlgos, ColumnarJoinFactory>,
+}
+
+impl JoinRegistry {
+	// Registry without any operators
+	pub fn empty() -> Self {
+		Self {
+			factories: HashMap::new(),
+			theta_factories: HashMap::new(),
+			columnar_factories: HashMap::new(),
+		}
+	}
+
+	// Registry holding every operator in this crate
+	pub fn new() -> Self {
+		let mut registry = Self::empty();
+		registry.register(JoinAlgos::NLJoin, |left, right, params| {
+			Box::new(NestedLoopsJoin::with_kind(left, right, params.join_kind))
+		});
+		registry.register(JoinAlgos::BNLJoin, |left, right, params| {
+			Box::new(BlockNL::new(left, right, params.left_block_size, params.right_block_size))
+		});
+		registry.register(JoinAlgos::SimpleHashJoin, |left, right, params| {
+			Box::new(SimpleHashJoin::with_kind(left, right, params.join_kind))
+		});
+		registry.register(JoinAlgos::RadixJoin, |left, right, params| {
+			let radix_bits: &[u32] = match params.radix_bits.as_slice() {
+				[] => &DEFAULT_RADIX_BITS,
+				bits => bits,
+			};
+			let options = PartitionOptions {
+				parallel: params.parallel_partitioning,
+				write_combining: params.write_combining,
+				skew_handling: params.skew_handling,
+			};
+			Box::new(RadixJoin::with_partitioning(left, right, params.join_kind, radix_bits, options))
+		});
+		registry.register(JoinAlgos::PNLJoin, |left, right, _params| {
+			Box::new(ParallelNestedLoopsJoin::new(left, right))
+		});
+		registry.register(JoinAlgos::ParallelSimpleHashJoin, |left, right, params| {
+			match params.skew_handling {
+				true => Box::new(ParallelSimpleHashJoin::with_skew_handling(left, right, params.join_kind)),
+				false => Box::new(ParallelSimpleHashJoin::with_kind(left, right, params.join_kind)),
+			}
+		});
+		registry.register(JoinAlgos::ParallelUnaryLeapFrogJoin, |left, right, params| {
+			Box::new(ParallelUnaryLeapFrogJoin::with_kind(left, right, params.join_kind))
+		});
+		registry.register(JoinAlgos::GraceHashJoin, |left, right, params| {
+			let memory_budget = match params.memory_budget_bytes {
+				0 => DEFAULT_MEMORY_BUDGET,
+				budget => budget,
+			};
+			Box::new(GraceHashJoin::new(left, right, memory_budget))
+		});
+
+		// Theta joins, with the nested loops joins as fallback
+		registry.register_theta(JoinAlgos::NLJoin, |left, right, params| {
+			Box::new(NestedLoopsJoin::with_kind(left, right, params.join_kind))
+		});
+		registry.register_theta(JoinAlgos::BNLJoin, |left, right, params| {
+			Box::new(BlockNL::new(left, right, params.left_block_size, params.right_block_size))
+		});
+		registry.register_theta(JoinAlgos::BandJoin, |left, right, _params| {
+			Box::new(SortBandJoin::new(left, right))
+		});
+		registry.register_theta(JoinAlgos::IEJoin, |left, right, _params| {
+			Box::new(IEJoin::new(left, right))
+		});
+
+		// Column-at-a-time versions of the hash joins
+		registry.register_columnar(JoinAlgos::SimpleHashJoin, |left, right, params| {
+			Box::new(ColumnarHashJoin::with_kind(left, right, params.join_kind))
+		});
+		registry.register_columnar(JoinAlgos::RadixJoin, |left, right, params| {
+			let radix_bits: &[u32] = match params.radix_bits.as_slice() {
+				[] => &DEFAULT_RADIX_BITS,
+				bits => bits,
+			};
+			Box::new(ColumnarRadixJoin::with_radix_bits(left, right, params.join_kind, radix_bits))
+		});
+		registry
+	}
+
+	// Adds an operator, replacing any previous
+	// factory registered for the same algorithm
+	pub fn register(&mut self, algo: JoinAlgos, factory: JoinFactory) {
+		self.factories.insert(algo, factory);
+	}
+
+	pub fn contains(&self, algo: &JoinAlgos) -> bool {
+		self.factories.contains_key(algo)
+	}
+
+	// Adds a theta join operator, replacing any previous
+	// factory registered for the same algorithm
+	pub fn register_theta(&mut self, algo: JoinAlgos, factory: ThetaJoinFactory) {
+		self.theta_factories.insert(algo, factory);
+	}
+
+	pub fn contains_theta(&self, algo: &JoinAlgos) -> bool {
+		self.theta_factories.contains_key(algo)
+	}
+
+	// Adds a columnar join operator, replacing any previous
+	// factory registered for the same algorithm
+	pub fn register_columnar(&mut self, algo: JoinAlgos, factory: ColumnarJoinFactory) {
+		self.columnar_factories.insert(algo, factory);
+	}
+
+	pub fn contains_columnar(&self, algo: &JoinAlgos) -> bool {
+		self.columnar_factories.contains_key(algo)
+	}
+
+	// Registered equi-join algorithms, built-in ones first
+	pub fn algos(&self) -> Vec<JoinAlgos> {
+		ordered_algos(&self.factories)
+	}
+
+	// Registered theta join algorithms, built-in ones first
+	pub fn theta_algos(&self) -> Vec<JoinAlgos> {
+		ordered_algos(&self.theta_factories)
+	}
+
+	// Registered columnar join algorithms, built-in ones first
+	pub fn columnar_algos(&self) -> Vec<JoinAlgos> {
+		ordered_algos(&self.columnar_factories)
+	}
+
+	// Builds the operator registered for algo
+	pub fn create<'a>(&self,
+										algo: &JoinAlgos,
+										left: &'a mut SimpleTable,
+										right: &'a mut SimpleTable,
+										params: &JoinParams) -> Box<dyn EquiJoin + 'a> {
+		check_kind(algo, params);
+		match self.factories.get(algo) {
+			None => panic!("No join registered for {:?}", algo),
+			Some(factory) => factory(left, right, params),
+		}
+	}
+
+	// Builds the theta join operator registered for algo
+	pub fn create_theta<'a>(&self,
+													algo: &JoinAlgos,
+													left: &'a mut SimpleTable,
+													right: &'a mut SimpleTable,
+													params: &JoinParams) -> Box<dyn ThetaJoin + 'a> {
+		check_kind(algo, params);
+		match self.theta_factories.get(algo) {
+			None => panic!("No theta join registered for {:?}", algo),
+			Some(factory) => factory(left, right, params),
+		}
+	}
+
+	// Builds the columnar join operator registered for algo
+	pub fn create_columnar<'a>(&self,
+														 algo: &JoinAlgos,
+														 left: &'a ColumnarTable,
+														 right: &'a ColumnarTable,
+														 params: &JoinParams) -> Box<dyn ColumnarJoin + 'a> {
+		check_kind(algo, params);
+		match self.columnar_factories.get(algo) {
+			None => panic!("No columnar join registered for {:?}", algo),
+			Some(factory) => factory(left, right, params),
+		}
+	}
+}
+
+// Keys of a factory map in the order of JoinAlgos,
+// followed by any custom algorithms sorted by name
+fn ordered_algos<F>(factories: &HashMap<JoinAlgos, F>) -> Vec<JoinAlgos> {
+	let mut algos: Vec<JoinAlgos> = JoinAlgos::iter()
+		.filter(|algo| factories.contains_key(algo))
+		.collect();
+	let mut custom: Vec<JoinAlgos> = factories.keys()
+		.filter(|algo| matches!(algo, JoinAlgos::Custom(_)))
+		.cloned()
+		.collect();
+	custom.sort_by_key(|algo| format!("{:?}", algo));
+	algos.extend(custom);
+	algos
+}
+
+impl Default for JoinRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
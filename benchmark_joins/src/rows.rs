@@ -0,0 +1,220 @@
+use std::fmt::Debug;
+
+use crate::record::{Record, M};
+use crate::schema::{decode_value, encode_value, Schema, Value};
+
+// Rows of any number of columns, stored back to back in one arena:
+// the values of row i are values[i * width..(i + 1) * width], and its
+// null bitmap the words of nulls at the same row offset. Unlike
+// Record there is no cap on the width, at the cost of rows living
+// in their table rather than being values of their own
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rows {
+	width: usize,
+	// Null bitmap words per row
+	words: usize,
+	len: usize,
+	values: Vec<i32>,
+	nulls: Vec<u64>,
+}
+
+impl Rows {
+	pub fn new(width: usize) -> Self {
+		Self::with_capacity(width, 0)
+	}
+
+	pub fn with_capacity(width: usize, num_rows: usize) -> Self {
+		let words = width.div_ceil(64);
+		Self {
+			width,
+			words,
+			len: 0,
+			values: Vec::with_capacity(width * num_rows),
+			nulls: Vec::with_capacity(words * num_rows),
+		}
+	}
+
+	pub fn from_records(width: usize, records: &[Record]) -> Self {
+		let mut rows = Self::with_capacity(width, records.len());
+		for r in records {
+			rows.push_record(r);
+		}
+		rows
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn get(&self, i: usize) -> Row<'_> {
+		Row {
+			values: &self.values[i * self.width..(i + 1) * self.width],
+			nulls: &self.nulls[i * self.words..(i + 1) * self.words],
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+		(0..self.len()).map(move |i| self.get(i))
+	}
+
+	// Appends a row of the values, none of them NULL
+	pub fn push(&mut self, values: &[i32]) {
+		if values.len() != self.width {
+			panic!("Row input has {:?} columns, but the rows have {:?}", values.len(), self.width);
+		}
+		self.values.extend_from_slice(values);
+		self.nulls.resize(self.nulls.len() + self.words, 0);
+		self.len += 1;
+	}
+
+	// Appends a row of only NULLs
+	pub fn push_nulls(&mut self) {
+		self.values.resize(self.values.len() + self.width, 0);
+		self.nulls.resize(self.nulls.len() + self.words, 0);
+		self.len += 1;
+		for i in 0..self.width {
+			self.set_null(self.len - 1, i);
+		}
+	}
+
+	pub fn push_record(&mut self, record: &Record) {
+		self.push(record.get_column_values());
+		let last = self.len() - 1;
+		for i in (0..self.width).filter(|i| record.is_null(*i)) {
+			self.set_null(last, i);
+		}
+	}
+
+	// Appends the left row followed by the right one, either of
+	// which is all NULL when missing, e.g. for outer joins
+	pub fn push_merged(&mut self, left: Option<Row>, left_width: usize, right: Option<Row>) {
+		let start = self.len;
+		self.values.resize(self.values.len() + self.width, 0);
+		self.nulls.resize(self.nulls.len() + self.words, 0);
+		self.len += 1;
+		let parts = [(left, 0, left_width), (right, left_width, self.width - left_width)];
+		for (row, offset, width) in parts {
+			for i in 0..width {
+				match row {
+					Some(row) if !row.is_null(i) => self.set_column(start, offset + i, *row.get_column(i)),
+					_ => self.set_null(start, offset + i),
+				}
+			}
+		}
+	}
+
+	pub fn set_column(&mut self, row: usize, i: usize, value: i32) {
+		if i >= self.width {
+			panic!("OOB Index")
+		}
+		self.values[row * self.width + i] = value;
+		self.nulls[row * self.words + i / 64] &= !(1 << (i % 64));
+	}
+
+	pub fn set_null(&mut self, row: usize, i: usize) {
+		if i >= self.width {
+			panic!("OOB Index")
+		}
+		self.values[row * self.width + i] = 0;
+		self.nulls[row * self.words + i / 64] |= 1 << (i % 64);
+	}
+
+	// Stores a value in the slots of a column of the schema
+	pub fn set_value(&mut self, row: usize, schema: &Schema, column: usize, value: &Value) {
+		let slots = schema.slots(column);
+		match encode_value(schema.column_type(column), value) {
+			None => slots.for_each(|i| self.set_null(row, i)),
+			Some(encoded) => slots.zip(encoded).for_each(|(i, v)| self.set_column(row, i, v)),
+		}
+	}
+
+	// The rows as records, which they must fit in
+	pub fn to_records(&self) -> Vec<Record> {
+		if self.width > M {
+			panic!("Rows have {:?} columns, but max support is {:?}", self.width, M);
+		}
+		self.iter().map(|r| r.to_record()).collect()
+	}
+}
+
+// A row of a Rows arena, read like a Record
+#[derive(Clone, Copy)]
+pub struct Row<'a> {
+	values: &'a [i32],
+	nulls: &'a [u64],
+}
+
+impl<'a> Row<'a> {
+	pub fn get_column(&self, i: usize) -> &'a i32 {
+		if i >= self.values.len() {
+			panic!("OOB Index")
+		}
+		&self.values[i]
+	}
+
+	pub fn is_null(&self, i: usize) -> bool {
+		if i >= self.values.len() {
+			panic!("OOB Index")
+		}
+		self.nulls[i / 64] & (1 << (i % 64)) != 0
+	}
+
+	// Value of column i, or None when it is NULL
+	pub fn get_nullable_column(&self, i: usize) -> Option<&'a i32> {
+		if self.is_null(i) {
+			None
+		} else {
+			Some(self.get_column(i))
+		}
+	}
+
+	pub fn get_column_values(&self) -> &'a [i32] {
+		self.values
+	}
+
+	pub fn get_num_columns(&self) -> usize {
+		self.values.len()
+	}
+
+	// Value of a column of the schema, read from its slots
+	pub fn get_value(&self, schema: &Schema, column: usize) -> Value {
+		let slots = schema.slots(column);
+		if self.is_null(slots.start) {
+			return Value::Null;
+		}
+		decode_value(schema.column_type(column), &self.values[slots])
+	}
+
+	// The row as a record, which it must fit in
+	pub fn to_record(&self) -> Record {
+		let mut record = Record::new(self.values);
+		for i in (0..self.values.len()).filter(|i| self.is_null(*i)) {
+			record.set_null(i);
+		}
+		record
+	}
+}
+
+impl<'a> Debug for Row<'a> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_list()
+			.entries((0..self.values.len()).map(|i| self.get_nullable_column(i)))
+			.finish()
+	}
+}
+
+impl<'a> PartialEq for Row<'a> {
+	fn eq(&self, other: &Self) -> bool {
+		self.values == other.values && self.nulls == other.nulls
+	}
+}
+
+impl<'a> Eq for Row<'a> {}
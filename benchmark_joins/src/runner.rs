@@ -1,30 +1,53 @@
 
 use crate::record::Record;
 use std::{time::Instant};
-use strum::IntoEnumIterator;
 use serde::{Deserialize, Serialize};
 
-use crate::join::BlockNL;
-use crate::join::NestedLoopsJoin;
-use crate::join::SimpleHashJoin;
+use crate::aggregate::Aggregate;
 use crate::join::JoinAlgos;
+use crate::join::JoinKind;
+use crate::join::JoinOperator;
+use crate::join::JoinParams;
+use crate::key::JoinKeys;
+use crate::materialize::{distinct_left, materialize, Projection};
 
-use crate::radixjoin::RadixJoin;
-use crate::parjoin::*;
-use crate::table::SimpleTable;
+use crate::columnartable::ColumnarTable;
+use crate::registry::JoinRegistry;
+use crate::rows::Rows;
+use crate::schema::Schema;
+use crate::table::{write_table, SimpleTable};
+use crate::thetajoin::ThetaPredicate;
+use crate::widejoin::wide_join;
+use crate::widetable::{write_wide_table, WideTable};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Table {
 	table_name: String,
 	num_records: usize,
 	columns_per_record: usize,
+	// Types of the columns, for writing the join output
+	#[serde(skip)]
+	schema: Schema,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JoinAlgoDetails {
 	join_name: JoinAlgos,
-	left_block_size: usize,
-	right_block_size: usize,
+	#[serde(flatten)]
+	params: JoinParams,
+}
+
+// What a run reduces the join output to: the merged records,
+// their number only, or an aggregate of each join key. Distinct
+// runs semi and anti joins as an inner join whose left rows are
+// then deduplicated, as the baseline for the semi and anti operators
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum OutputMode {
+	#[default]
+	Records,
+	Count,
+	Aggregate(Aggregate),
+	Distinct,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -34,10 +57,30 @@ pub struct JoinRunResult {
 	outer_table: Table,
 	inner_table: Table,
 	num_emitted_records: usize,
-	pub trial_number: i8
+	pub trial_number: i8,
+	// Set for theta joins, which have no join columns
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	theta_predicate: Option<ThetaPredicate>,
+	// Bytes the operator wrote to disk while joining
+	#[serde(default)]
+	bytes_spilled: u64,
+	// Whether the tables were stored column by column
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	columnar: bool,
+	// Set for late materialization, where the execution time only
+	// covers the join index and this the records built from it
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	materialize_time_nanos: Option<u128>,
+	// Set for streamed runs, whose output is handed over
+	// in batches of up to this many records and not kept
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	batch_size: Option<usize>,
+	// For aggregates, num_emitted_records counts the groups
+	#[serde(default)]
+	output_mode: OutputMode,
 }
 
-fn flush_caches() -> () {
+fn flush_caches() {
 	// TODO confirm that this actually
 	// flushes the caches!
 	//
@@ -45,165 +88,198 @@ fn flush_caches() -> () {
 	// then write 0 everywhere
 	const N: usize = 1000 * 1000;
 	let mut a: [i32; N] = [1; N];
-	for i in 0..N {
-		a[i] = 0;
+	for v in a.iter_mut() {
+		*v = 0;
 	}
 }
 
-fn run_bnl_join(bnlj: &mut BlockNL, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
-	flush_caches();
-	let start: Instant = Instant::now();
-	let results: Vec<Record> = bnlj.equi_join(left_col, right_col);
-	let end: Instant = Instant::now();
+// Schema of the join output, whose columns are named left_col1, ...
+// then right_col1, ... unless the join drops the right columns
+fn output_schema(t1: &Table, t2: &Table, join_kind: JoinKind) -> Schema {
+	projected_output_schema(t1, t2, join_kind, &Projection::default())
+}
 
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::BNLJoin,
-			left_block_size: bnlj.get_left_block_size(),
-			right_block_size: bnlj.get_right_block_size(),
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
-	}
+// Same as output_schema, keeping the projected columns only
+fn projected_output_schema(t1: &Table, t2: &Table, join_kind: JoinKind, projection: &Projection) -> Schema {
+	let (left, right) = named_schemas(t1, t2);
+	projection.schema(&left, &right, join_kind)
 }
 
-fn run_nl_join(nlj: &mut NestedLoopsJoin, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
-	flush_caches();
-	let start: Instant = Instant::now();
-	let results: Vec<Record> = nlj.equi_join(left_col, right_col);
-	let end: Instant = Instant::now();
+// Schemas of the tables under their names in the join output
+fn named_schemas(t1: &Table, t2: &Table) -> (Schema, Schema) {
+	let names = |side: &str, table: &Table| -> Vec<String> {
+		(1..=table.schema.num_columns()).map(|c| format!("{}_col{}", side, c)).collect()
+	};
+	(t1.schema.renamed(&names("left", t1)), t2.schema.renamed(&names("right", t2)))
+}
 
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::NLJoin,
-			left_block_size: 0,
-			right_block_size: 0,
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
-	}
+// What the join compares
+pub enum JoinCondition {
+	Keys(JoinKeys),
+	Theta(ThetaPredicate),
 }
 
-fn run_pnl_join(pnlj: &mut ParallelNestedLoopsJoin, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
-	flush_caches();
-	let start: Instant = Instant::now();
-	let results: Vec<Record> = pnlj.equi_join(left_col, right_col);
-	let end: Instant = Instant::now();
+// How a run stores the tables and what it does with the join output.
+// Not every combination is supported, see check
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+	// Tables wider than a record, joined through their key columns
+	pub wide: bool,
+	// Tables stored column by column, for hash and radix
+	pub columnar: bool,
+	// Join into row ids, then build the records of these columns apart
+	pub projection: Option<Projection>,
+	// Stream the join output in batches of this many records,
+	// which are counted and dropped rather than collected
+	pub batch_size: Option<usize>,
+	// What the join output is reduced to
+	pub output_mode: OutputMode,
+	// Where to write the join output, in the format of its extension
+	pub output_table: Option<String>,
+}
 
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::PNLJoin,
-			left_block_size: 0,
-			right_block_size: 0,
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
+impl RunOptions {
+	// Errors for the options which cannot be combined with each other,
+	// the algorithm, the join condition or the join kind, before any
+	// join runs. The baseline for semi and anti joins runs an inner join
+	pub fn check(&self, algo: &JoinAlgos, condition: &JoinCondition, join_kind: JoinKind) -> Result<(), String> {
+		match self.output_mode {
+			OutputMode::Distinct => algo.check_kind(JoinKind::Inner)?,
+			_ => algo.check_kind(join_kind)?,
+		}
+		let reduced = self.output_mode != OutputMode::Records;
+		if self.output_mode == OutputMode::Count && self.output_table.is_some() {
+			return Err("--count emits no records to write to --output-table".to_owned());
+		}
+		let late = self.projection.is_some();
+		let streamed = self.batch_size.is_some();
+		let semi_or_anti = join_kind == JoinKind::LeftSemi || join_kind == JoinKind::LeftAnti;
+		match condition {
+			_ if self.output_mode == OutputMode::Distinct && !semi_or_anti => {
+				Err("--distinct is the baseline for semi and anti joins only".to_owned())
+			},
+			JoinCondition::Theta(_) if late || streamed || reduced => {
+				Err("Theta joins do not support late materialization, --batch-size, --count, --aggregate or --distinct".to_owned())
+			},
+			JoinCondition::Theta(_) if self.wide || self.columnar => Err("Theta joins do not support --wide or --columnar tables".to_owned()),
+			JoinCondition::Keys(_) if late && (self.wide || self.columnar) => {
+				Err("Late materialization does not support --wide or --columnar tables".to_owned())
+			},
+			JoinCondition::Keys(_) if streamed && (self.wide || self.columnar || late || self.output_table.is_some()) => {
+				Err("Streamed joins do not support --wide, --columnar, late materialization or --output-table".to_owned())
+			},
+			JoinCondition::Keys(_) if reduced && (self.wide || self.columnar || late || streamed) => {
+				Err("--count, --aggregate and --distinct do not support --wide, --columnar, late materialization or --batch-size".to_owned())
+			},
+			_ => Ok(()),
+		}
 	}
 }
 
-fn run_simplehash_join(shj: &mut SimpleHashJoin, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
-	flush_caches();
-	let start: Instant = Instant::now();
-	let results: Vec<Record> = shj.equi_join(left_col, right_col);
-	let end: Instant = Instant::now();
+// What a run measured of the join
+struct Measured {
+	execution_time_nanos: u128,
+	// Set for late materialization
+	materialize_time_nanos: Option<u128>,
+	num_emitted_records: usize,
+	bytes_spilled: u64,
+}
 
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::SimpleHashJoin,
-			left_block_size: 0,
-			right_block_size: 0,
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
+impl JoinRunResult {
+	// Result of a run, whose trial number the caller sets
+	fn new(join_type: JoinAlgoDetails,
+				 (outer_table, inner_table): (Table, Table),
+				 measured: Measured,
+				 condition: &JoinCondition,
+				 options: &RunOptions) -> JoinRunResult {
+		JoinRunResult {
+			join_type,
+			execution_time_nanos: measured.execution_time_nanos,
+			outer_table,
+			inner_table,
+			num_emitted_records: measured.num_emitted_records,
+			trial_number: -1,
+			theta_predicate: match condition {
+				JoinCondition::Keys(_) => None,
+				JoinCondition::Theta(predicate) => Some(predicate.clone()),
+			},
+			bytes_spilled: measured.bytes_spilled,
+			columnar: options.columnar,
+			materialize_time_nanos: measured.materialize_time_nanos,
+			batch_size: options.batch_size,
+			output_mode: options.output_mode.clone(),
+		}
 	}
 }
 
-fn run_radix_join(rj: &mut RadixJoin, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
-	flush_caches();
-	let start: Instant = Instant::now();
-	let results: Vec<Record> = rj.equi_join(left_col, right_col);
-	let end: Instant = Instant::now();
-
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::RadixJoin,
-			left_block_size: 0,
-			right_block_size: 0,
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
+// Name and parameters of an operator
+fn algo_details<J: JoinOperator + ?Sized>(join: &J) -> JoinAlgoDetails {
+	JoinAlgoDetails {
+		join_name: join.name(),
+		params: join.parameters(),
 	}
 }
 
-fn run_psh_join(pshj: &mut ParallelSimpleHashJoin, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
+// Runs f with cold caches, returning its result and how long it took
+fn timed<T, F: FnOnce() -> T>(f: F) -> (T, u128) {
 	flush_caches();
 	let start: Instant = Instant::now();
-	let results: Vec<Record> = pshj.equi_join(left_col, right_col);
+	let result = f();
 	let end: Instant = Instant::now();
+	(result, end.duration_since(start).as_nanos())
+}
 
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::ParallelSimpleHashJoin,
-			left_block_size: 0,
-			right_block_size: 0,
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
+// Writes the join output, after the timed part of the run
+fn write_output(options: &RunOptions, results: &[Record], schema: &Schema) {
+	if let Some(path) = &options.output_table {
+		if let Err(e) = write_table(path, schema, results) {
+			panic!("Could not write join output to {:?} {:?}", path, e);
+		}
 	}
 }
 
-fn run_pulf_join(pulf: &mut ParallelUnaryLeapFrogJoin, left_col: usize, right_col: usize, t1: Table, t2: Table) -> JoinRunResult {
-	// Run the join
-	flush_caches();
-	let start: Instant = Instant::now();
-	let results: Vec<Record> = pulf.equi_join(left_col, right_col);
-	let end: Instant = Instant::now();
-
-	// Output result
-	JoinRunResult {
-		join_type: JoinAlgoDetails {
-			join_name: JoinAlgos::ParallelUnaryLeapFrogJoin,
-			left_block_size: 0,
-			right_block_size: 0,
-		},
-		execution_time_nanos: end.duration_since(start).as_nanos(),
-		outer_table: t1,
-		inner_table: t2,
-		num_emitted_records: results.len(),
-		trial_number: -1,
+// Same as write_output, for rows of any width
+fn write_wide_output(options: &RunOptions, rows: &Rows, schema: &Schema) {
+	if let Some(path) = &options.output_table {
+		if let Err(e) = write_wide_table(path, schema, rows) {
+			panic!("Could not write join output to {:?} {:?}", path, e);
+		}
 	}
 }
 
+fn load_table(table_name: &str) -> (SimpleTable, Table) {
+	let table = SimpleTable::new(table_name);
+	let t = Table {
+		table_name: table_name.to_owned(),
+		num_records: table.get_num_records(),
+		columns_per_record: table.get_num_columns_per_record(),
+		schema: table.schema().clone(),
+	};
+	(table, t)
+}
+
+fn load_wide_table(table_name: &str) -> (WideTable, Table) {
+	let table = WideTable::new(table_name);
+	let t = Table {
+		table_name: table_name.to_owned(),
+		num_records: table.get_num_records(),
+		columns_per_record: table.get_num_columns_per_record(),
+		schema: table.schema().clone(),
+	};
+	(table, t)
+}
+
+fn load_columnar_table(table_name: &str) -> (ColumnarTable, Table) {
+	let table = ColumnarTable::new(table_name);
+	let t = Table {
+		table_name: table_name.to_owned(),
+		num_records: table.get_num_records(),
+		columns_per_record: table.get_num_columns_per_record(),
+		schema: table.schema().clone(),
+	};
+	(table, t)
+}
+
 pub fn run_one_join(
 	table1_name: &str, 
 	table2_name: &str,
@@ -212,67 +288,253 @@ pub fn run_one_join(
 	l_block_sz: usize, 
 	r_block_sz: usize,
 	algo: &JoinAlgos) -> JoinRunResult {
-	// Create tables
-	let table1: &mut SimpleTable = &mut SimpleTable::new(table1_name);
-	let t1: Table = Table {
-		table_name: table1_name.to_owned(),
-		num_records: table1.get_num_records(),
-		columns_per_record: table1.get_num_columns_per_record(),
-	};
-	let table2: &mut SimpleTable = &mut SimpleTable::new(table2_name);
-	let t2: Table = Table {
-		table_name: table2_name.to_owned(),
-		num_records: table2.get_num_records(),
-		columns_per_record: table2.get_num_columns_per_record(),
+	let params = JoinParams {
+		left_block_size: l_block_sz,
+		right_block_size: r_block_sz,
+		..JoinParams::default()
 	};
+	run_registered_join(
+		&JoinRegistry::new(),
+		(table1_name, table2_name),
+		&JoinCondition::Keys(JoinKeys::single(left_col, right_col)),
+		&params, algo, &RunOptions::default())
+}
+
+// Same as run_one_join, but builds the join operator from the given
+// registry instead of the built-in one, and runs it as the options
+// say, which check must have accepted. With an output table, the
+// join output of the run is written to it
+pub fn run_registered_join(
+	registry: &JoinRegistry,
+	table_names: (&str, &str),
+	condition: &JoinCondition,
+	params: &JoinParams,
+	algo: &JoinAlgos,
+	options: &RunOptions) -> JoinRunResult {
+	let run = Run { registry, table_names, condition, params, algo, options };
+	match condition {
+		JoinCondition::Theta(predicate) => run.theta_join(predicate),
+		JoinCondition::Keys(keys) if options.output_mode == OutputMode::Distinct => run.distinct_join(keys),
+		JoinCondition::Keys(keys) if options.output_mode != OutputMode::Records => run.reduced_join(keys),
+		JoinCondition::Keys(keys) if options.batch_size.is_some() => run.streamed_join(keys),
+		JoinCondition::Keys(keys) if options.wide => run.wide_join(keys),
+		JoinCondition::Keys(keys) if options.columnar => run.columnar_join(keys),
+		JoinCondition::Keys(keys) if options.projection.is_some() => run.late_materialized_join(keys),
+		JoinCondition::Keys(keys) => run.join(keys),
+	}
+}
+
+// Everything a run is given, shared by the ways of running a join
+struct Run<'r> {
+	registry: &'r JoinRegistry,
+	table_names: (&'r str, &'r str),
+	condition: &'r JoinCondition,
+	params: &'r JoinParams,
+	algo: &'r JoinAlgos,
+	options: &'r RunOptions,
+}
+
+impl<'r> Run<'r> {
+	fn result(&self, join_type: JoinAlgoDetails, tables: (Table, Table), measured: Measured) -> JoinRunResult {
+		JoinRunResult::new(join_type, tables, measured, self.condition, self.options)
+	}
+
+	fn join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (mut table1, t1) = load_table(self.table_names.0);
+		let (mut table2, t2) = load_table(self.table_names.1);
+
+		// Build the operator and run the experiment
+		let mut join = self.registry.create(self.algo, &mut table1, &mut table2, self.params);
+		let (results, execution_time_nanos) = timed(|| join.equi_join_on(keys));
+		write_output(self.options, &results, &output_schema(&t1, &t2, join.join_kind()));
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records: results.len(),
+			bytes_spilled: join.bytes_spilled(),
+		};
+		self.result(algo_details(join.as_ref()), (t1, t2), measured)
+	}
+
+	// The operator streams its output in batches,
+	// which are counted and dropped rather than collected
+	fn streamed_join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (mut table1, t1) = load_table(self.table_names.0);
+		let (mut table2, t2) = load_table(self.table_names.1);
 
-	// Dispatch experiment and result measurement
-	match algo {
-		JoinAlgos::NLJoin => 
-			run_nl_join(
-				&mut NestedLoopsJoin::new(table1, table2), 
-				left_col, 
-				right_col,
-				t1, t2),
-		JoinAlgos::BNLJoin => 
-			run_bnl_join(
-				&mut BlockNL::new(table1, table2, l_block_sz, r_block_sz), 
-				left_col, 
-				right_col,
-				t1, t2),
-		JoinAlgos::SimpleHashJoin =>
-			run_simplehash_join(
-				&mut SimpleHashJoin::new(table1, table2), 
-				left_col, 
-				right_col,
-				t1, t2),
-		JoinAlgos::RadixJoin =>
-			run_radix_join(
-				&mut RadixJoin::new(table1, table2), 
-				left_col, 
-				right_col,
-				t1, t2),
-		JoinAlgos::PNLJoin => {
-			run_pnl_join(
-				&mut ParallelNestedLoopsJoin::new(table1, table2), 
-				left_col, 
-				right_col,
-				t1, t2)
+		// Build the operator and run the join
+		let mut join = self.registry.create(self.algo, &mut table1, &mut table2, self.params);
+		let batch_size = self.options.batch_size.unwrap();
+		let mut num_emitted_records = 0;
+		let ((), execution_time_nanos) = timed(|| {
+			join.equi_join_batches(keys, batch_size, &mut |batch: &[Record]| num_emitted_records += batch.len())
+		});
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records,
+			bytes_spilled: join.bytes_spilled(),
+		};
+		self.result(algo_details(join.as_ref()), (t1, t2), measured)
+	}
+
+	// The operator reduces its output to the output mode as it runs,
+	// without merging records. Only aggregates have output records,
+	// one per join key, to write to the output table
+	fn reduced_join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (mut table1, t1) = load_table(self.table_names.0);
+		let (mut table2, t2) = load_table(self.table_names.1);
+
+		// Build the operator and run the join
+		let mut join = self.registry.create(self.algo, &mut table1, &mut table2, self.params);
+		let join_kind = join.join_kind();
+		let ((num_emitted_records, results), execution_time_nanos) = timed(|| match &self.options.output_mode {
+			OutputMode::Count => (join.count_on(keys), Vec::new()),
+			OutputMode::Aggregate(aggregate) => {
+				let results = join.aggregate_on(keys, aggregate);
+				(results.len(), results)
 			},
-		JoinAlgos::ParallelSimpleHashJoin => {
-			run_psh_join(
-				&mut ParallelSimpleHashJoin::new(table1, table2), 
-				left_col, 
-				right_col,
-				t1, t2)
-		},
-		JoinAlgos::ParallelUnaryLeapFrogJoin => {
-			run_pulf_join(
-				&mut ParallelUnaryLeapFrogJoin::new(table1, table2), 
-				left_col, 
-				right_col,
-				t1, t2)
-		},
+			OutputMode::Records | OutputMode::Distinct => unreachable!("Records and distinct runs are join's and distinct_join's"),
+		});
+		if let OutputMode::Aggregate(aggregate) = &self.options.output_mode {
+			let (left, right) = named_schemas(&t1, &t2);
+			write_output(self.options, &results, &aggregate.schema(&left, &right, keys, join_kind));
+		}
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records,
+			bytes_spilled: join.bytes_spilled(),
+		};
+		self.result(algo_details(join.as_ref()), (t1, t2), measured)
+	}
+
+	// The baseline for semi and anti joins: the operator runs an inner
+	// join into a join index, whose distinct left row ids then give the
+	// semi or anti join's records, see distinct_left. The timed part
+	// covers both steps, and the result reports the semi or anti join
+	// kind, so that it lines up with the runs of the semi and anti
+	// operators
+	fn distinct_join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (mut table1, t1) = load_table(self.table_names.0);
+		let (mut table2, t2) = load_table(self.table_names.1);
+		let join_kind = self.params.join_kind;
+		let inner = JoinParams {
+			join_kind: JoinKind::Inner,
+			..self.params.clone()
+		};
+
+		// Build the operator and run the join
+		let mut join = self.registry.create(self.algo, &mut table1, &mut table2, &inner);
+		let (results, execution_time_nanos) = timed(|| {
+			let index = join.join_index_on(keys);
+			distinct_left(&index, join.tables().0, join_kind)
+		});
+		write_output(self.options, &results, &output_schema(&t1, &t2, join_kind));
+		let mut join_type = algo_details(join.as_ref());
+		join_type.params.join_kind = join_kind;
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records: results.len(),
+			bytes_spilled: join.bytes_spilled(),
+		};
+		self.result(join_type, (t1, t2), measured)
+	}
+
+	// Late materialization: the operator emits a join index of row
+	// ids, from which the records of the projected columns are then
+	// built. The two steps are timed apart
+	fn late_materialized_join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (mut table1, t1) = load_table(self.table_names.0);
+		let (mut table2, t2) = load_table(self.table_names.1);
+		let projection = self.options.projection.as_ref().unwrap();
+
+		// Build the operator and run the join
+		let mut join = self.registry.create(self.algo, &mut table1, &mut table2, self.params);
+		let (index, execution_time_nanos) = timed(|| join.join_index_on(keys));
+		let join_type = algo_details(join.as_ref());
+		let bytes_spilled = join.bytes_spilled();
+		drop(join);
+
+		// Build the records from the row ids
+		let join_kind = join_type.params.join_kind;
+		let materialize_start: Instant = Instant::now();
+		let results: Vec<Record> = materialize(&index, &table1, &table2, join_kind, projection);
+		let materialize_end: Instant = Instant::now();
+		write_output(self.options, &results, &projected_output_schema(&t1, &t2, join_kind, projection));
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: Some(materialize_end.duration_since(materialize_start).as_nanos()),
+			num_emitted_records: results.len(),
+			bytes_spilled,
+		};
+		self.result(join_type, (t1, t2), measured)
+	}
+
+	// Tables of any width, see wide_join. The timed part includes
+	// projecting the key columns and gathering the output rows
+	fn wide_join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (table1, t1) = load_wide_table(self.table_names.0);
+		let (table2, t2) = load_wide_table(self.table_names.1);
+
+		let ((results, bytes_spilled), execution_time_nanos) = timed(|| {
+			wide_join(self.registry, self.algo, self.params, &table1, &table2, keys)
+		});
+		write_wide_output(self.options, results.rows(), &output_schema(&t1, &t2, self.params.join_kind));
+		let join_type = JoinAlgoDetails {
+			join_name: self.algo.clone(),
+			params: self.params.clone(),
+		};
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records: results.get_num_records(),
+			bytes_spilled,
+		};
+		self.result(join_type, (t1, t2), measured)
+	}
+
+	// Columnar tables, with the columnar operator registered for the
+	// algorithm. The timed part includes gathering the output columns
+	fn columnar_join(&self, keys: &JoinKeys) -> JoinRunResult {
+		let (table1, t1) = load_columnar_table(self.table_names.0);
+		let (table2, t2) = load_columnar_table(self.table_names.1);
+
+		// Build the operator and run the experiment
+		let mut join = self.registry.create_columnar(self.algo, &table1, &table2, self.params);
+		let (results, execution_time_nanos) = timed(|| join.equi_join_on(keys));
+		if self.options.output_table.is_some() {
+			write_wide_output(self.options, &results.to_rows(), &output_schema(&t1, &t2, join.parameters().join_kind));
+		}
+		let join_type = JoinAlgoDetails {
+			join_name: join.name(),
+			params: join.parameters(),
+		};
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records: results.get_num_records(),
+			bytes_spilled: 0,
+		};
+		self.result(join_type, (t1, t2), measured)
+	}
+
+	fn theta_join(&self, predicate: &ThetaPredicate) -> JoinRunResult {
+		let (mut table1, t1) = load_table(self.table_names.0);
+		let (mut table2, t2) = load_table(self.table_names.1);
+
+		// Build the operator and run the experiment
+		let mut join = self.registry.create_theta(self.algo, &mut table1, &mut table2, self.params);
+		let (results, execution_time_nanos) = timed(|| join.theta_join(predicate));
+		write_output(self.options, &results, &output_schema(&t1, &t2, join.join_kind()));
+		let measured = Measured {
+			execution_time_nanos,
+			materialize_time_nanos: None,
+			num_emitted_records: results.len(),
+			bytes_spilled: join.bytes_spilled(),
+		};
+		self.result(algo_details(join.as_ref()), (t1, t2), measured)
 	}
 }
 
@@ -286,7 +548,7 @@ pub fn run_all_joins(
 		
 	// Profile our joins on the input tables
 	let mut results: Vec<JoinRunResult> = Vec::new();
-	for algo in JoinAlgos::iter() {
+	for algo in JoinRegistry::new().algos() {
 		// Run join as table1 J table2
 		println!("Running join {:?} on {:?} as left and {:?} as right...", algo, table1_name, table2_name);
 		let result: JoinRunResult = run_one_join(
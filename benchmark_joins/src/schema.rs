@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::record::M;
+
+// Type of a table column. Records hold i32 slots, and each column
+// takes one or more of them: Int32 one, Int64 and Float64 two, in an
+// encoding which keeps their order, and Utf8 one, holding the id of
+// the interned string. Keys of any type are then compared, hashed
+// and sorted slot by slot, so the joins need not know the types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnType {
+	Int32,
+	Int64,
+	Float64,
+	Utf8,
+}
+
+impl ColumnType {
+	// Record slots a value of this type takes
+	pub fn slots(&self) -> usize {
+		match self {
+			ColumnType::Int32 | ColumnType::Utf8 => 1,
+			ColumnType::Int64 | ColumnType::Float64 => 2,
+		}
+	}
+
+	// Value of a CSV field, where empty fields are NULL
+	pub fn parse(&self, text: &str) -> Result<Value, String> {
+		if text.is_empty() {
+			return Ok(Value::Null);
+		}
+		match self {
+			ColumnType::Int32 => text.parse().map(Value::Int32).map_err(|e| format!("{:?} is not an i32, {}", text, e)),
+			ColumnType::Int64 => text.parse().map(Value::Int64).map_err(|e| format!("{:?} is not an i64, {}", text, e)),
+			ColumnType::Float64 => text.parse().map(Value::Float64).map_err(|e| format!("{:?} is not an f64, {}", text, e)),
+			ColumnType::Utf8 => Ok(Value::Utf8(text.into())),
+		}
+	}
+}
+
+impl fmt::Display for ColumnType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			ColumnType::Int32 => "i32",
+			ColumnType::Int64 => "i64",
+			ColumnType::Float64 => "f64",
+			ColumnType::Utf8 => "utf8",
+		})
+	}
+}
+
+impl FromStr for ColumnType {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"i32" | "int" | "int32" => Ok(ColumnType::Int32),
+			"i64" | "bigint" | "int64" => Ok(ColumnType::Int64),
+			"f64" | "double" | "float64" => Ok(ColumnType::Float64),
+			"utf8" | "str" | "string" | "text" => Ok(ColumnType::Utf8),
+			_ => Err(format!("Unknown column type {:?}, expected i32, i64, f64 or utf8", s)),
+		}
+	}
+}
+
+// A value of a column, as read from or written to a record
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Null,
+	Int32(i32),
+	Int64(i64),
+	Float64(f64),
+	Utf8(Arc<str>),
+}
+
+impl Value {
+	pub fn is_null(&self) -> bool {
+		matches!(self, Value::Null)
+	}
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Value::Null => f.write_str("NULL"),
+			Value::Int32(v) => write!(f, "{}", v),
+			Value::Int64(v) => write!(f, "{}", v),
+			Value::Float64(v) => write!(f, "{}", v),
+			Value::Utf8(v) => f.write_str(v),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+	pub name: String,
+	pub column_type: ColumnType,
+}
+
+impl Column {
+	pub fn new(name: &str, column_type: ColumnType) -> Self {
+		Self {
+			name: name.to_owned(),
+			column_type,
+		}
+	}
+}
+
+// Names and types of a table's columns, and where each column's
+// slots start in the table's rows. Schemas of any width can be
+// built, but only those which fit fit in a Record
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+	columns: Vec<Column>,
+	offsets: Vec<usize>,
+}
+
+impl Schema {
+	pub fn new(columns: Vec<Column>) -> Self {
+		let mut offsets: Vec<usize> = Vec::with_capacity(columns.len() + 1);
+		offsets.push(0);
+		for c in columns.iter() {
+			offsets.push(offsets[offsets.len() - 1] + c.column_type.slots());
+		}
+		Self {
+			columns,
+			offsets,
+		}
+	}
+
+	// All Int32 columns, as in the generated tables
+	pub fn int32(column_names: &[String]) -> Self {
+		Self::new(column_names.iter().map(|n| Column::new(n, ColumnType::Int32)).collect())
+	}
+
+	// Whether columns of these types fit in a record
+	pub fn fits(types: &[ColumnType]) -> bool {
+		types.iter().map(|t| t.slots()).sum::<usize>() <= M
+	}
+
+	// Whether rows of this schema fit in a record
+	pub fn fits_record(&self) -> bool {
+		self.num_slots() <= M
+	}
+
+	pub fn columns(&self) -> &[Column] {
+		&self.columns
+	}
+
+	pub fn column_names(&self) -> Vec<String> {
+		self.columns.iter().map(|c| c.name.clone()).collect()
+	}
+
+	pub fn column_type(&self, column: usize) -> ColumnType {
+		match self.columns.get(column) {
+			Some(c) => c.column_type,
+			None => panic!("Schema has {:?} columns, no column {:?}", self.columns.len(), column),
+		}
+	}
+
+	pub fn num_columns(&self) -> usize {
+		self.columns.len()
+	}
+
+	// Record slots taken by all of the columns
+	pub fn num_slots(&self) -> usize {
+		self.offsets[self.columns.len()]
+	}
+
+	// Record slots of a column
+	pub fn slots(&self, column: usize) -> Range<usize> {
+		if column >= self.columns.len() {
+			panic!("Schema has {:?} columns, no column {:?}", self.columns.len(), column);
+		}
+		self.offsets[column]..self.offsets[column + 1]
+	}
+
+	// Whether every column is an Int32, so that columns and slots are the same
+	pub fn is_int32(&self) -> bool {
+		self.columns.iter().all(|c| c.column_type == ColumnType::Int32)
+	}
+
+	// Schema of records merged from records of this schema and another
+	pub fn merge(&self, other: &Schema) -> Schema {
+		Schema::new(self.columns.iter().chain(other.columns.iter()).cloned().collect())
+	}
+
+	// Schema of the given columns, in that order
+	pub fn project(&self, columns: &[usize]) -> Schema {
+		Schema::new(columns.iter()
+			.map(|c| match self.columns.get(*c) {
+				Some(column) => column.clone(),
+				None => panic!("Schema has {:?} columns, no column {:?}", self.columns.len(), c),
+			})
+			.collect())
+	}
+
+	// Same columns under other names
+	pub fn renamed(&self, column_names: &[String]) -> Schema {
+		assert_eq!(column_names.len(), self.columns.len());
+		Schema::new(self.columns.iter()
+			.zip(column_names)
+			.map(|(c, name)| Column::new(name, c.column_type))
+			.collect())
+	}
+}
+
+impl Default for Schema {
+	fn default() -> Self {
+		Schema::new(Vec::new())
+	}
+}
+
+// Int64s are split into a high slot, which orders by sign, and a
+// low slot whose top bit is flipped, so that comparing the slots
+// as i32s orders them like the i64s
+pub(crate) fn encode_i64(v: i64) -> [i32; 2] {
+	[(v >> 32) as i32, ((v as u32) ^ 0x8000_0000) as i32]
+}
+
+pub(crate) fn decode_i64(slots: [i32; 2]) -> i64 {
+	((slots[0] as i64) << 32) | (((slots[1] as u32) ^ 0x8000_0000) as i64)
+}
+
+// Float64s are mapped onto i64s of the same order. Negative zero is
+// stored as zero and every NaN as the same NaN, so that equal floats
+// have equal slots
+pub(crate) fn encode_f64(v: f64) -> [i32; 2] {
+	let v = if v == 0.0 { 0.0 } else if v.is_nan() { f64::NAN } else { v };
+	let bits = v.to_bits() as i64;
+	encode_i64(if bits < 0 { bits ^ i64::MAX } else { bits })
+}
+
+pub(crate) fn decode_f64(slots: [i32; 2]) -> f64 {
+	let bits = decode_i64(slots);
+	f64::from_bits((if bits < 0 { bits ^ i64::MAX } else { bits }) as u64)
+}
+
+// Value of a column from its slots, which are not NULL
+pub(crate) fn decode_value(column_type: ColumnType, slots: &[i32]) -> Value {
+	match column_type {
+		ColumnType::Int32 => Value::Int32(slots[0]),
+		ColumnType::Int64 => Value::Int64(decode_i64([slots[0], slots[1]])),
+		ColumnType::Float64 => Value::Float64(decode_f64([slots[0], slots[1]])),
+		ColumnType::Utf8 => Value::Utf8(interned(slots[0])),
+	}
+}
+
+// Slots of a value of a column, or None for NULL. Only the
+// first ColumnType::slots of them are used
+pub(crate) fn encode_value(column_type: ColumnType, value: &Value) -> Option<[i32; 2]> {
+	match (column_type, value) {
+		(_, Value::Null) => None,
+		(ColumnType::Int32, Value::Int32(v)) => Some([*v, 0]),
+		(ColumnType::Int64, Value::Int64(v)) => Some(encode_i64(*v)),
+		(ColumnType::Float64, Value::Float64(v)) => Some(encode_f64(*v)),
+		(ColumnType::Utf8, Value::Utf8(v)) => Some([intern(v), 0]),
+		(column_type, value) => panic!("Value {:?} does not fit a {} column", value, column_type),
+	}
+}
+
+// Strings are stored as ids handed out in order of first sight, so
+// they compare equal exactly when the strings do, but do not sort like
+// them. The dictionary is shared by every table so that equal keys on
+// either side of a join get equal ids, which is also why it is never
+// pruned: it holds every distinct string the process has loaded.
+// Ids are only meaningful within the process that interned them, so
+// Utf8 columns live in memory only. CSV, Arrow and Parquet tables
+// store the strings themselves, while binary and record tables refuse
+// Utf8 columns. Spilled partitions stay within the process, so they
+// may keep the ids
+#[derive(Default)]
+struct Interner {
+	ids: HashMap<Arc<str>, i32>,
+	strings: Vec<Arc<str>>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+	static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+	INTERNER.get_or_init(|| RwLock::new(Interner::default()))
+}
+
+pub fn intern(s: &str) -> i32 {
+	if let Some(id) = interner().read().unwrap().ids.get(s) {
+		return *id;
+	}
+	let mut interner = interner().write().unwrap();
+	if let Some(id) = interner.ids.get(s) {
+		return *id;
+	}
+	let id = interner.strings.len() as i32;
+	let s: Arc<str> = s.into();
+	interner.strings.push(s.clone());
+	interner.ids.insert(s, id);
+	id
+}
+
+pub fn interned(id: i32) -> Arc<str> {
+	match interner().read().unwrap().strings.get(id as usize) {
+		Some(s) => s.clone(),
+		None => panic!("No string was interned with id {:?}", id),
+	}
+}
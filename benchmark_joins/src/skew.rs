@@ -0,0 +1,99 @@
+use std::collections::{HashMap, HashSet};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::join::{row_id, JoinKind, JoinOutput};
+use crate::key::{JoinKeys, KeyRef};
+use crate::record::Record;
+
+// Records sampled from the build side to find heavy keys
+pub const SAMPLE_SIZE: usize = 1_000;
+
+// A key is heavy when it takes at least this share of the sample
+pub const HEAVY_KEY_SHARE: f64 = 0.01;
+
+// Records of each side in one block of a heavy key's pairs
+const HEAVY_BLOCK_SIZE: usize = 1_024;
+
+// Keys which take at least HEAVY_KEY_SHARE of an evenly spaced
+// sample of the records. A key needs to be seen twice to count,
+// so tables smaller than the sample do not make every key heavy.
+// NULL keys match nothing, so they are never heavy
+pub(crate) fn detect_heavy_keys<'a>(records: &'a [Record], columns: &'a [usize]) -> HashSet<KeyRef<'a>> {
+	let step = (records.len() / SAMPLE_SIZE).max(1);
+	let mut counts: HashMap<KeyRef, usize> = HashMap::new();
+	let mut num_sampled = 0;
+	for record in records.iter().step_by(step) {
+		let key = KeyRef::new(record, columns);
+		if !key.is_null() {
+			*counts.entry(key).or_default() += 1;
+		}
+		num_sampled += 1;
+	}
+
+	let threshold = ((num_sampled as f64 * HEAVY_KEY_SHARE).ceil() as usize).max(2);
+	counts.into_iter()
+		.filter(|(_, count)| *count >= threshold)
+		.map(|(key, _)| key)
+		.collect()
+}
+
+// Joins left records whose keys are heavy with their right matches.
+// Instead of one worker taking all pairs of a key, the left and right
+// records of every key are cut into blocks and each pair of blocks is
+// a separate task, so a hot key is spread over all of the workers.
+// Records are references into views, the left and right record views
+// which give their row ids
+pub(crate) fn join_heavy_keys<O: JoinOutput>(keys: &JoinKeys,
+																						 views: (&[Record], &[Record]),
+																						 heavy_left: &[&Record],
+																						 right_matches: &HashMap<KeyRef, Vec<&Record>>,
+																						 kind: JoinKind,
+																						 join_result: &mut O) {
+	let (left_view, right_view) = views;
+	match kind {
+		// Every heavy key has a match, and semi and anti joins
+		// emit at most one record per left record anyway
+		JoinKind::LeftSemi => {
+			for lr in heavy_left {
+				join_result.left_only(row_id(left_view, lr), lr);
+			}
+			return;
+		},
+		JoinKind::LeftAnti => return,
+		_ => {},
+	}
+
+	let mut left_by_key: HashMap<KeyRef, Vec<&Record>> = HashMap::new();
+	for lr in heavy_left {
+		left_by_key.entry(keys.left_key(lr)).or_default().push(lr);
+	}
+
+	let mut blocks: Vec<(&[&Record], &[&Record])> = Vec::new();
+	for (key, left_records) in left_by_key.iter() {
+		let right_records = match right_matches.get(key) {
+			None => continue,
+			Some(rs) => rs,
+		};
+		for left_block in left_records.chunks(HEAVY_BLOCK_SIZE) {
+			for right_block in right_records.chunks(HEAVY_BLOCK_SIZE) {
+				blocks.push((left_block, right_block));
+			}
+		}
+	}
+
+	let block_results: Vec<O> = blocks.into_par_iter()
+		.map(|(left_block, right_block)| {
+			let mut block_result = join_result.split();
+			for lr in left_block {
+				for rr in right_block {
+					block_result.pair(row_id(left_view, lr), lr, row_id(right_view, rr), rr);
+				}
+			}
+			block_result
+		})
+		.collect();
+	for block_result in block_results {
+		join_result.absorb(block_result);
+	}
+}
@@ -1,76 +1,194 @@
-use std::{clone::Clone, slice::Iter};
+use std::error::Error;
+use std::slice::Iter;
 use std::cmp::min;
+use std::ops::{Deref, DerefMut};
 use rayon::iter::IntoParallelRefIterator;
 use rayon::prelude::ParallelSliceMut;
 
+use crate::arrowtable::{arrow_format, arrow_path_format, read_arrow_table, write_ipc_table, write_parquet_table, ArrowFormat};
+use crate::bintable::{binary_layout, can_map_records, is_binary_path, is_record_path, read_binary_table, write_binary_table, write_record_table, Layout, MappedRecords};
+use crate::key::KeyRef;
 use crate::record::Record;
-use crate::readtable::fetch_records;
+use crate::readtable::{read_csv_table, CsvError, CsvOptions};
+use crate::schema::{Schema, Value};
+
+// Where a table's records live. Both read as a slice of records,
+// so the table's views, iterators and blocks work the same on either
+pub enum RecordStorage {
+  Owned(Vec<Record>),
+  Mapped(MappedRecords),
+}
+
+impl Deref for RecordStorage {
+  type Target = [Record];
+
+  fn deref(&self) -> &[Record] {
+    match self {
+      RecordStorage::Owned(records) => records,
+      RecordStorage::Mapped(records) => records.as_slice(),
+    }
+  }
+}
+
+impl DerefMut for RecordStorage {
+  fn deref_mut(&mut self) -> &mut [Record] {
+    match self {
+      RecordStorage::Owned(records) => records,
+      RecordStorage::Mapped(records) => records.as_mut_slice(),
+    }
+  }
+}
 
 pub struct SimpleTable {
   // The collection of records which
   // make up this table
-  records: Vec<Record>,
+  records: RecordStorage,
 
-  // Number of columns in a record
-  num_columns: usize,
+  // Names and types of the columns
+  schema: Schema,
 
   // Index of the next read
   index: usize,
 }
 
 impl SimpleTable {
+  // Loads a CSV, binary, Arrow IPC or Parquet table, telling them
+  // apart by their magic bytes. Record tables are memory-mapped
+  // where their layout matches Record's
   pub fn new(filepath: &str) -> SimpleTable {
-    // Get raw table contents from on-disk table
-    let raw_table: Vec<Vec<i32>>;
-    match fetch_records(filepath) {
-      Err(e) => panic!("{:?}", e),
-      Ok(fetched_raw_table) => {
-        raw_table = fetched_raw_table;
-      }
+    match SimpleTable::try_new(filepath) {
+      Err(e) => panic!("{}", e),
+      Ok(table) => table,
     }
-    let num_columns = raw_table.get(0).unwrap().len();
+  }
 
-    // Create Record from raw
-    let records = raw_table
-      .iter()
-      .map(|rr| Record::new(rr))
-      .collect();
+  // Same as new, but returns load errors rather than panicking
+  pub fn try_new(filepath: &str) -> Result<SimpleTable, Box<dyn Error>> {
+    let (schema, records) = match binary_layout(filepath) {
+      Some(Layout::Records) if can_map_records() => {
+        let (schema, records) = MappedRecords::open(filepath)?;
+        (schema, RecordStorage::Mapped(records))
+      },
+      Some(_) => {
+        let (schema, records) = read_binary_table(filepath)?;
+        (schema, RecordStorage::Owned(records))
+      },
+      None if arrow_format(filepath).is_some() => {
+        let (schema, records) = read_arrow_table(filepath)?;
+        (schema, RecordStorage::Owned(records))
+      },
+      None => return Ok(SimpleTable::from_csv(filepath, &CsvOptions::default())?),
+    };
+    Ok(SimpleTable {
+      records,
+      schema,
+      index: 0
+    })
+  }
+
+  // Loads a CSV table with the given delimiter, header, columns,
+  // types and mode. Rows skipped in lenient mode are dropped, use
+  // read_csv_table to see them
+  pub fn from_csv(filepath: &str, options: &CsvOptions) -> Result<SimpleTable, CsvError> {
+    let table = read_csv_table(filepath, options)?;
+    Ok(SimpleTable::from_records(table.schema, table.records))
+  }
+
+  pub fn from_binary(filepath: &str) -> SimpleTable {
+    let (schema, records) = match read_binary_table(filepath) {
+      Err(e) => panic!("{:?}", e),
+      Ok(table) => table,
+    };
+    SimpleTable::from_records(schema, records)
+  }
 
+  pub fn from_arrow(filepath: &str) -> SimpleTable {
+    let (schema, records) = match read_arrow_table(filepath) {
+      Err(e) => panic!("{:?}", e),
+      Ok(table) => table,
+    };
+    SimpleTable::from_records(schema, records)
+  }
+
+  // Table of records built in memory, which must
+  // have as many slots as the schema
+  pub fn from_records(schema: Schema, records: Vec<Record>) -> SimpleTable {
+    if let Some(r) = records.iter().find(|r| r.get_num_columns() != schema.num_slots()) {
+      panic!("Record has {:?} columns, but the schema takes {:?}", r.get_num_columns(), schema.num_slots());
+    }
     SimpleTable {
-      records,
-      num_columns,
+      records: RecordStorage::Owned(records),
+      schema,
+      index: 0
+    }
+  }
+
+  // Maps a record table in place rather than reading it,
+  // so that loading takes no time whatever its size
+  pub fn mapped(filepath: &str) -> SimpleTable {
+    let (schema, records) = match MappedRecords::open(filepath) {
+      Err(e) => panic!("{:?}", e),
+      Ok(table) => table,
+    };
+    SimpleTable {
+      records: RecordStorage::Mapped(records),
+      schema,
       index: 0
     }
   }
 
+  pub fn is_mapped(&self) -> bool {
+    matches!(self.records, RecordStorage::Mapped(_))
+  }
+
   // Expensive operation
   pub fn copy_to_vec_of_records(&self) -> Vec<Record> {
-    self.records.clone()
+    self.records.to_vec()
   }
 
   // Expensive operation
-  // Sorts the records by column in parrallel
+  // Sorts the records by record slot in parrallel,
+  // with NULLs last like KeyRef orders them
   pub fn sort_by(&mut self, i: usize) {
     // Unstable sort will be faster for us because
     // stable sorting doesn't matter and we are unlikely
     // to have almost-sorted records or a handful of sorted
     // record subvecs that will be merged
-    self.records.par_sort_unstable_by_key(|r| *r.get_column(i));
+    self.records.par_sort_unstable_by_key(|r| ((r.is_null(i) as i64) << 32) + *r.get_column(i) as i64);
+  }
+
+  // Expensive operation
+  // Sorts the records lexicographically by the
+  // given record slots in parrallel
+  pub fn sort_by_columns(&mut self, columns: &[usize]) {
+    if let [i] = columns {
+      self.sort_by(*i);
+    } else {
+      self.records.par_sort_unstable_by(|a, b| {
+        KeyRef::new(a, columns).cmp(&KeyRef::new(b, columns))
+      });
+    }
   }
 
   pub fn get_num_records(&self) -> usize {
     self.records.len()
   }
 
+  // Record slots, which is the number of columns
+  // unless the schema has wide columns
   pub fn get_num_columns_per_record(&self) -> usize {
-    self.num_columns
+    self.schema.num_slots()
   }
 
-  pub fn record_iterator(&self) -> Iter<Record> {
+  pub fn schema(&self) -> &Schema {
+    &self.schema
+  }
+
+  pub fn record_iterator(&self) -> Iter<'_, Record> {
     self.records.iter()
   }
 
-  pub fn record_par_iterator(&self) -> rayon::slice::Iter<Record> {
+  pub fn record_par_iterator(&self) -> rayon::slice::Iter<'_, Record> {
     self.records.par_iter()
   }
 
@@ -94,4 +212,56 @@ impl SimpleTable {
   pub fn rewind(&mut self) {
     self.index = 0;
   }
-}
\ No newline at end of file
+}
+
+// A table's schema and records, in any format SimpleTable::new reads
+pub fn read_table(filepath: &str) -> Result<(Schema, Vec<Record>), Box<dyn Error>> {
+  if binary_layout(filepath).is_some() {
+    return read_binary_table(filepath);
+  }
+  if arrow_format(filepath).is_some() {
+    return read_arrow_table(filepath);
+  }
+  let table = read_csv_table(filepath, &CsvOptions::default())?;
+  Ok((table.schema, table.records))
+}
+
+// Writes records in the format of the path's extension: .bin,
+// .rec, .arrow or .parquet, and CSV otherwise. CSV leaves NULLs
+// empty and does not keep the types, see CsvOptions::types
+pub fn write_table(filepath: &str, schema: &Schema, records: &[Record]) -> Result<(), Box<dyn Error>> {
+  if is_binary_path(filepath) {
+    return write_binary_table(filepath, schema, records);
+  }
+  if is_record_path(filepath) {
+    return write_record_table(filepath, schema, records);
+  }
+  match arrow_path_format(filepath) {
+    Some(ArrowFormat::Ipc) => return write_ipc_table(filepath, schema, records),
+    Some(ArrowFormat::Parquet) => return write_parquet_table(filepath, schema, records),
+    None => (),
+  }
+
+  write_csv_table(filepath, schema, records.iter().map(|r| (0..schema.num_columns()).map(move |i| r.get_value(schema, i))))
+}
+
+// Writes rows of values as CSV, leaving NULLs empty
+pub(crate) fn write_csv_table<R, V>(filepath: &str, schema: &Schema, rows: R) -> Result<(), Box<dyn Error>>
+where
+  R: Iterator<Item = V>,
+  V: Iterator<Item = Value>,
+{
+  let mut writer = csv::Writer::from_path(filepath)?;
+  writer.write_record(schema.column_names())?;
+  let mut fields: Vec<String> = Vec::with_capacity(schema.num_columns());
+  for row in rows {
+    fields.clear();
+    fields.extend(row.map(|v| match v {
+      Value::Null => String::new(),
+      v => v.to_string(),
+    }));
+    writer.write_record(&fields)?;
+  }
+  writer.flush()?;
+  Ok(())
+}
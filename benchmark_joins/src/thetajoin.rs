@@ -0,0 +1,443 @@
+use std::ops::Range;
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use crate::join::{join_batches, record_widths, BlockNL, JoinAlgos, JoinOperator, JoinOutput, NestedLoopsJoin, RecordOutput, RecordSink};
+use crate::parjoin::CHUNK_SIZE;
+use crate::record::Record;
+use crate::schema::{ColumnType, Schema};
+use crate::table::SimpleTable;
+
+// Comparison between a left and a right column value
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThetaOp {
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+impl ThetaOp {
+	pub fn holds(&self, left_value: i32, right_value: i32) -> bool {
+		match self {
+			ThetaOp::Lt => left_value < right_value,
+			ThetaOp::Le => left_value <= right_value,
+			ThetaOp::Gt => left_value > right_value,
+			ThetaOp::Ge => left_value >= right_value,
+		}
+	}
+}
+
+// left[left_col] op right[right_col]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inequality {
+	pub left_col: usize,
+	pub op: ThetaOp,
+	pub right_col: usize,
+}
+
+impl Inequality {
+	// A comparison with NULL is never true
+	pub fn matches(&self, left: &Record, right: &Record) -> bool {
+		match (left.get_nullable_column(self.left_col), right.get_nullable_column(self.right_col)) {
+			(Some(l), Some(r)) => self.op.holds(*l, *r),
+			_ => false,
+		}
+	}
+}
+
+// Non-equality join conditions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ThetaPredicate {
+	// |left[left_col] - right[right_col]| <= width
+	Band {
+		left_col: usize,
+		right_col: usize,
+		width: u32,
+	},
+	// Conjunction of one or two inequalities
+	Inequalities(Vec<Inequality>),
+}
+
+impl ThetaPredicate {
+	// Predicate on the record slots of the tables' columns, which is
+	// what the operators compare, see JoinKeys::resolve. Slots compare
+	// as i32s, so every column of the predicate must be an Int32
+	pub fn resolve(&self, left: &Schema, right: &Schema) -> Result<ThetaPredicate, String> {
+		let slot = |schema: &Schema, side: &str, column: usize| -> Result<usize, String> {
+			if column >= schema.num_columns() {
+				return Err(format!("The {} table has {} columns, no column {}", side, schema.num_columns(), column));
+			}
+			match schema.column_type(column) {
+				ColumnType::Int32 => Ok(schema.slots(column).start),
+				t => Err(format!("Theta joins compare i32 columns, but {} column {} is {}", side, column, t)),
+			}
+		};
+		Ok(match self {
+			ThetaPredicate::Band { left_col, right_col, width } => ThetaPredicate::Band {
+				left_col: slot(left, "left", *left_col)?,
+				right_col: slot(right, "right", *right_col)?,
+				width: *width,
+			},
+			ThetaPredicate::Inequalities(inequalities) => ThetaPredicate::Inequalities(inequalities.iter()
+				.map(|i| Ok(Inequality {
+					left_col: slot(left, "left", i.left_col)?,
+					op: i.op,
+					right_col: slot(right, "right", i.right_col)?,
+				}))
+				.collect::<Result<Vec<Inequality>, String>>()?),
+		})
+	}
+
+	// Records whose predicate columns are NULL match nothing
+	pub fn matches(&self, left: &Record, right: &Record) -> bool {
+		match self {
+			ThetaPredicate::Band { left_col, right_col, width } => {
+				match (left.get_nullable_column(*left_col), right.get_nullable_column(*right_col)) {
+					(Some(l), Some(r)) => (*l as i64 - *r as i64).abs() <= *width as i64,
+					_ => false,
+				}
+			},
+			ThetaPredicate::Inequalities(inequalities) => {
+				inequalities.iter().all(|i| i.matches(left, right))
+			},
+		}
+	}
+}
+
+// Parses the command line form of a predicate. Bands are written
+// as left:right~width, e.g. 5:5~100, and inequalities as one or two
+// comparisons separated by , e.g. 5<5 or 5>=5,6<6
+impl FromStr for ThetaPredicate {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let parse_col = |c: &str| -> Result<usize, String> {
+			c.trim().parse().map_err(|_| format!("Bad column {:?} in {:?}", c, s))
+		};
+
+		if let Some((cols, width)) = s.split_once('~') {
+			let (l, r) = cols.split_once(':').ok_or(format!("Expected left:right~width, got {:?}", s))?;
+			let width = width.trim().parse().map_err(|_| format!("Bad band width {:?}", width))?;
+			return Ok(ThetaPredicate::Band {
+				left_col: parse_col(l)?,
+				right_col: parse_col(r)?,
+				width,
+			});
+		}
+
+		let mut inequalities = Vec::new();
+		for raw in s.split(',') {
+			// Check two character operators first
+			let (symbol, op) = [("<=", ThetaOp::Le), (">=", ThetaOp::Ge), ("<", ThetaOp::Lt), (">", ThetaOp::Gt)]
+				.iter()
+				.find(|(symbol, _)| raw.contains(symbol))
+				.ok_or(format!("No comparison in {:?}", raw))?;
+			let (l, r) = raw.split_once(symbol).unwrap();
+			inequalities.push(Inequality {
+				left_col: parse_col(l)?,
+				op: *op,
+				right_col: parse_col(r)?,
+			});
+		}
+		if inequalities.len() > 2 {
+			return Err(format!("At most two inequalities are supported, got {:?}", s));
+		}
+		Ok(ThetaPredicate::Inequalities(inequalities))
+	}
+}
+
+// Common interface of joins on non-equality predicates, whose columns
+// are resolved against the tables' schemas, panicking unless they are
+// Int32 columns. It comes with ThetaJoinInto, which is what operators
+// implement
+pub trait ThetaJoin: JoinOperator {
+	// Join every left and right record satisfying the predicate
+	fn theta_join(&mut self, predicate: &ThetaPredicate) -> Vec<Record>;
+
+	// Same join, handing the records to sink in batches, see
+	// EquiJoin::equi_join_batches
+	fn theta_join_batches(&mut self, predicate: &ThetaPredicate, batch_size: usize, sink: &mut RecordSink);
+}
+
+// Theta join operators which hand each output row to a JoinOutput
+// as they find it, see JoinInto
+pub trait ThetaJoinInto: JoinOperator {
+	// Join every left and right record satisfying the predicate,
+	// whose columns are record slots, see ThetaPredicate::resolve
+	fn theta_join_into<O: JoinOutput>(&mut self, predicate: &ThetaPredicate, join_result: &mut O);
+}
+
+// The predicate on the slots of the operator's tables
+fn resolved<J: JoinOperator + ?Sized>(join: &J, predicate: &ThetaPredicate) -> ThetaPredicate {
+	let (left, right) = join.tables();
+	match predicate.resolve(left.schema(), right.schema()) {
+		Err(e) => panic!("{}", e),
+		Ok(predicate) => predicate,
+	}
+}
+
+impl<J: ThetaJoinInto> ThetaJoin for J {
+	fn theta_join(&mut self, predicate: &ThetaPredicate) -> Vec<Record> {
+		let predicate = resolved(self, predicate);
+		let (left_width, right_width) = record_widths(self);
+		let mut join_result = RecordOutput::new(left_width, right_width);
+		self.theta_join_into(&predicate, &mut join_result);
+		join_result.into_records()
+	}
+
+	fn theta_join_batches(&mut self, predicate: &ThetaPredicate, batch_size: usize, sink: &mut RecordSink) {
+		let predicate = resolved(self, predicate);
+		join_batches(record_widths(self), batch_size, sink, |join_result| self.theta_join_into(&predicate, join_result));
+	}
+}
+
+// Nested loops evaluate any predicate, and serve as the fallback
+impl<'a> ThetaJoinInto for NestedLoopsJoin<'a> {
+	fn theta_join_into<O: JoinOutput>(&mut self, predicate: &ThetaPredicate, join_result: &mut O) {
+		self.join_where(|l, r| predicate.matches(l, r), join_result);
+	}
+}
+
+impl<'a> ThetaJoinInto for BlockNL<'a> {
+	fn theta_join_into<O: JoinOutput>(&mut self, predicate: &ThetaPredicate, join_result: &mut O) {
+		self.join_where(|l, r| predicate.matches(l, r), join_result);
+	}
+}
+
+// Positions of the records which are not NULL in any of the slots,
+// the only ones which can satisfy a predicate on them
+fn non_null_rows(records: &[Record], slots: &[usize]) -> Vec<usize> {
+	(0..records.len())
+		.filter(|i| slots.iter().all(|s| !records[*i].is_null(*s)))
+		.collect()
+}
+
+// Positions of the sorted values for which `left_value op value`
+// holds. Since values are sorted, these always form a prefix or
+// a suffix, so they can be found with a binary search
+fn satisfying_range(sorted_values: &[i32], left_value: i32, op: ThetaOp) -> Range<usize> {
+	let n = sorted_values.len();
+	match op {
+		ThetaOp::Lt => sorted_values.partition_point(|v| *v <= left_value)..n,
+		ThetaOp::Le => sorted_values.partition_point(|v| *v < left_value)..n,
+		ThetaOp::Gt => 0..sorted_values.partition_point(|v| *v < left_value),
+		ThetaOp::Ge => 0..sorted_values.partition_point(|v| *v <= left_value),
+	}
+}
+
+// Band join which sorts the right table on its join column, then
+// for each left record binary searches for the start of its band
+// and scans forward until the band ends.
+// Will mutate the right table by sorting in place
+pub struct SortBandJoin<'a> {
+	left: &'a mut SimpleTable,
+	right: &'a mut SimpleTable,
+}
+
+impl<'a> SortBandJoin<'a> {
+
+	pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+		Self {
+			left,
+			right
+		}
+	}
+}
+
+impl<'a> JoinOperator for SortBandJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::BandJoin
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> ThetaJoinInto for SortBandJoin<'a> {
+	// Row ids of the right table index it as sorted by the join
+	fn theta_join_into<O: JoinOutput>(&mut self, predicate: &ThetaPredicate, join_result: &mut O) {
+		let (left_col, right_col, width) = match predicate {
+			ThetaPredicate::Band { left_col, right_col, width } => (*left_col, *right_col, *width as i64),
+			_ => panic!("SortBandJoin only supports band predicates, got {:?}", predicate),
+		};
+
+		// NULLs sort last, where they would break the binary search
+		self.right.sort_by(right_col);
+		let right_records = self.right.record_view();
+		let right_records = &right_records[..right_records.partition_point(|rr| !rr.is_null(right_col))];
+
+		// Each chunk of left records fills its own output
+		let intermediate_join_results: Vec<O> = self.left.record_par_iterator()
+			.enumerate()
+			.chunks(CHUNK_SIZE)
+			.map(|left_records| {
+				let mut intermediate_join_result = join_result.split();
+				for (l, lr) in left_records.into_iter().filter(|(_, lr)| !lr.is_null(left_col)) {
+					// Band is [value - width, value + width], computed
+					// in i64 so it cannot overflow at the i32 extremes
+					let value = *lr.get_column(left_col) as i64;
+					let start = right_records.partition_point(|rr| (*rr.get_column(right_col) as i64) < value - width);
+					for (r, rr) in right_records.iter().enumerate().skip(start) {
+						if *rr.get_column(right_col) as i64 > value + width {
+							break;
+						}
+						intermediate_join_result.pair(l as u32, lr, r as u32, rr);
+					}
+				}
+				intermediate_join_result
+			})
+			.collect();
+
+		for intermediate_join_result in intermediate_join_results {
+			join_result.absorb(intermediate_join_result);
+		}
+	}
+}
+
+// Inequality join in the style of IEJoin (Khayyat et al.).
+// For one inequality, the matches of each left record are a
+// contiguous range of the right table sorted on its column.
+// For two inequalities, left records are visited in the order
+// in which the right records satisfying the second inequality
+// only grow. Those are marked in a bit array laid out in the
+// order of the first inequality's column, so the matches are
+// the marked bits within the range of the first inequality
+pub struct IEJoin<'a> {
+	left: &'a mut SimpleTable,
+	right: &'a mut SimpleTable,
+}
+
+impl<'a> IEJoin<'a> {
+
+	pub fn new(left: &'a mut SimpleTable, right: &'a mut SimpleTable) -> Self {
+		Self {
+			left,
+			right
+		}
+	}
+
+	fn single_inequality<O: JoinOutput>(&self, ineq: &Inequality, join_result: &mut O) {
+		let right_records = self.right.record_view();
+
+		// Right records ordered by their join column, leaving out NULLs
+		let mut order: Vec<usize> = non_null_rows(right_records, &[ineq.right_col]);
+		order.sort_unstable_by_key(|i| *right_records[*i].get_column(ineq.right_col));
+		let sorted_values: Vec<i32> = order.iter().map(|i| *right_records[*i].get_column(ineq.right_col)).collect();
+
+		// Each chunk of left records fills its own output
+		let intermediate_join_results: Vec<O> = self.left.record_par_iterator()
+			.enumerate()
+			.chunks(CHUNK_SIZE)
+			.map(|left_records| {
+				let mut intermediate_join_result = join_result.split();
+				for (l, lr) in left_records.into_iter().filter(|(_, lr)| !lr.is_null(ineq.left_col)) {
+					let range = satisfying_range(&sorted_values, *lr.get_column(ineq.left_col), ineq.op);
+					for i in &order[range] {
+						intermediate_join_result.pair(l as u32, lr, *i as u32, &right_records[*i]);
+					}
+				}
+				intermediate_join_result
+			})
+			.collect();
+
+		for intermediate_join_result in intermediate_join_results {
+			join_result.absorb(intermediate_join_result);
+		}
+	}
+
+	fn two_inequalities<O: JoinOutput>(&self, first: &Inequality, second: &Inequality, join_result: &mut O) {
+		let left_records = self.left.record_view();
+		let right_records = self.right.record_view();
+
+		// Records which are NULL in either column satisfy neither
+		// inequality, so they are left out of every order
+		let right_rows = non_null_rows(right_records, &[first.right_col, second.right_col]);
+		let right_size = right_rows.len();
+
+		// Right records ordered by the first inequality's column,
+		// along with where each right record lands in that order
+		let mut x_order: Vec<usize> = right_rows.clone();
+		x_order.sort_unstable_by_key(|i| *right_records[*i].get_column(first.right_col));
+		let x_values: Vec<i32> = x_order.iter().map(|i| *right_records[*i].get_column(first.right_col)).collect();
+		let mut x_position = vec![0; right_records.len()];
+		for (p, i) in x_order.iter().enumerate() {
+			x_position[*i] = p;
+		}
+
+		// Both sides ordered by the second inequality's columns, in the
+		// direction where each next left record is satisfied by a superset
+		// of the right records satisfying the previous one
+		let mut y_order: Vec<usize> = right_rows;
+		y_order.sort_unstable_by_key(|i| *right_records[*i].get_column(second.right_col));
+		let mut left_order: Vec<usize> = non_null_rows(left_records, &[first.left_col, second.left_col]);
+		left_order.sort_unstable_by_key(|i| *left_records[*i].get_column(second.left_col));
+		if matches!(second.op, ThetaOp::Lt | ThetaOp::Le) {
+			y_order.reverse();
+			left_order.reverse();
+		}
+
+		// Bit p is set once right record x_order[p] satisfies the second inequality
+		let mut marked: Vec<u64> = vec![0; right_size.div_ceil(64)];
+		let mut next = 0;
+
+		for l in left_order {
+			let lr = &left_records[l];
+
+			// Mark the right records which now satisfy the second inequality
+			let y_value = *lr.get_column(second.left_col);
+			while next < right_size && second.op.holds(y_value, *right_records[y_order[next]].get_column(second.right_col)) {
+				let p = x_position[y_order[next]];
+				marked[p / 64] |= 1 << (p % 64);
+				next += 1;
+			}
+
+			// Emit the marked records within the range of the first inequality
+			let range = satisfying_range(&x_values, *lr.get_column(first.left_col), first.op);
+			if range.is_empty() {
+				continue;
+			}
+			let first_word = range.start / 64;
+			let last_word = (range.end - 1) / 64;
+			for (w, word) in marked.iter().enumerate().take(last_word + 1).skip(first_word) {
+				let mut word = *word;
+				if w == first_word {
+					word &= u64::MAX << (range.start % 64);
+				}
+				if w == last_word {
+					word &= u64::MAX >> (63 - (range.end - 1) % 64);
+				}
+				while word != 0 {
+					let p = w * 64 + word.trailing_zeros() as usize;
+					join_result.pair(l as u32, lr, x_order[p] as u32, &right_records[x_order[p]]);
+					word &= word - 1;
+				}
+			}
+		}
+	}
+}
+
+impl<'a> JoinOperator for IEJoin<'a> {
+	fn name(&self) -> JoinAlgos {
+		JoinAlgos::IEJoin
+	}
+
+	fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+		(self.left, self.right)
+	}
+}
+
+impl<'a> ThetaJoinInto for IEJoin<'a> {
+	fn theta_join_into<O: JoinOutput>(&mut self, predicate: &ThetaPredicate, join_result: &mut O) {
+		match predicate {
+			ThetaPredicate::Inequalities(inequalities) => match inequalities.as_slice() {
+				[ineq] => self.single_inequality(ineq, join_result),
+				[first, second] => self.two_inequalities(first, second, join_result),
+				_ => panic!("IEJoin supports one or two inequalities, got {:?}", inequalities),
+			},
+			_ => panic!("IEJoin only supports inequality predicates, got {:?}", predicate),
+		}
+	}
+}
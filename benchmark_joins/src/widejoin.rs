@@ -0,0 +1,85 @@
+use crate::join::{JoinAlgos, JoinKind, JoinParams};
+use crate::key::JoinKeys;
+use crate::record::{Record, M};
+use crate::registry::JoinRegistry;
+use crate::rows::Rows;
+use crate::schema::Schema;
+use crate::table::SimpleTable;
+use crate::widetable::WideTable;
+
+// Joins tables of any width with any registered operator. Each side
+// is projected to narrow records of its key slots and its row id,
+// the operator joins those as usual, and the output rows are gathered
+// from the row ids it emits. So the operators only ever see records,
+// and the copy of the wide rows is paid once per output row
+pub fn wide_join(
+	registry: &JoinRegistry,
+	algo: &JoinAlgos,
+	params: &JoinParams,
+	left: &WideTable,
+	right: &WideTable,
+	keys: &JoinKeys) -> (WideTable, u64) {
+	let keys = keys.resolve(left.schema(), right.schema());
+	let k = keys.num_columns();
+	if 2 * (k + 1) > M {
+		panic!("Wide joins support keys of up to {:?} slots, got {:?}", M / 2 - 1, k);
+	}
+
+	// Join the narrow tables on the key slots, which come first
+	let mut narrow_left = narrow_table(left.rows(), keys.left_columns());
+	let mut narrow_right = narrow_table(right.rows(), keys.right_columns());
+	let narrow_keys = JoinKeys::new(&(0..k).map(|i| (i, i)).collect::<Vec<_>>());
+	let mut join = registry.create(algo, &mut narrow_left, &mut narrow_right, params);
+	let matches: Vec<Record> = join.equi_join_on(&narrow_keys);
+	let bytes_spilled = join.bytes_spilled();
+
+	// Gather the rows of the emitted row ids. A padded side's row id is NULL
+	let row_id = |r: &Record, i: usize| r.get_nullable_column(i).map(|id| *id as u32 as usize);
+	let (schema, rows) = match params.join_kind {
+		JoinKind::LeftSemi | JoinKind::LeftAnti => {
+			let mut rows = Rows::with_capacity(left.rows().width(), matches.len());
+			for m in matches.iter() {
+				rows.push_merged(row_id(m, k).map(|i| left.rows().get(i)), left.rows().width(), None);
+			}
+			(left.schema().clone(), rows)
+		},
+		_ => {
+			let width = left.rows().width() + right.rows().width();
+			let mut rows = Rows::with_capacity(width, matches.len());
+			for m in matches.iter() {
+				rows.push_merged(
+					row_id(m, k).map(|i| left.rows().get(i)),
+					left.rows().width(),
+					row_id(m, 2 * k + 1).map(|i| right.rows().get(i)));
+			}
+			(left.schema().merge(right.schema()), rows)
+		},
+	};
+	(WideTable::from_rows(schema, rows), bytes_spilled)
+}
+
+// Records of the key slots of each row followed by its row id
+fn narrow_table(rows: &Rows, key_slots: &[usize]) -> SimpleTable {
+	if rows.len() > u32::MAX as usize {
+		panic!("Wide joins support up to {:?} rows, got {:?}", u32::MAX, rows.len());
+	}
+	let names: Vec<String> = (1..=key_slots.len() + 1).map(|c| format!("col{}", c)).collect();
+	let mut fields: Vec<i32> = vec![0; key_slots.len() + 1];
+	let records: Vec<Record> = rows.iter()
+		.enumerate()
+		.map(|(id, row)| {
+			for (field, &slot) in fields.iter_mut().zip(key_slots) {
+				*field = *row.get_column(slot);
+			}
+			fields[key_slots.len()] = id as u32 as i32;
+			let mut record = Record::new(&fields);
+			for (i, &slot) in key_slots.iter().enumerate() {
+				if row.is_null(slot) {
+					record.set_null(i);
+				}
+			}
+			record
+		})
+		.collect();
+	SimpleTable::from_records(Schema::int32(&names), records)
+}
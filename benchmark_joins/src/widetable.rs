@@ -0,0 +1,101 @@
+use std::error::Error;
+
+use crate::arrowtable::{arrow_format, arrow_path_format};
+use crate::bintable::{binary_layout, is_binary_path, is_record_path};
+use crate::readtable::{read_csv_rows, CsvError, CsvOptions};
+use crate::rows::{Row, Rows};
+use crate::schema::Schema;
+use crate::table::{read_table, write_csv_table, write_table, SimpleTable};
+
+// A table of any number of columns, whose rows live in a Rows arena
+// rather than in Records. SimpleTable stays the fast path the join
+// operators run on; wide tables are joined through it, see widejoin
+pub struct WideTable {
+  schema: Schema,
+  rows: Rows,
+}
+
+impl WideTable {
+  // Loads a table in any format SimpleTable::new reads. Only CSV
+  // tables may be wider than a record
+  pub fn new(filepath: &str) -> WideTable {
+    match WideTable::try_new(filepath) {
+      Err(e) => panic!("{}", e),
+      Ok(table) => table,
+    }
+  }
+
+  // Same as new, but returns load errors rather than panicking
+  pub fn try_new(filepath: &str) -> Result<WideTable, Box<dyn Error>> {
+    if binary_layout(filepath).is_none() && arrow_format(filepath).is_none() {
+      return Ok(WideTable::from_csv(filepath, &CsvOptions::default())?);
+    }
+    let (schema, records) = read_table(filepath)?;
+    let rows = Rows::from_records(schema.num_slots(), &records);
+    Ok(WideTable { schema, rows })
+  }
+
+  // Loads a CSV table of any width, see read_csv_table
+  pub fn from_csv(filepath: &str, options: &CsvOptions) -> Result<WideTable, CsvError> {
+    let table = read_csv_rows(filepath, options)?;
+    Ok(WideTable::from_rows(table.schema, table.rows))
+  }
+
+  // Table of rows built in memory, which must
+  // have as many slots as the schema
+  pub fn from_rows(schema: Schema, rows: Rows) -> WideTable {
+    if rows.width() != schema.num_slots() {
+      panic!("Rows have {:?} columns, but the schema takes {:?}", rows.width(), schema.num_slots());
+    }
+    WideTable { schema, rows }
+  }
+
+  // Copies the records of a table
+  pub fn from_table(table: &SimpleTable) -> WideTable {
+    let rows = Rows::from_records(table.get_num_columns_per_record(), table.record_view());
+    WideTable::from_rows(table.schema().clone(), rows)
+  }
+
+  // Copies the rows into records, or None
+  // when they are too wide for a record
+  pub fn to_simple_table(&self) -> Option<SimpleTable> {
+    if !self.schema.fits_record() {
+      return None;
+    }
+    Some(SimpleTable::from_records(self.schema.clone(), self.rows.to_records()))
+  }
+
+  pub fn get_num_records(&self) -> usize {
+    self.rows.len()
+  }
+
+  // Row slots, which is the number of columns
+  // unless the schema has wide columns
+  pub fn get_num_columns_per_record(&self) -> usize {
+    self.rows.width()
+  }
+
+  pub fn schema(&self) -> &Schema {
+    &self.schema
+  }
+
+  pub fn rows(&self) -> &Rows {
+    &self.rows
+  }
+
+  pub fn row_iterator(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+    self.rows.iter()
+  }
+}
+
+// Writes rows like write_table does records. Rows which fit in a
+// record may go to any format, wider ones only to CSV
+pub fn write_wide_table(filepath: &str, schema: &Schema, rows: &Rows) -> Result<(), Box<dyn Error>> {
+  if schema.fits_record() {
+    return write_table(filepath, schema, &rows.to_records());
+  }
+  if is_binary_path(filepath) || is_record_path(filepath) || arrow_path_format(filepath).is_some() {
+    return Err(format!("Table has {} columns, too wide for {:?}, write it as CSV", schema.num_columns(), filepath).into());
+  }
+  write_csv_table(filepath, schema, rows.iter().map(|r| (0..schema.num_columns()).map(move |i| r.get_value(schema, i))))
+}
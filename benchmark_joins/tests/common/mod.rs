@@ -0,0 +1,51 @@
+#![allow(dead_code)]
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// A directory of a test's own, removed along with its files when
+// dropped, even when the test fails. Tests of a file run in parallel,
+// so each picks a name no other test uses
+pub struct TempDir {
+  path: PathBuf,
+}
+
+impl TempDir {
+  pub fn new(name: &str) -> Self {
+    let path = std::env::temp_dir().join(format!("{}_test_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    TempDir { path }
+  }
+
+  // Empties the directory, for a test to write its files again
+  pub fn clear(&self) {
+    std::fs::remove_dir_all(&self.path).unwrap();
+    std::fs::create_dir_all(&self.path).unwrap();
+  }
+
+  // Path of a file in the directory
+  pub fn file(&self, name: &str) -> String {
+    self.path.join(name).to_string_lossy().into_owned()
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl AsRef<Path> for TempDir {
+  fn as_ref(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.path);
+  }
+}
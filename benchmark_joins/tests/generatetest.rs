@@ -0,0 +1,146 @@
+extern crate joinlib;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::TempDir;
+  use joinlib::join::{JoinAlgos, JoinParams};
+  use joinlib::key::JoinKeys;
+  use joinlib::registry::JoinRegistry;
+  use joinlib::table::SimpleTable;
+  use std::collections::BTreeMap;
+  use std::fs;
+  use std::path::{Path, PathBuf};
+  use std::process::Command;
+
+  // Runs the generator, which fails when a right table
+  // it reads back does not join to the planned size
+  fn generate(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_generate")).args(args).output().unwrap();
+    assert!(output.status.success(), "generate {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+  }
+
+  // Contents of every file under dir, by path
+  fn read_files(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut files: BTreeMap<PathBuf, Vec<u8>> = BTreeMap::new();
+    for entry in fs::read_dir(dir).unwrap() {
+      let path = entry.unwrap().path();
+      if path.is_dir() {
+        files.extend(read_files(&path));
+      } else {
+        files.insert(path.clone(), fs::read(&path).unwrap());
+      }
+    }
+    files
+  }
+
+  fn tables_with_extension(files: &BTreeMap<PathBuf, Vec<u8>>, extension: &str) -> Vec<String> {
+    files.keys()
+      .filter(|p| p.extension().is_some_and(|e| e == extension))
+      .map(|p| p.to_string_lossy().into_owned())
+      .collect()
+  }
+
+  fn manifest(table: &str) -> serde_json::Value {
+    let path = Path::new(table).with_extension("manifest.json");
+    serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+  }
+
+  // Output rows of the join of two written tables on column 5,
+  // counted by a hash join rather than the generator
+  fn join_size(left: &str, right: &str) -> usize {
+    let (mut left, mut right) = (SimpleTable::new(left), SimpleTable::new(right));
+    let mut join = JoinRegistry::new().create(&JoinAlgos::SimpleHashJoin, &mut left, &mut right, &JoinParams::default());
+    join.count_on(&JoinKeys::single(5, 5))
+  }
+
+  #[test]
+  fn test_same_seed_same_tables() {
+    for streaming in [false, true] {
+      let dir = TempDir::new("generate_seed");
+      let output = format!("--output={}", dir.to_string_lossy());
+      let mut args = vec!["experiment", "--name=seeded", "--left-rows=2K", "--right-rows=1K,3K", "--seed=42", output.as_str()];
+      if streaming {
+        args.push("--streaming");
+      }
+
+      // Each run writes to the same paths, so that
+      // the manifests' arguments are the same too
+      generate(&args);
+      let first = read_files(&dir);
+      dir.clear();
+      generate(&args);
+      let second = read_files(&dir);
+      assert_eq!(tables_with_extension(&first, "csv").len(), 3);
+      assert_eq!(first.keys().filter(|p| p.to_string_lossy().ends_with(".manifest.json")).count(), 3);
+      assert_eq!(first.keys().collect::<Vec<_>>(), second.keys().collect::<Vec<_>>());
+      for (path, bytes) in first.iter() {
+        assert!(bytes == &second[path], "{:?} differs between runs with the same seed", path);
+      }
+
+      // Another seed gives other tables
+      let mut reseeded = args.clone();
+      reseeded[4] = "--seed=43";
+      dir.clear();
+      generate(&reseeded);
+      let third = read_files(&dir);
+      assert!(tables_with_extension(&first, "csv").iter().any(|t| first[Path::new(t)] != third[Path::new(t)]));
+    }
+  }
+
+  #[test]
+  fn test_hot_key_keeps_selectivity() {
+    let dir = TempDir::new("generate_hot");
+    let left = dir.file("left.csv");
+    let right = dir.file("right.csv");
+    generate(&["left", "--rows=10K", "--seed=3", &format!("--output={}", left)]);
+    generate(&["right", &format!("--left={}", left), "--rows=10K", "--selectivity=20", "--hot-key=50", "--seed=4", &format!("--output={}", right)]);
+
+    // The other matched keys keep a row each next to the hot key
+    let join = &manifest(&right)["join"];
+    assert_eq!(join["matched_left_rows"].as_u64().unwrap(), 2000);
+    let matched_right_rows = join["matched_right_rows"].as_u64().unwrap();
+    assert!(matched_right_rows >= 2 * 1999);
+    assert_eq!(join_size(&left, &right), join["output_rows"].as_u64().unwrap() as usize);
+  }
+
+  #[test]
+  fn test_streamed_tables_round_trip() {
+    for format in ["csv", "binary", "records"] {
+      let dir = TempDir::new(&format!("generate_{}", format));
+      let output = format!("--output={}", dir.to_string_lossy());
+      let format_arg = format!("--format={}", format);
+      generate(&["experiment", "--name=streamed", "--left-rows=4K", "--right-rows=2K,4K", "--streaming", "--seed=7", &format_arg, &output]);
+
+      let files = read_files(&dir);
+      let extension = match format {
+        "csv" => "csv",
+        "binary" => "bin",
+        _ => "rec",
+      };
+      let tables = tables_with_extension(&files, extension);
+      let (lefts, rights): (Vec<&String>, Vec<&String>) = tables.iter().partition(|t| manifest(t)["join"].is_null());
+      assert_eq!((lefts.len(), rights.len()), (1, 2));
+      let left = lefts[0];
+      assert!(!manifest(left)["key_space"].is_null());
+
+      // The join sizes the generator read back match a real join
+      for right in rights.iter() {
+        let output_rows = manifest(right)["join"]["output_rows"].as_u64().unwrap() as usize;
+        assert!(output_rows > 0);
+        assert_eq!(join_size(left, right), output_rows);
+      }
+
+      // A right table made later recomputes the left keys
+      // from the left table's manifest
+      let later = dir.file(&format!("later.{}", extension));
+      let left_arg = format!("--left={}", left);
+      let later_arg = format!("--output={}", later);
+      generate(&["right", &left_arg, "--rows=3K", "--selectivity=50", "--seed=9", &later_arg]);
+      let output_rows = manifest(&later)["join"]["output_rows"].as_u64().unwrap() as usize;
+      assert_eq!(output_rows, 2000);
+      assert_eq!(join_size(left, &later), output_rows);
+    }
+  }
+}
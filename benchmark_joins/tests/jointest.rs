@@ -1,16 +1,35 @@
 extern crate joinlib;
 
+mod common;
+
 #[cfg(test)]
 mod tests {
+  use crate::common::TempDir;
+  use joinlib::aggregate::{Aggregate, AggregateFunction};
+  use joinlib::bintable::{default_column_names, read_binary_table, write_binary_table, write_record_table, BinaryTableWriter};
+  use joinlib::columnartable::ColumnarTable;
+  use joinlib::gracejoin::GraceHashJoin;
   use joinlib::join::*;
-  use joinlib::radixjoin::*;
-  use joinlib::parjoin::*;
+  use joinlib::key::JoinKeys;
+  use joinlib::materialize::{distinct_left, materialize, Projection};
+  use joinlib::readtable::CsvOptions;
   use joinlib::record::Record;
-  use joinlib::table::SimpleTable;
+  use joinlib::registry::JoinRegistry;
+  use joinlib::runner::{JoinCondition, OutputMode, RunOptions};
+  use joinlib::schema::{Column, ColumnType, Schema, Value};
+  use joinlib::table::{read_table, write_table, SimpleTable};
+  use joinlib::thetajoin::*;
+  use joinlib::widejoin::wide_join;
+  use joinlib::widetable::{write_wide_table, WideTable};
+  use std::sync::atomic::{AtomicUsize, Ordering};
   const SMALL1: &str = "tables/small1.csv";
   const SMALL2: &str = "tables/small2.csv";
   const MED1: &str = "tables/med1.csv";
   const MED2: &str = "tables/med2.csv";
+  const COMPOSITE1: &str = "tables/composite1.csv";
+  const COMPOSITE2: &str = "tables/composite2.csv";
+  const SKEW1: &str = "tables/skew1.csv";
+  const SKEW2: &str = "tables/skew2.csv";
 
   fn nl_result(file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
     let table1 = &mut SimpleTable::new(file1);
@@ -31,42 +50,232 @@ mod tests {
     bnl.equi_join(col1, col2)
   }
 
+  fn files(file1: &str, file2: &str) -> (SimpleTable, SimpleTable) {
+    (SimpleTable::new(file1), SimpleTable::new(file2))
+  }
+
+  // Params of the operators under test, with blocks, partitions and a
+  // memory budget small enough for the test tables to span several
+  fn test_params(join_kind: JoinKind) -> JoinParams {
+    JoinParams {
+      join_kind,
+      left_block_size: 2,
+      right_block_size: 3,
+      memory_budget_bytes: 200,
+      radix_bits: vec![2, 1],
+      ..JoinParams::default()
+    }
+  }
+
+  // Join on keys by the operator registered for algo
+  fn join_result(algo: &JoinAlgos, (mut left, mut right): (SimpleTable, SimpleTable), keys: &JoinKeys, params: &JoinParams) -> Vec<Record> {
+    let mut join = JoinRegistry::new().create(algo, &mut left, &mut right, params);
+    assert_eq!(join.name(), *algo);
+    assert_eq!(join.parameters().join_kind, params.join_kind);
+    join.equi_join_on(keys)
+  }
+
+  fn registry_result(algo: JoinAlgos, file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
+    join_result(&algo, files(file1, file2), &JoinKeys::single(col1, col2), &JoinParams::default())
+  }
+
   fn simplehash_result(file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
-    let table1 = &mut SimpleTable::new(file1);
-    let table2 = &mut SimpleTable::new(file2);
-    let mut simplehash = SimpleHashJoin::new(table1, table2);
-    simplehash.equi_join(col1, col2)
+    registry_result(JoinAlgos::SimpleHashJoin, file1, file2, col1, col2)
   }
 
   fn radix_result(file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
-    let table1 = &mut SimpleTable::new(file1);
-    let table2 = &mut SimpleTable::new(file2);
-    let mut radix = RadixJoin::new(table1, table2);
-    radix.equi_join(col1, col2)
+    registry_result(JoinAlgos::RadixJoin, file1, file2, col1, col2)
   }
 
   fn pnl_result(file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
-    let table1 = &mut SimpleTable::new(file1);
-    let table2 = &mut SimpleTable::new(file2);
-    let mut pnl = ParallelNestedLoopsJoin::new(table1, table2);
-    pnl.equi_join(col1, col2)
+    registry_result(JoinAlgos::PNLJoin, file1, file2, col1, col2)
   }
 
   fn psh_result(file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
-    let table1 = &mut SimpleTable::new(file1);
-    let table2 = &mut SimpleTable::new(file2);
-    let mut pnl = ParallelSimpleHashJoin::new(table1, table2);
-    pnl.equi_join(col1, col2)
+    registry_result(JoinAlgos::ParallelSimpleHashJoin, file1, file2, col1, col2)
   }
 
   fn pulf_result(file1: &str, file2: &str, col1: usize, col2: usize) -> Vec<Record> {
+    registry_result(JoinAlgos::ParallelUnaryLeapFrogJoin, file1, file2, col1, col2)
+  }
+
+  fn grace_result(file1: &str, file2: &str, col1: usize, col2: usize, memory_budget: usize) -> (Vec<Record>, u64) {
     let table1 = &mut SimpleTable::new(file1);
     let table2 = &mut SimpleTable::new(file2);
-    let mut pulf = ParallelUnaryLeapFrogJoin::new(table1, table2);
-    pulf.equi_join(col1, col2)
+    let mut grace = GraceHashJoin::new(table1, table2, memory_budget);
+    let res = grace.equi_join(col1, col2);
+    (res, grace.bytes_spilled())
+  }
+
+  fn compare_outer_results(algo: JoinAlgos, file1: &str, file2: &str, col1: usize, col2: usize) {
+    for join_kind in [JoinKind::LeftOuter, JoinKind::RightOuter, JoinKind::FullOuter] {
+      let keys = JoinKeys::single(col1, col2);
+      let mut expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), &keys, &test_params(join_kind));
+      let mut actual = join_result(&algo, files(file1, file2), &keys, &test_params(join_kind));
+      compare_results(&mut actual, &mut expected);
+    }
+  }
+
+  fn compare_semi_anti_results(algo: JoinAlgos, file1: &str, file2: &str, col1: usize, col2: usize) {
+    for join_kind in [JoinKind::LeftSemi, JoinKind::LeftAnti] {
+      let keys = JoinKeys::single(col1, col2);
+      let mut expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), &keys, &test_params(join_kind));
+      let mut actual = join_result(&algo, files(file1, file2), &keys, &test_params(join_kind));
+      compare_results(&mut actual, &mut expected);
+    }
+  }
+
+  // Theta join by the operator registered for algo
+  fn theta_result(algo: &JoinAlgos, (mut left, mut right): (SimpleTable, SimpleTable), predicate: &str, params: &JoinParams) -> Vec<Record> {
+    let mut join = JoinRegistry::new().create_theta(algo, &mut left, &mut right, params);
+    join.theta_join(&predicate.parse().unwrap())
+  }
+
+  fn compare_theta_results(algo: JoinAlgos, file1: &str, file2: &str, predicate: &str) {
+    let params = test_params(JoinKind::Inner);
+    let mut expected = theta_result(&JoinAlgos::NLJoin, files(file1, file2), predicate, &params);
+    let mut actual = theta_result(&algo, files(file1, file2), predicate, &params);
+    compare_results(&mut actual, &mut expected);
+  }
+
+  // Tables keyed on i64, utf8 and f64 columns, with NULLs
+  fn typed_tables() -> (SimpleTable, SimpleTable) {
+    // Tests load these in parallel, so each call writes its own files
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let dir = TempDir::new(&format!("typed_join_{}", CALLS.fetch_add(1, Ordering::Relaxed)));
+    let left = dir.join("left.csv");
+    let right = dir.join("right.csv");
+    std::fs::write(&left, "id,name,score\n5000000000,alpha,1.5\n-5000000000,beta,-0.0\n,gamma,2.25\n7,,\n5000000000,delta,1.5\n").unwrap();
+    std::fs::write(&right, "key,label,x\n5000000000,alpha,0.0\n-5000000000,beta,1.5\n,,\n7,gamma,2.25\n").unwrap();
+    let options = CsvOptions {
+      types: Some(vec![ColumnType::Int64, ColumnType::Utf8, ColumnType::Float64]),
+      ..CsvOptions::default()
+    };
+    (
+      SimpleTable::from_csv(&left.to_string_lossy(), &options).unwrap(),
+      SimpleTable::from_csv(&right.to_string_lossy(), &options).unwrap(),
+    )
+  }
+
+  fn typed_result(algo: JoinAlgos, keys: &JoinKeys, join_kind: JoinKind) -> Vec<Record> {
+    let (mut left, mut right) = typed_tables();
+    let params = JoinParams {
+      join_kind,
+      left_block_size: 2,
+      right_block_size: 3,
+      ..JoinParams::default()
+    };
+    let mut join = JoinRegistry::new().create(&algo, &mut left, &mut right, &params);
+    join.equi_join_on(keys)
+  }
+
+  // Table of 15 columns, where column c of row i is i * 100 + c,
+  // but for the key column, which is i % modulo or NULL for row 0
+  fn wide_csv(path: &std::path::Path, rows: usize, key: usize, modulo: usize) {
+    let mut text: String = (1..=15).map(|c| format!("col{}", c)).collect::<Vec<_>>().join(",");
+    for i in 0..rows {
+      let fields: Vec<String> = (0..15)
+        .map(|c| match c {
+          _ if c == key && i == 0 => String::new(),
+          _ if c == key => (i % modulo).to_string(),
+          _ => (i * 100 + c).to_string(),
+        })
+        .collect();
+      text.push('\n');
+      text.push_str(&fields.join(","));
+    }
+    std::fs::write(path, text).unwrap();
+  }
+
+  fn columnar_result(algo: &JoinAlgos, left: &ColumnarTable, right: &ColumnarTable, keys: &JoinKeys, params: &JoinParams) -> Vec<Record> {
+    let mut join = JoinRegistry::new().create_columnar(algo, left, right, params);
+    assert_eq!(join.name(), *algo);
+    let output = join.equi_join_on(keys);
+    assert_eq!(output.get_num_records(), join.join_index(keys).len());
+    output.to_records()
+  }
+
+  fn late_result(algo: &JoinAlgos, left: &SimpleTable, right: &SimpleTable, keys: &JoinKeys, params: &JoinParams, projection: &Projection) -> Vec<Record> {
+    // Operators may sort their tables, so each run gets copies
+    let mut left = SimpleTable::from_records(left.schema().clone(), left.copy_to_vec_of_records());
+    let mut right = SimpleTable::from_records(right.schema().clone(), right.copy_to_vec_of_records());
+    let index = {
+      let mut join = JoinRegistry::new().create(algo, &mut left, &mut right, params);
+      join.join_index_on(keys)
+    };
+    materialize(&index, &left, &right, params.join_kind, projection)
+  }
+
+  // Semi or anti join from the join index of an inner join, see distinct_left
+  fn distinct_result(algo: &JoinAlgos, (mut left, mut right): (SimpleTable, SimpleTable), keys: &JoinKeys, join_kind: JoinKind) -> Vec<Record> {
+    let index = {
+      let mut join = JoinRegistry::new().create(algo, &mut left, &mut right, &test_params(JoinKind::Inner));
+      join.join_index_on(keys)
+    };
+    distinct_left(&index, &left, join_kind)
+  }
+
+  // Concatenated batches of a streamed join, checking their sizes
+  fn streamed_result<F: FnOnce(&mut RecordSink)>(batch_size: usize, join: F) -> Vec<Record> {
+    let mut batches: Vec<Vec<Record>> = Vec::new();
+    join(&mut |batch: &[Record]| batches.push(batch.to_vec()));
+    assert!(batches.iter().all(|b| !b.is_empty() && b.len() <= batch_size));
+    batches.concat()
   }
 
-  fn compare_results(actual: &mut Vec<Record>, expected: &mut Vec<Record>) {
+  // Aggregate of each join key computed from the merged records of a
+  // join, where rows whose left side is padding group by the right key
+  fn expected_aggregate(records: &[Record], left: &Schema, right: &Schema, keys: &JoinKeys, join_kind: JoinKind, aggregate: &Aggregate) -> Vec<Record> {
+    let output = Projection::default().schema(left, right, join_kind);
+    let mut groups: Vec<(Vec<Value>, usize, Vec<Value>)> = Vec::new();
+    for r in records {
+      let left_padded = (0..left.num_slots()).all(|i| r.is_null(i));
+      let key: Vec<Value> = keys.left_columns().iter().zip(keys.right_columns())
+        .map(|(&lc, &rc)| match left_padded {
+          true => r.get_value(&output, left.num_columns() + rc),
+          false => r.get_value(&output, lc),
+        })
+        .collect();
+      let i = match groups.iter().position(|g| g.0 == key) {
+        Some(i) => i,
+        None => {
+          groups.push((key, 0, Vec::new()));
+          groups.len() - 1
+        },
+      };
+      groups[i].1 += 1;
+      groups[i].2.extend(aggregate.column.map(|c| r.get_value(&output, c)).filter(|v| !v.is_null()));
+    }
+
+    let schema = aggregate.schema(left, right, keys, join_kind);
+    let value_type = schema.column_type(schema.num_columns() - 1);
+    groups.into_iter()
+      .map(|(mut key, rows, values)| {
+        let numbers = values.iter().map(|v| match v {
+          Value::Int32(v) => *v as f64,
+          Value::Int64(v) => *v as f64,
+          Value::Float64(v) => *v,
+          _ => 0.0,
+        });
+        let value = match aggregate.function {
+          AggregateFunction::Count if aggregate.column.is_none() => Some(rows as f64),
+          AggregateFunction::Count => Some(values.len() as f64),
+          AggregateFunction::Sum => numbers.reduce(|a, b| a + b),
+          AggregateFunction::Min => numbers.reduce(f64::min),
+          AggregateFunction::Max => numbers.reduce(f64::max),
+        };
+        key.push(match (value, value_type) {
+          (None, _) => Value::Null,
+          (Some(v), ColumnType::Int32) => Value::Int32(v as i32),
+          (Some(v), ColumnType::Int64) => Value::Int64(v as i64),
+          (Some(v), _) => Value::Float64(v),
+        });
+        Record::from_values(&schema, &key)
+      })
+      .collect()
+  }
+
+  fn compare_results(actual: &mut [Record], expected: &mut [Record]) {
     assert_eq!(actual.len(), expected.len());
     actual.sort();
     expected.sort();
@@ -179,6 +388,110 @@ mod tests {
     compare_results(&mut actual, &mut expected.clone());
   }
 
+  #[test]
+  fn test_grace_small1_small2() {
+    let col1 = 2;
+    let col2 = 0;
+    let expected = nl_result(SMALL1, SMALL2, col1, col2);
+
+    // Right table fits, so nothing is spilled
+    let (mut actual, bytes_spilled) = grace_result(SMALL1, SMALL2, col1, col2, 1 << 20);
+    compare_results(&mut actual, &mut expected.clone());
+    assert_eq!(bytes_spilled, 0);
+
+    let (mut actual, bytes_spilled) = grace_result(SMALL1, SMALL2, col1, col2, 1);
+    compare_results(&mut actual, &mut expected.clone());
+    assert!(bytes_spilled > 0);
+  }
+
+  #[test]
+  fn test_grace_med1_med2() {
+    let col1 = 2;
+    let col2 = 0;
+    let expected = nl_result(MED1, MED2, col1, col2);
+
+    // Budgets of a few records force recursive repartitioning
+    let record_size = std::mem::size_of::<Record>();
+    for records in [1, 3, 10, 100] {
+      let (mut actual, _) = grace_result(MED1, MED2, col1, col2, records * record_size);
+      compare_results(&mut actual, &mut expected.clone());
+    }
+  }
+
+  #[test]
+  fn test_grace_mapped_skew1_skew2() {
+    let dir = TempDir::new("grace_join");
+    let spill_dir = dir.join("spill");
+    std::fs::create_dir_all(&spill_dir).unwrap();
+    let skew1 = dir.file("skew1.rec");
+    let skew2 = dir.file("skew2.rec");
+    for (csv, rec) in [(SKEW1, &skew1), (SKEW2, &skew2)] {
+      let table = SimpleTable::new(csv);
+      write_record_table(rec, table.schema(), table.record_view()).unwrap();
+    }
+    let keys = JoinKeys::single(0, 0);
+    let expected = nl_result(SKEW1, SKEW2, 0, 0);
+
+    // Partitioned as read from the mapped tables. Heavy keys overflow
+    // whichever partition they land in, the hybrid one included
+    let record_size = std::mem::size_of::<Record>();
+    for records in [1, 2, 5] {
+      let (table1, table2) = (&mut SimpleTable::new(&skew1), &mut SimpleTable::new(&skew2));
+      assert!(table1.is_mapped() && table2.is_mapped());
+      let mut grace = GraceHashJoin::with_spill_dir(table1, table2, records * record_size, &spill_dir);
+      let mut actual = grace.equi_join_on(&keys);
+      compare_results(&mut actual, &mut expected.clone());
+      assert!(grace.bytes_spilled() > 0);
+      assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+    }
+
+    // Spill files are removed even when the join panics
+    let (table1, table2) = (&mut SimpleTable::new(&skew1), &mut SimpleTable::new(&skew2));
+    let mut grace = GraceHashJoin::with_spill_dir(table1, table2, record_size, &spill_dir);
+    let joined = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      grace.equi_join_batches(&keys, 1, &mut |_: &[Record]| panic!("sink failed"));
+    }));
+    assert!(joined.is_err());
+    assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+  }
+
+  #[test]
+  fn test_radix_bits_med1_med2() {
+    let keys = JoinKeys::single(2, 0);
+    let expected = nl_result(MED1, MED2, 2, 0);
+    for radix_bits in [vec![1], vec![10], vec![2, 3], vec![5, 5], vec![1, 1, 1], vec![4, 3, 3]] {
+      let params = JoinParams { radix_bits, ..test_params(JoinKind::Inner) };
+      let mut actual = join_result(&JoinAlgos::RadixJoin, files(MED1, MED2), &keys, &params);
+      compare_results(&mut actual, &mut expected.clone());
+    }
+
+    let keys = JoinKeys::new(&[(2, 0), (1, 1)]);
+    let expected = join_result(&JoinAlgos::NLJoin, files(MED1, MED2), &keys, &test_params(JoinKind::Inner));
+    for radix_bits in [vec![3], vec![2, 2, 2]] {
+      let params = JoinParams { radix_bits, ..test_params(JoinKind::Inner) };
+      let mut actual = join_result(&JoinAlgos::RadixJoin, files(MED1, MED2), &keys, &params);
+      compare_results(&mut actual, &mut expected.clone());
+    }
+  }
+
+  #[test]
+  fn test_radix_partitioning_med1_med2() {
+    let keys = JoinKeys::single(2, 0);
+    let expected = nl_result(MED1, MED2, 2, 0);
+    for (parallel_partitioning, write_combining) in [(true, false), (false, true), (true, true)] {
+      for radix_bits in [vec![4], vec![2, 3], vec![1, 2, 1]] {
+        let params = JoinParams {
+          radix_bits,
+          parallel_partitioning,
+          write_combining,
+          ..test_params(JoinKind::Inner)
+        };
+        let mut actual = join_result(&JoinAlgos::RadixJoin, files(MED1, MED2), &keys, &params);
+        compare_results(&mut actual, &mut expected.clone());
+      }
+    }
+  }
+
   #[test]
   fn test_pnl_small1_small2() { 
     let col1 = 2;
@@ -232,4 +545,834 @@ mod tests {
     let mut actual = pulf_result(MED1, MED2, col1, col2);
     compare_results(&mut actual, &mut expected.clone());
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_nl_outer_small1_small2() {
+    let left = join_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), &JoinKeys::single(2, 0), &test_params(JoinKind::LeftOuter));
+    assert_eq!(left.len(), 6);
+    assert_eq!(left.iter().filter(|r| r.is_null(3)).count(), 3);
+
+    let right = join_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), &JoinKeys::single(2, 0), &test_params(JoinKind::RightOuter));
+    assert_eq!(right.len(), 6);
+    assert_eq!(right.iter().filter(|r| r.is_null(0)).count(), 3);
+
+    let full = join_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), &JoinKeys::single(2, 0), &test_params(JoinKind::FullOuter));
+    assert_eq!(full.len(), 9);
+  }
+
+  #[test]
+  fn test_simplehash_outer() {
+    compare_outer_results(JoinAlgos::SimpleHashJoin, SMALL1, SMALL2, 2, 0);
+    compare_outer_results(JoinAlgos::SimpleHashJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_psh_outer() {
+    compare_outer_results(JoinAlgos::ParallelSimpleHashJoin, SMALL1, SMALL2, 2, 0);
+    compare_outer_results(JoinAlgos::ParallelSimpleHashJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_pulf_outer() {
+    compare_outer_results(JoinAlgos::ParallelUnaryLeapFrogJoin, SMALL1, SMALL2, 2, 0);
+    compare_outer_results(JoinAlgos::ParallelUnaryLeapFrogJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_nl_semi_anti_small1_small2() {
+    let semi = join_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), &JoinKeys::single(2, 0), &test_params(JoinKind::LeftSemi));
+    assert_eq!(semi.len(), 3);
+    assert!(semi.iter().all(|r| r.get_num_columns() == 3));
+
+    let anti = join_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), &JoinKeys::single(2, 0), &test_params(JoinKind::LeftAnti));
+    assert_eq!(anti.len(), 3);
+    assert!(anti.iter().all(|r| r.get_num_columns() == 3));
+  }
+
+  #[test]
+  fn test_simplehash_semi_anti() {
+    compare_semi_anti_results(JoinAlgos::SimpleHashJoin, SMALL1, SMALL2, 2, 0);
+    compare_semi_anti_results(JoinAlgos::SimpleHashJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_psh_semi_anti() {
+    compare_semi_anti_results(JoinAlgos::ParallelSimpleHashJoin, SMALL1, SMALL2, 2, 0);
+    compare_semi_anti_results(JoinAlgos::ParallelSimpleHashJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_radix_semi_anti() {
+    compare_semi_anti_results(JoinAlgos::RadixJoin, SMALL1, SMALL2, 2, 0);
+    compare_semi_anti_results(JoinAlgos::RadixJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_pulf_semi_anti() {
+    compare_semi_anti_results(JoinAlgos::ParallelUnaryLeapFrogJoin, SMALL1, SMALL2, 2, 0);
+    compare_semi_anti_results(JoinAlgos::ParallelUnaryLeapFrogJoin, MED1, MED2, 2, 0);
+  }
+
+  #[test]
+  fn test_distinct_semi_anti_baseline() {
+    let cases = [
+      (SMALL1, SMALL2, JoinKeys::single(0, 0)),
+      (MED1, MED2, JoinKeys::single(1, 2)),
+      (COMPOSITE1, COMPOSITE2, JoinKeys::new(&[(1, 1), (0, 0)])),
+      (SKEW1, SKEW2, JoinKeys::single(0, 0)),
+    ];
+    for (file1, file2, keys) in cases.iter() {
+      for join_kind in [JoinKind::LeftSemi, JoinKind::LeftAnti] {
+        let mut expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), keys, &test_params(join_kind));
+        // Every operator runs inner joins, so each can give the baseline
+        for algo in JoinRegistry::new().algos() {
+          let mut actual = distinct_result(&algo, files(file1, file2), keys, join_kind);
+          compare_results(&mut actual, &mut expected);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_unsupported_kinds_rejected() {
+    let keys = JoinCondition::Keys(JoinKeys::single(5, 5));
+    let options = RunOptions::default();
+    assert!(options.check(&JoinAlgos::RadixJoin, &keys, JoinKind::FullOuter).is_err());
+    assert!(options.check(&JoinAlgos::BNLJoin, &keys, JoinKind::LeftSemi).is_err());
+    assert!(options.check(&JoinAlgos::RadixJoin, &keys, JoinKind::LeftAnti).is_ok());
+
+    // The baseline for semi joins runs an inner join
+    let distinct = RunOptions { output_mode: OutputMode::Distinct, ..RunOptions::default() };
+    assert!(distinct.check(&JoinAlgos::BNLJoin, &keys, JoinKind::LeftSemi).is_ok());
+    assert!(distinct.check(&JoinAlgos::BNLJoin, &keys, JoinKind::Inner).is_err());
+
+    // Theta joins are inner joins of every record
+    let band = JoinCondition::Theta("5:5~100".parse().unwrap());
+    assert!(options.check(&JoinAlgos::BandJoin, &band, JoinKind::LeftOuter).is_err());
+    assert!(distinct.check(&JoinAlgos::NLJoin, &band, JoinKind::LeftSemi).is_err());
+  }
+
+  #[test]
+  fn test_count_has_no_output_table() {
+    let keys = JoinCondition::Keys(JoinKeys::single(5, 5));
+    let count = RunOptions { output_mode: OutputMode::Count, ..RunOptions::default() };
+    assert!(count.check(&JoinAlgos::SimpleHashJoin, &keys, JoinKind::Inner).is_ok());
+    let written = RunOptions { output_table: Some("out.csv".to_owned()), ..count };
+    assert!(written.check(&JoinAlgos::SimpleHashJoin, &keys, JoinKind::Inner).is_err());
+  }
+
+  #[test]
+  fn test_nl_composite() {
+    let keys = JoinKeys::new(&[(0, 0), (1, 1)]);
+    let res = join_result(&JoinAlgos::NLJoin, files(COMPOSITE1, COMPOSITE2), &keys, &test_params(JoinKind::Inner));
+    assert_eq!(res.len(), 5);
+
+    // Only the first key column alone matches more
+    let res = join_result(&JoinAlgos::NLJoin, files(COMPOSITE1, COMPOSITE2), &JoinKeys::single(0, 0), &test_params(JoinKind::Inner));
+    assert_eq!(res.len(), 12);
+  }
+
+  #[test]
+  fn test_composite_all_joins() {
+    let keys = JoinKeys::new(&[(0, 0), (1, 1)]);
+    let expected = join_result(&JoinAlgos::NLJoin, files(COMPOSITE1, COMPOSITE2), &keys, &test_params(JoinKind::Inner));
+    for algo in JoinRegistry::new().algos() {
+      let mut actual = join_result(&algo, files(COMPOSITE1, COMPOSITE2), &keys, &test_params(JoinKind::Inner));
+      compare_results(&mut actual, &mut expected.clone());
+    }
+
+    // Key columns in a different order than the table columns
+    let keys = JoinKeys::new(&[(1, 1), (0, 0)]);
+    for algo in JoinRegistry::new().algos() {
+      let mut actual = join_result(&algo, files(COMPOSITE1, COMPOSITE2), &keys, &test_params(JoinKind::Inner));
+      compare_results(&mut actual, &mut expected.clone());
+    }
+  }
+
+  #[test]
+  fn test_composite_med1_med2() {
+    let keys = JoinKeys::new(&[(2, 0), (1, 1)]);
+    let expected = join_result(&JoinAlgos::NLJoin, files(MED1, MED2), &keys, &test_params(JoinKind::Inner));
+    for algo in JoinRegistry::new().algos() {
+      let mut actual = join_result(&algo, files(MED1, MED2), &keys, &test_params(JoinKind::Inner));
+      compare_results(&mut actual, &mut expected.clone());
+    }
+  }
+
+  #[test]
+  fn test_nl_skew1_skew2() {
+    // Key 7 is on 6 left and 13 right rows, key 3 on 2 and 3,
+    // and 7 more keys are on one row of each side
+    let res = nl_result(SKEW1, SKEW2, 0, 0);
+    assert_eq!(res.len(), 6 * 13 + 2 * 3 + 7);
+  }
+
+  #[test]
+  fn test_skew_handling_skew1_skew2() {
+    let kinds = [
+      JoinKind::Inner, JoinKind::LeftOuter, JoinKind::RightOuter,
+      JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti,
+    ];
+    for join_kind in kinds {
+      let mut expected = join_result(&JoinAlgos::NLJoin, files(SKEW1, SKEW2), &JoinKeys::single(0, 0), &test_params(join_kind));
+      let params = JoinParams { skew_handling: true, ..test_params(join_kind) };
+      let mut actual = join_result(&JoinAlgos::ParallelSimpleHashJoin, files(SKEW1, SKEW2), &JoinKeys::single(0, 0), &params);
+      compare_results(&mut actual, &mut expected);
+    }
+    for join_kind in [JoinKind::Inner, JoinKind::LeftSemi, JoinKind::LeftAnti] {
+      let mut expected = join_result(&JoinAlgos::NLJoin, files(SKEW1, SKEW2), &JoinKeys::single(0, 0), &test_params(join_kind));
+      let params = JoinParams { skew_handling: true, ..test_params(join_kind) };
+      let mut actual = join_result(&JoinAlgos::RadixJoin, files(SKEW1, SKEW2), &JoinKeys::single(0, 0), &params);
+      compare_results(&mut actual, &mut expected);
+    }
+  }
+
+  #[test]
+  fn test_binary_table_med1_med2() {
+    let dir = TempDir::new("binary_table");
+    let med1 = dir.file("med1.bin");
+    let med2 = dir.file("med2.bin");
+    for (csv, bin) in [(MED1, &med1), (MED2, &med2)] {
+      let table = SimpleTable::new(csv);
+      write_binary_table(bin, table.schema(), table.record_view()).unwrap();
+    }
+
+    // Same records, and so the same joins, as the CSV tables
+    let csv_table = SimpleTable::new(MED1);
+    let bin_table = SimpleTable::new(&med1);
+    assert_eq!(bin_table.get_num_records(), csv_table.get_num_records());
+    assert_eq!(bin_table.get_num_columns_per_record(), csv_table.get_num_columns_per_record());
+    assert!(bin_table.record_view().iter().zip(csv_table.record_view()).all(|(b, c)| b == c));
+    assert_eq!(simplehash_result(&med1, &med2, 0, 0).len(), simplehash_result(MED1, MED2, 0, 0).len());
+
+    // A flipped bit fails the checksum
+    let mut bytes = std::fs::read(&med1).unwrap();
+    let last_value = bytes.len() - 9;
+    bytes[last_value] ^= 1;
+    std::fs::write(&med1, bytes).unwrap();
+    assert!(read_binary_table(&med1).is_err());
+  }
+
+  #[test]
+  fn test_binary_table_nulls() {
+    let dir = TempDir::new("binary_nulls");
+    let path = dir.file("nulls.bin");
+    let schema = Schema::new(vec![
+      Column::new("a", ColumnType::Int32),
+      Column::new("b", ColumnType::Int64),
+      Column::new("c", ColumnType::Float64),
+    ]);
+    let records: Vec<Record> = (0..150)
+      .map(|i| Record::from_values(&schema, &[
+        if i % 3 == 0 { Value::Null } else { Value::Int32(i % 4) },
+        if i % 7 == 0 { Value::Null } else { Value::Int64(i as i64) },
+        if i > 100 { Value::Null } else { Value::Float64(0.0) },
+      ]))
+      .collect();
+
+    // Blocks which start and end within a byte of the NULL bitmaps
+    let mut writer = BinaryTableWriter::create(&path, &schema, records.len()).unwrap();
+    for (first_row, block) in [(0, &records[..61]), (61, &records[61..67]), (67, &records[67..])] {
+      writer.write_records(first_row, block).unwrap();
+    }
+    writer.finish().unwrap();
+    assert_eq!(read_binary_table(&path).unwrap(), (schema.clone(), records.clone()));
+
+    // NULL keys read back as NULL, and so match nothing
+    write_binary_table(&path, &schema, &records).unwrap();
+    assert_eq!(read_binary_table(&path).unwrap().1, records);
+    let num_matches: usize = (0..4)
+      .map(|k| records.iter().filter(|r| r.get_value(&schema, 0) == Value::Int32(k)).count().pow(2))
+      .sum();
+    assert_eq!(simplehash_result(&path, &path, 0, 0).len(), num_matches);
+  }
+
+  #[test]
+  fn test_mapped_record_table_med1_med2() {
+    let dir = TempDir::new("record_table");
+    let med1 = dir.file("med1.rec");
+    let med2 = dir.file("med2.rec");
+    for (csv, rec) in [(MED1, &med1), (MED2, &med2)] {
+      let table = SimpleTable::new(csv);
+      write_record_table(rec, table.schema(), table.record_view()).unwrap();
+    }
+
+    // Mapped in place, with the same records and joins as the CSV tables
+    let csv_table = SimpleTable::new(MED1);
+    let mut rec_table = SimpleTable::new(&med1);
+    assert!(rec_table.is_mapped());
+    assert_eq!(rec_table.get_num_records(), csv_table.get_num_records());
+    assert_eq!(rec_table.get_num_columns_per_record(), csv_table.get_num_columns_per_record());
+    assert_eq!(rec_table.copy_to_vec_of_records(), csv_table.copy_to_vec_of_records());
+    assert_eq!(rec_table.read_next_block(7), &csv_table.record_view()[..7]);
+    assert_eq!(simplehash_result(&med1, &med2, 0, 0).len(), simplehash_result(MED1, MED2, 0, 0).len());
+
+    // Sorting changes the mapped copy, not the file
+    let original = std::fs::read(&med1).unwrap();
+    rec_table.sort_by(0);
+    assert!(rec_table.record_view().windows(2).all(|w| w[0].get_column(0) <= w[1].get_column(0)));
+    drop(rec_table);
+    assert_eq!(std::fs::read(&med1).unwrap(), original);
+
+    // Reading rather than mapping checks the checksum
+    assert_eq!(read_binary_table(&med1).unwrap().1, csv_table.copy_to_vec_of_records());
+    let mut bytes = original;
+    let last_value = bytes.len() - 9;
+    bytes[last_value] ^= 1;
+    std::fs::write(&med1, bytes).unwrap();
+    assert!(read_binary_table(&med1).is_err());
+  }
+
+  #[test]
+  fn test_arrow_parquet_med1_med2() {
+    let dir = TempDir::new("arrow_table");
+    let (schema, records) = read_table(MED1).unwrap();
+    assert_eq!(schema, Schema::int32(&default_column_names(SimpleTable::new(MED1).get_num_columns_per_record())));
+    for extension in ["arrow", "parquet"] {
+      let path = dir.file(&format!("med1.{}", extension));
+      write_table(&path, &schema, &records).unwrap();
+
+      // Same names and records, and so the same joins
+      assert_eq!(read_table(&path).unwrap(), (schema.clone(), records.clone()));
+      assert_eq!(SimpleTable::new(&path).copy_to_vec_of_records(), records);
+      assert_eq!(simplehash_result(&path, MED2, 0, 0).len(), simplehash_result(MED1, MED2, 0, 0).len());
+
+      // Outer join output keeps its NULLs
+      let output = join_result(&JoinAlgos::SimpleHashJoin, files(MED1, MED2), &JoinKeys::single(0, 0), &test_params(JoinKind::FullOuter));
+      assert!(output.iter().any(|r| r.is_null(0)));
+      let num_columns = output[0].get_num_columns();
+      write_table(&path, &Schema::int32(&default_column_names(num_columns)), &output).unwrap();
+      assert_eq!(read_table(&path).unwrap().1, output);
+    }
+  }
+
+  #[test]
+  fn test_typed_keys_all_joins() {
+    // NULL keys match nothing, not even NULL keys, and -0.0 equals 0.0
+    let cases = [
+      (JoinKeys::single(0, 0), 4),
+      (JoinKeys::single(1, 1), 3),
+      (JoinKeys::single(2, 2), 4),
+      (JoinKeys::new(&[(0, 0), (1, 1)]), 2),
+    ];
+    for (keys, num_matches) in cases.iter() {
+      let expected = typed_result(JoinAlgos::NLJoin, keys, JoinKind::Inner);
+      assert_eq!(expected.len(), *num_matches);
+      for algo in JoinRegistry::new().algos() {
+        let mut actual = typed_result(algo, keys, JoinKind::Inner);
+        compare_results(&mut actual, &mut expected.clone());
+      }
+    }
+
+    // Output records read back as the typed values
+    let (left, right) = typed_tables();
+    let schema = left.schema().merge(right.schema());
+    let output = typed_result(JoinAlgos::SimpleHashJoin, &JoinKeys::single(1, 1), JoinKind::Inner);
+    let alpha = output.iter().find(|r| r.get_value(&schema, 1) == Value::Utf8("alpha".into())).unwrap();
+    assert_eq!(alpha.get_value(&schema, 0), Value::Int64(5_000_000_000));
+    assert_eq!(alpha.get_value(&schema, 5), Value::Float64(0.0));
+  }
+
+  #[test]
+  fn test_null_keys_outer_semi_anti() {
+    // The NULL keyed records are unmatched on both sides
+    let keys = JoinKeys::single(0, 0);
+    let algos = [JoinAlgos::NLJoin, JoinAlgos::SimpleHashJoin, JoinAlgos::ParallelSimpleHashJoin, JoinAlgos::ParallelUnaryLeapFrogJoin];
+    for algo in algos.iter() {
+      assert_eq!(typed_result(algo.clone(), &keys, JoinKind::LeftOuter).len(), 5);
+      assert_eq!(typed_result(algo.clone(), &keys, JoinKind::RightOuter).len(), 5);
+      assert_eq!(typed_result(algo.clone(), &keys, JoinKind::FullOuter).len(), 6);
+    }
+    for algo in algos.iter().chain([JoinAlgos::RadixJoin].iter()) {
+      assert_eq!(typed_result(algo.clone(), &keys, JoinKind::LeftSemi).len(), 4);
+      let anti = typed_result(algo.clone(), &keys, JoinKind::LeftAnti);
+      assert_eq!(anti.len(), 1);
+      assert!(anti[0].is_null(0) && anti[0].is_null(1));
+    }
+
+    // The baseline for semi and anti joins keeps the key types and NULLs
+    for join_kind in [JoinKind::LeftSemi, JoinKind::LeftAnti] {
+      let mut actual = distinct_result(&JoinAlgos::SimpleHashJoin, typed_tables(), &keys, join_kind);
+      compare_results(&mut actual, &mut typed_result(JoinAlgos::SimpleHashJoin, &keys, join_kind));
+    }
+  }
+
+  #[test]
+  fn test_wide_join_small1_small2() {
+    // Tables which fit in records join the same either way
+    let left = WideTable::new(SMALL1);
+    let right = WideTable::new(SMALL2);
+    let keys = JoinKeys::single(0, 0);
+    let expected = nl_result(SMALL1, SMALL2, 0, 0);
+    for algo in JoinRegistry::new().algos() {
+      let mut actual = wide_join(&JoinRegistry::new(), &algo, &test_params(JoinKind::Inner), &left, &right, &keys).0.rows().to_records();
+      compare_results(&mut actual, &mut expected.clone());
+    }
+    let kinds = [JoinKind::LeftOuter, JoinKind::RightOuter, JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti];
+    for join_kind in kinds {
+      let mut expected = join_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), &JoinKeys::single(0, 0), &test_params(join_kind));
+      for algo in [JoinAlgos::SimpleHashJoin, JoinAlgos::ParallelUnaryLeapFrogJoin] {
+        let mut actual = wide_join(&JoinRegistry::new(), &algo, &test_params(join_kind), &left, &right, &keys).0.rows().to_records();
+        compare_results(&mut actual, &mut expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_wide_three_way_join() {
+    let dir = TempDir::new("wide_join");
+    let paths: Vec<String> = ["a.csv", "b.csv", "c.csv"].iter().map(|f| dir.file(f)).collect();
+    wide_csv(std::path::Path::new(&paths[0]), 12, 0, 4);
+    wide_csv(std::path::Path::new(&paths[1]), 10, 3, 4);
+    wide_csv(std::path::Path::new(&paths[2]), 8, 14, 3);
+    let tables: Vec<WideTable> = paths.iter().map(|p| WideTable::new(p)).collect();
+
+    // a.col1 = b.col4, then b.col4 = c.col15, which is column 18 of a join b.
+    // Key 0 has 2 rows in a, 2 in b and 2 in c, key 1 has 3, 3 and 3, key 2
+    // has 3, 2 and 2, and key 3 has 3, 2 and 0, leaving out the NULL rows
+    let ab = wide_join(&JoinRegistry::new(), &JoinAlgos::SimpleHashJoin, &test_params(JoinKind::Inner), &tables[0], &tables[1], &JoinKeys::single(0, 3)).0;
+    assert_eq!(ab.get_num_records(), 2 * 2 + 3 * 3 + 3 * 2 + 3 * 2);
+    assert!(tables[0].to_simple_table().is_some() && ab.to_simple_table().is_none());
+    for algo in JoinRegistry::new().algos() {
+      let abc = wide_join(&JoinRegistry::new(), &algo, &test_params(JoinKind::Inner), &ab, &tables[2], &JoinKeys::single(18, 14)).0;
+      assert_eq!(abc.get_num_records(), 2 * 2 * 2 + 3 * 3 * 3 + 3 * 2 * 2);
+      assert_eq!(abc.get_num_columns_per_record(), 45);
+      for row in abc.row_iterator() {
+        let key = row.get_column(0);
+        assert_eq!(row.get_column(18), key);
+        assert_eq!(row.get_column(44), key);
+        // The other columns are those of the joined rows
+        assert_eq!(*row.get_column(1) % 100, 1);
+        assert_eq!(*row.get_column(15) % 100, 0);
+        assert_eq!(*row.get_column(43) % 100, 13);
+      }
+    }
+
+    // Unmatched rows are padded with NULLs, and wide output round trips through CSV
+    let full = wide_join(&JoinRegistry::new(), &JoinAlgos::SimpleHashJoin, &test_params(JoinKind::FullOuter), &ab, &tables[2], &JoinKeys::single(18, 14)).0;
+    assert_eq!(full.get_num_records(), 47 + 3 * 2 + 1);
+    let output = dir.file("abc.csv");
+    write_wide_table(&output, full.schema(), full.rows()).unwrap();
+    let read = WideTable::new(&output);
+    assert_eq!(read.get_num_records(), full.get_num_records());
+    assert_eq!(read.rows().iter().filter(|r| r.is_null(0)).count(), 1);
+    assert!(write_wide_table(&dir.file("abc.parquet"), full.schema(), full.rows()).is_err());
+  }
+
+  #[test]
+  fn test_columnar_joins() {
+    let cases = [
+      (SMALL1, SMALL2, JoinKeys::single(0, 0)),
+      (MED1, MED2, JoinKeys::single(1, 2)),
+      (COMPOSITE1, COMPOSITE2, JoinKeys::new(&[(1, 1), (0, 0)])),
+      (SKEW1, SKEW2, JoinKeys::single(0, 0)),
+    ];
+    let kinds = [JoinKind::Inner, JoinKind::LeftOuter, JoinKind::RightOuter, JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti];
+    for (file1, file2, keys) in cases.iter() {
+      let (left, right) = (ColumnarTable::new(file1), ColumnarTable::new(file2));
+      for join_kind in kinds {
+        let mut expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), keys, &test_params(join_kind));
+        for algo in JoinRegistry::new().columnar_algos() {
+          if algo == JoinAlgos::RadixJoin && join_kind.emits_pairs() && join_kind != JoinKind::Inner {
+            continue;
+          }
+          let mut actual = columnar_result(&algo, &left, &right, keys, &test_params(join_kind));
+          compare_results(&mut actual, &mut expected);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_columnar_typed_null_keys() {
+    let (left, right) = typed_tables();
+    let (left, right) = (ColumnarTable::from_table(&left), ColumnarTable::from_table(&right));
+    for keys in [JoinKeys::single(0, 0), JoinKeys::single(1, 1), JoinKeys::new(&[(0, 0), (1, 1)])] {
+      for join_kind in [JoinKind::Inner, JoinKind::FullOuter, JoinKind::LeftAnti] {
+        let mut expected = join_result(&JoinAlgos::NLJoin, typed_tables(), &keys, &test_params(join_kind));
+        let mut actual = columnar_result(&JoinAlgos::SimpleHashJoin, &left, &right, &keys, &test_params(join_kind));
+        compare_results(&mut actual, &mut expected);
+      }
+      let mut expected = join_result(&JoinAlgos::NLJoin, typed_tables(), &keys, &test_params(JoinKind::Inner));
+      let mut actual = columnar_result(&JoinAlgos::RadixJoin, &left, &right, &keys, &test_params(JoinKind::Inner));
+      compare_results(&mut actual, &mut expected);
+    }
+  }
+
+  #[test]
+  fn test_columnar_mapped_binary_tables() {
+    let dir = TempDir::new("columnar_mapped");
+    let keys = JoinKeys::new(&[(1, 1), (0, 0)]);
+    let mut tables: Vec<ColumnarTable> = Vec::new();
+    for (i, file) in [COMPOSITE1, COMPOSITE2].iter().enumerate() {
+      let (schema, records) = read_table(file).unwrap();
+      let path = dir.file(&format!("{}.bin", i));
+      write_binary_table(&path, &schema, &records).unwrap();
+      let mapped = ColumnarTable::new(&path);
+      let read = ColumnarTable::new(file);
+      assert!(mapped.is_mapped() && !read.is_mapped());
+      for c in 0..read.get_num_columns_per_record() {
+        assert_eq!(mapped.column(c), read.column(c));
+        assert!(!mapped.has_nulls(c));
+      }
+      tables.push(mapped);
+    }
+    for join_kind in [JoinKind::Inner, JoinKind::FullOuter, JoinKind::LeftAnti] {
+      let mut expected = join_result(&JoinAlgos::NLJoin, files(COMPOSITE1, COMPOSITE2), &keys, &test_params(join_kind));
+      let mut actual = columnar_result(&JoinAlgos::SimpleHashJoin, &tables[0], &tables[1], &keys, &test_params(join_kind));
+      compare_results(&mut actual, &mut expected);
+    }
+
+    // Mapped NULLs read back as NULLs
+    let schema = Schema::new(vec![Column::new("a", ColumnType::Int32), Column::new("b", ColumnType::Int32)]);
+    let mut records: Vec<Record> = (0..70).map(|i| Record::new(&[i, i])).collect();
+    records[65].set_value(&schema, 1, &Value::Null);
+    let path = dir.file("nulls.bin");
+    write_binary_table(&path, &schema, &records).unwrap();
+    let mapped = ColumnarTable::new(&path);
+    assert!(mapped.is_mapped() && !mapped.has_nulls(0) && mapped.has_nulls(1));
+    assert!(mapped.is_null(1, 65) && !mapped.is_null(1, 64));
+    assert_eq!(mapped.to_records(), records);
+  }
+
+  #[test]
+  fn test_late_materialization_all_joins() {
+    let cases = [
+      (SMALL1, SMALL2, JoinKeys::single(0, 0)),
+      (MED1, MED2, JoinKeys::single(1, 2)),
+      (COMPOSITE1, COMPOSITE2, JoinKeys::new(&[(1, 1), (0, 0)])),
+      (SKEW1, SKEW2, JoinKeys::single(0, 0)),
+    ];
+    let kinds = [JoinKind::Inner, JoinKind::LeftOuter, JoinKind::RightOuter, JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti];
+    for (file1, file2, keys) in cases.iter() {
+      let (left, right) = (SimpleTable::new(file1), SimpleTable::new(file2));
+      for join_kind in kinds {
+        let mut expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), keys, &test_params(join_kind));
+        for algo in JoinRegistry::new().algos() {
+          if algo.check_kind(join_kind).is_err() {
+            continue;
+          }
+          // Spill, partition in several passes and spread heavy keys too
+          for skew_handling in [false, true] {
+            let params = JoinParams { skew_handling, ..test_params(join_kind) };
+            let mut actual = late_result(&algo, &left, &right, keys, &params, &Projection::default());
+            compare_results(&mut actual, &mut expected);
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_late_materialization_projection() {
+    assert_eq!("0,2:*".parse::<Projection>().unwrap(), Projection { left: Some(vec![0, 2]), right: None });
+    assert_eq!("1:".parse::<Projection>().unwrap(), Projection::new(&[1], &[]));
+    assert!("0,2".parse::<Projection>().is_err());
+    assert!("a:1".parse::<Projection>().is_err());
+
+    // Projected eager output, by column values
+    let (left, right) = typed_tables();
+    let projection = Projection::new(&[1], &[2, 0]);
+    let keys = JoinKeys::single(0, 0);
+    for join_kind in [JoinKind::Inner, JoinKind::FullOuter, JoinKind::LeftSemi] {
+      let merged = left.schema().merge(right.schema());
+      let columns: &[usize] = match join_kind {
+        JoinKind::LeftSemi => &[1],
+        _ => &[1, 5, 3],
+      };
+      let schema = projection.schema(left.schema(), right.schema(), join_kind);
+      assert_eq!(schema.num_columns(), columns.len());
+      let mut expected: Vec<Record> = join_result(&JoinAlgos::NLJoin, typed_tables(), &keys, &test_params(join_kind)).iter()
+        .map(|r| {
+          let schema = if join_kind == JoinKind::LeftSemi { left.schema() } else { &merged };
+          columns.iter().map(|c| r.get_value(schema, *c)).collect::<Vec<Value>>()
+        })
+        .map(|values| Record::from_values(&schema, &values))
+        .collect();
+      for algo in [JoinAlgos::NLJoin, JoinAlgos::SimpleHashJoin, JoinAlgos::ParallelSimpleHashJoin, JoinAlgos::ParallelUnaryLeapFrogJoin] {
+        let mut actual = late_result(&algo, &left, &right, &keys, &test_params(join_kind), &projection);
+        compare_results(&mut actual, &mut expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_streamed_joins() {
+    let cases = [
+      (SMALL1, SMALL2, JoinKeys::single(0, 0)),
+      (MED1, MED2, JoinKeys::single(1, 2)),
+      (SKEW1, SKEW2, JoinKeys::single(0, 0)),
+    ];
+    let kinds = [JoinKind::Inner, JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti];
+    for (file1, file2, keys) in cases.iter() {
+      for join_kind in kinds {
+        let mut expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), keys, &test_params(join_kind));
+        for algo in JoinRegistry::new().algos().iter().filter(|a| a.check_kind(join_kind).is_ok()) {
+          for (batch_size, skew_handling) in [(1, false), (4, true), (1000, false)] {
+            let (table1, table2) = (&mut SimpleTable::new(file1), &mut SimpleTable::new(file2));
+            let params = JoinParams { skew_handling, ..test_params(join_kind) };
+            let mut join = JoinRegistry::new().create(algo, table1, table2, &params);
+            let mut actual = streamed_result(batch_size, |sink| join.equi_join_batches(keys, batch_size, sink));
+            compare_results(&mut actual, &mut expected);
+          }
+        }
+      }
+    }
+
+    // Theta joins stream too
+    let theta_cases = JoinRegistry::new().theta_algos().into_iter().flat_map(|algo| {
+      let predicates: &[&str] = if algo == JoinAlgos::IEJoin { &["2<0", "2<0,1>=1"] } else { &["2:0~3"] };
+      predicates.iter().map(move |predicate| (algo.clone(), *predicate))
+    });
+    for (algo, predicate) in theta_cases {
+      let mut expected = theta_result(&JoinAlgos::NLJoin, files(MED1, MED2), predicate, &test_params(JoinKind::Inner));
+      let (table1, table2) = (&mut SimpleTable::new(MED1), &mut SimpleTable::new(MED2));
+      let mut join = JoinRegistry::new().create_theta(&algo, table1, table2, &test_params(JoinKind::Inner));
+      let predicate = predicate.parse().unwrap();
+      let mut actual = streamed_result(2, |sink| join.theta_join_batches(&predicate, 2, sink));
+      compare_results(&mut actual, &mut expected);
+    }
+  }
+
+  #[test]
+  fn test_theta_predicate_parsing() {
+    assert_eq!("5:6~100".parse::<ThetaPredicate>().unwrap(), ThetaPredicate::Band { left_col: 5, right_col: 6, width: 100 });
+    assert_eq!(
+      "1<=2,3>4".parse::<ThetaPredicate>().unwrap(),
+      ThetaPredicate::Inequalities(vec![
+        Inequality { left_col: 1, op: ThetaOp::Le, right_col: 2 },
+        Inequality { left_col: 3, op: ThetaOp::Gt, right_col: 4 },
+      ]));
+    assert!("1<2,2<3,3<4".parse::<ThetaPredicate>().is_err());
+    assert!("1=2".parse::<ThetaPredicate>().is_err());
+  }
+
+  #[test]
+  fn test_nl_band_small1_small2() {
+    // Left col3 is -6,-3,0,3,6,9 and right col3 is -6,-2763,3,8487,-12347,9
+    assert_eq!(theta_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), "2:0~0", &test_params(JoinKind::Inner)).len(), 3);
+    assert_eq!(theta_result(&JoinAlgos::NLJoin, files(SMALL1, SMALL2), "2:0~3", &test_params(JoinKind::Inner)).len(), 7);
+  }
+
+  #[test]
+  fn test_bnl_theta() {
+    for predicate in ["2:0~3", "2<0", "2>=0,1<1"] {
+      compare_theta_results(JoinAlgos::BNLJoin, SMALL1, SMALL2, predicate);
+      compare_theta_results(JoinAlgos::BNLJoin, MED1, MED2, predicate);
+    }
+  }
+
+  #[test]
+  fn test_band_join() {
+    for predicate in ["2:0~0", "2:0~3", "2:0~1000", "0:2~4294967295"] {
+      compare_theta_results(JoinAlgos::BandJoin, SMALL1, SMALL2, predicate);
+      compare_theta_results(JoinAlgos::BandJoin, MED1, MED2, predicate);
+    }
+  }
+
+  #[test]
+  fn test_iejoin_one_inequality() {
+    for predicate in ["2<0", "2<=0", "2>0", "2>=0"] {
+      compare_theta_results(JoinAlgos::IEJoin, SMALL1, SMALL2, predicate);
+      compare_theta_results(JoinAlgos::IEJoin, MED1, MED2, predicate);
+    }
+  }
+
+  #[test]
+  fn test_theta_null_columns() {
+    let dir = TempDir::new("theta_null");
+    let left = dir.file("left.csv");
+    let right = dir.file("right.csv");
+    std::fs::write(&left, "a,b\n5,1\n,2\n-3,\n0,0\n,\n7,-2\n").unwrap();
+    std::fs::write(&right, "a,b\n,3\n5,\n0,1\n-4,-4\n,\n9,2\n").unwrap();
+
+    // NULLs compare with nothing, although their slots hold 0
+    let expected = theta_result(&JoinAlgos::NLJoin, files(&left, &right), "0<0", &test_params(JoinKind::Inner));
+    assert_eq!(expected.len(), 7);
+    assert!(expected.iter().all(|r| !r.is_null(0) && !r.is_null(2)));
+    for predicate in ["0:0~2", "0<0", "0>=0", "1<=1", "0<0,1>1", "0>=0,1<=1"] {
+      compare_theta_results(JoinAlgos::BNLJoin, &left, &right, predicate);
+      let algo = if predicate.contains('~') { JoinAlgos::BandJoin } else { JoinAlgos::IEJoin };
+      compare_theta_results(algo, &left, &right, predicate);
+    }
+  }
+
+  #[test]
+  fn test_theta_typed_columns() {
+    let dir = TempDir::new("theta_typed");
+    let left = dir.file("left.csv");
+    let right = dir.file("right.csv");
+    std::fs::write(&left, "a,b\n30000000000,2\n8,0\n-1,5\n").unwrap();
+    std::fs::write(&right, "a,b\n7,1\n40000000000,3\n").unwrap();
+    let options = CsvOptions {
+      types: Some(vec![ColumnType::Int64, ColumnType::Int32]),
+      ..CsvOptions::default()
+    };
+    let load = |path: &str| SimpleTable::from_csv(path, &options).unwrap();
+
+    // Column 1 is the slot after the two of the i64, whatever the operator
+    for (algo, predicate, pairs) in [
+      (JoinAlgos::NLJoin, "1<1", vec![(2, 3), (0, 1), (0, 3)]),
+      (JoinAlgos::BNLJoin, "1<1", vec![(2, 3), (0, 1), (0, 3)]),
+      (JoinAlgos::IEJoin, "1<1", vec![(2, 3), (0, 1), (0, 3)]),
+      (JoinAlgos::BandJoin, "1:1~1", vec![(2, 1), (2, 3), (0, 1)]),
+    ] {
+      let tables = (load(&left), load(&right));
+      let schema = tables.0.schema().merge(tables.1.schema());
+      let int = |v: Value| match v {
+        Value::Int32(v) => v,
+        v => panic!("Expected an i32, got {:?}", v),
+      };
+      let mut actual: Vec<(i32, i32)> = theta_result(&algo, tables, predicate, &test_params(JoinKind::Inner)).iter()
+        .map(|r| (int(r.get_value(&schema, 1)), int(r.get_value(&schema, 3))))
+        .collect();
+      actual.sort();
+      let mut expected = pairs;
+      expected.sort();
+      assert_eq!(actual, expected, "{:?} {}", algo, predicate);
+    }
+
+    // Columns of other types, or which do not exist, are errors
+    let (table1, table2) = (load(&left), load(&right));
+    for predicate in ["0<0", "1<0", "0:1~5", "2<1"] {
+      let predicate: ThetaPredicate = predicate.parse().unwrap();
+      assert!(predicate.resolve(table1.schema(), table2.schema()).is_err());
+    }
+  }
+
+  #[test]
+  fn test_iejoin_two_inequalities() {
+    for first in ["<", "<=", ">", ">="] {
+      for second in ["<", "<=", ">", ">="] {
+        let predicate = format!("2{}0,1{}1", first, second);
+        compare_theta_results(JoinAlgos::IEJoin, SMALL1, SMALL2, &predicate);
+        compare_theta_results(JoinAlgos::IEJoin, MED1, MED2, &predicate);
+      }
+    }
+  }
+
+  // Operator defined outside of joinlib which only
+  // emits the matches of the first left record
+  struct FirstMatchJoin<'a> {
+    left: &'a SimpleTable,
+    right: &'a SimpleTable,
+  }
+
+  impl<'a> JoinOperator for FirstMatchJoin<'a> {
+    fn name(&self) -> JoinAlgos {
+      JoinAlgos::Custom("FirstMatchJoin".to_owned())
+    }
+
+    fn tables(&self) -> (&SimpleTable, &SimpleTable) {
+      (self.left, self.right)
+    }
+  }
+
+  impl<'a> JoinInto for FirstMatchJoin<'a> {
+    fn join_into<O: JoinOutput>(&mut self, keys: &JoinKeys, join_result: &mut O) {
+      let keys = &keys.resolve(self.left.schema(), self.right.schema());
+      let left_record = &self.left.record_view()[0];
+      for (r, right_record) in self.right.record_iterator().enumerate() {
+        if keys.matches(left_record, right_record) {
+          join_result.pair(0, left_record, r as u32, right_record);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_registry_custom_join() {
+    let algo = JoinAlgos::Custom("FirstMatchJoin".to_owned());
+    let mut registry = JoinRegistry::new();
+    assert!(!registry.contains(&algo));
+    registry.register(algo.clone(), |left, right, _| {
+      Box::new(FirstMatchJoin { left, right })
+    });
+    assert!(registry.contains(&algo));
+
+    let table1 = &mut SimpleTable::new(SMALL1);
+    let table2 = &mut SimpleTable::new(SMALL2);
+    let mut join = registry.create(&algo, table1, table2, &JoinParams::default());
+    assert_eq!(join.name(), algo);
+    assert_eq!(join.equi_join(2, 0).len(), 1);
+    assert_eq!(join.count_on(&JoinKeys::single(2, 0)), 1);
+  }
+
+  #[test]
+  fn test_count_all_joins() {
+    let cases = [
+      (SMALL1, SMALL2, JoinKeys::single(0, 0)),
+      (MED1, MED2, JoinKeys::single(1, 2)),
+      (COMPOSITE1, COMPOSITE2, JoinKeys::new(&[(1, 1), (0, 0)])),
+      (SKEW1, SKEW2, JoinKeys::single(0, 0)),
+    ];
+    let kinds = [JoinKind::Inner, JoinKind::LeftOuter, JoinKind::RightOuter, JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti];
+    for (file1, file2, keys) in cases.iter() {
+      for join_kind in kinds {
+        let expected = join_result(&JoinAlgos::NLJoin, files(file1, file2), keys, &test_params(join_kind)).len();
+        for algo in JoinRegistry::new().algos().iter().filter(|a| a.check_kind(join_kind).is_ok()) {
+          for skew_handling in [false, true] {
+            let (table1, table2) = (&mut SimpleTable::new(file1), &mut SimpleTable::new(file2));
+            let params = JoinParams { skew_handling, ..test_params(join_kind) };
+            let mut join = JoinRegistry::new().create(algo, table1, table2, &params);
+            assert_eq!(join.count_on(keys), expected, "{:?} {:?} on {:?}", algo, join_kind, file1);
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_aggregate_all_joins() {
+    assert_eq!("sum:3".parse::<Aggregate>().unwrap(), Aggregate::new(AggregateFunction::Sum, 3));
+    assert_eq!("COUNT".parse::<Aggregate>().unwrap(), Aggregate::count());
+    assert!("max".parse::<Aggregate>().is_err());
+    assert!("avg:1".parse::<Aggregate>().is_err());
+
+    let kinds = [JoinKind::Inner, JoinKind::LeftOuter, JoinKind::RightOuter, JoinKind::FullOuter, JoinKind::LeftSemi, JoinKind::LeftAnti];
+
+    // Int32 tables, aggregating columns of either side
+    let keys = JoinKeys::single(1, 2);
+    let (left, right) = (SimpleTable::new(MED1), SimpleTable::new(MED2));
+    let num_left_columns = left.schema().num_columns();
+    for join_kind in kinds {
+      let records = join_result(&JoinAlgos::NLJoin, files(MED1, MED2), &keys, &test_params(join_kind));
+      let mut aggregates = vec![Aggregate::count(), Aggregate::new(AggregateFunction::Sum, 0), Aggregate::new(AggregateFunction::Min, 2)];
+      if join_kind.emits_pairs() {
+        aggregates.push(Aggregate::new(AggregateFunction::Max, num_left_columns));
+        aggregates.push(Aggregate::new(AggregateFunction::Count, num_left_columns + 1));
+      }
+      for aggregate in aggregates.iter() {
+        let mut expected = expected_aggregate(&records, left.schema(), right.schema(), &keys, join_kind, aggregate);
+        for algo in JoinRegistry::new().algos().iter().filter(|a| a.check_kind(join_kind).is_ok()) {
+          let (table1, table2) = (&mut SimpleTable::new(MED1), &mut SimpleTable::new(MED2));
+          let mut join = JoinRegistry::new().create(algo, table1, table2, &test_params(join_kind));
+          let mut actual = join.aggregate_on(&keys, aggregate);
+          compare_results(&mut actual, &mut expected);
+        }
+      }
+    }
+
+    // Typed tables, whose NULL keys form a group in outer joins
+    let keys = JoinKeys::single(0, 0);
+    let (left, right) = typed_tables();
+    let aggregates = [
+      Aggregate::new(AggregateFunction::Sum, 2),
+      Aggregate::new(AggregateFunction::Min, 5),
+      Aggregate::new(AggregateFunction::Max, 3),
+      Aggregate::new(AggregateFunction::Count, 4),
+    ];
+    for join_kind in [JoinKind::Inner, JoinKind::FullOuter] {
+      let records = typed_result(JoinAlgos::NLJoin, &keys, join_kind);
+      for aggregate in aggregates.iter() {
+        let mut expected = expected_aggregate(&records, left.schema(), right.schema(), &keys, join_kind, aggregate);
+        for algo in JoinRegistry::new().algos().iter().filter(|a| a.check_kind(join_kind).is_ok()) {
+          let (mut table1, mut table2) = typed_tables();
+          let mut join = JoinRegistry::new().create(algo, &mut table1, &mut table2, &test_params(join_kind));
+          let mut actual = join.aggregate_on(&keys, aggregate);
+          compare_results(&mut actual, &mut expected);
+        }
+      }
+    }
+  }
+}
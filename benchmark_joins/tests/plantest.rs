@@ -0,0 +1,201 @@
+extern crate joinlib;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::TempDir;
+  use joinlib::aggregate::{Aggregate, AggregateFunction};
+  use joinlib::join::{JoinAlgos, JoinKind, JoinParams};
+  use joinlib::key::JoinKeys;
+  use joinlib::plan::*;
+  use joinlib::readtable::CsvOptions;
+  use joinlib::registry::JoinRegistry;
+  use joinlib::record::Record;
+  use joinlib::schema::{Column, ColumnType, Schema, Value};
+  use joinlib::table::SimpleTable;
+  use joinlib::thetajoin::ThetaPredicate;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  const MED1: &str = "tables/med1.csv";
+  const MED2: &str = "tables/med2.csv";
+
+  // Fruit, quantity and price, with a NULL in each column
+  fn fruit_table() -> SimpleTable {
+    // Tests load these in parallel, so each call writes its own files
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let dir = TempDir::new(&format!("plan_{}", CALLS.fetch_add(1, Ordering::Relaxed)));
+    let path = dir.join("fruit.csv");
+    std::fs::write(&path, "name,qty,price\npear,3,1.5\napple,,2.0\nfig,5,\nbanana,3,0.5\n,1,1.0\n").unwrap();
+    let options = CsvOptions {
+      types: Some(vec![ColumnType::Utf8, ColumnType::Int64, ColumnType::Float64]),
+      ..CsvOptions::default()
+    };
+    SimpleTable::from_csv(&path.to_string_lossy(), &options).unwrap()
+  }
+
+  fn fruit_plan() -> Plan {
+    let table = fruit_table();
+    Plan::records(table.schema().clone(), table.copy_to_vec_of_records())
+  }
+
+  fn run(plan: Plan) -> Vec<Vec<Value>> {
+    let table = plan.execute(&JoinRegistry::new()).unwrap();
+    let schema = table.schema();
+    table.record_iterator()
+      .map(|r| (0..schema.num_columns()).map(|c| r.get_value(schema, c)).collect())
+      .collect()
+  }
+
+  // Values of the name column
+  fn names(plan: Plan) -> Vec<Value> {
+    run(plan.project(&[0])).into_iter().map(|mut row| row.remove(0)).collect()
+  }
+
+  fn utf8(s: &str) -> Value {
+    Value::Utf8(s.into())
+  }
+
+  #[test]
+  fn test_filter_project() {
+    let qty_at_least_3 = Predicate::compare(1, CompareOp::Ge, Value::Int32(3));
+    assert_eq!(names(fruit_plan().filter(qty_at_least_3.clone())), vec![utf8("pear"), utf8("fig"), utf8("banana")]);
+
+    // Comparisons with NULL are unknown, and so is their negation
+    assert_eq!(names(fruit_plan().filter(Predicate::Not(Box::new(qty_at_least_3)))), vec![Value::Null]);
+    assert_eq!(names(fruit_plan().filter(Predicate::IsNull(1))), vec![utf8("apple")]);
+    let cheap = Predicate::compare(2, CompareOp::Lt, Value::Float64(1.0));
+    let one = Predicate::compare(1, CompareOp::Eq, Value::Int64(1));
+    assert_eq!(names(fruit_plan().filter(one.or(cheap.clone()))), vec![utf8("banana"), Value::Null]);
+    assert_eq!(names(fruit_plan().filter(cheap.and(Predicate::compare(0, CompareOp::Ne, utf8("fig"))))), vec![utf8("banana")]);
+
+    // Integers compare with floats, and strings as strings
+    assert_eq!(names(fruit_plan().filter(Predicate::compare_columns(1, CompareOp::Gt, 2))), vec![utf8("pear"), utf8("banana")]);
+    assert_eq!(names(fruit_plan().filter(Predicate::compare(0, CompareOp::Le, utf8("banana")))), vec![utf8("apple"), utf8("banana")]);
+
+    assert_eq!(run(fruit_plan().project(&[2, 0]).limit(2)), vec![
+      vec![Value::Float64(1.5), utf8("pear")],
+      vec![Value::Float64(2.0), utf8("apple")],
+    ]);
+    assert_eq!(run(fruit_plan().limit(10)).len(), 5);
+  }
+
+  #[test]
+  fn test_sort() {
+    // NULLs sort last, and so first when descending
+    assert_eq!(names(fruit_plan().sort(&[SortKey::asc(0)])), vec![utf8("apple"), utf8("banana"), utf8("fig"), utf8("pear"), Value::Null]);
+    assert_eq!(names(fruit_plan().sort(&[SortKey::desc(0)])), vec![Value::Null, utf8("pear"), utf8("fig"), utf8("banana"), utf8("apple")]);
+    assert_eq!(
+      names(fruit_plan().sort(&[SortKey::desc(1), SortKey::asc(0)])),
+      vec![utf8("apple"), utf8("fig"), utf8("banana"), utf8("pear"), Value::Null]);
+
+    // Sorting is stable
+    assert_eq!(names(fruit_plan().sort(&[SortKey::asc(1)])), vec![Value::Null, utf8("pear"), utf8("banana"), utf8("fig"), utf8("apple")]);
+  }
+
+  #[test]
+  fn test_aggregate() {
+    let aggregates = [
+      Aggregate::count(),
+      Aggregate::new(AggregateFunction::Sum, 2),
+      Aggregate::new(AggregateFunction::Max, 2),
+      Aggregate::new(AggregateFunction::Count, 0),
+    ];
+    let table = fruit_plan().aggregate(&[1], &aggregates).execute(&JoinRegistry::new()).unwrap();
+    assert_eq!(table.schema().column_names(), vec!["qty", "count", "sum_price", "max_price", "count_name"]);
+    assert_eq!(run(fruit_plan().aggregate(&[1], &aggregates)), vec![
+      vec![Value::Int64(1), Value::Int64(1), Value::Float64(1.0), Value::Float64(1.0), Value::Int64(0)],
+      vec![Value::Int64(3), Value::Int64(2), Value::Float64(2.0), Value::Float64(1.5), Value::Int64(2)],
+      vec![Value::Int64(5), Value::Int64(1), Value::Null, Value::Null, Value::Int64(1)],
+      vec![Value::Null, Value::Int64(1), Value::Float64(2.0), Value::Float64(2.0), Value::Int64(1)],
+    ]);
+
+    // Without group by columns there is one group, even of no records
+    let nothing = Predicate::compare(1, CompareOp::Gt, Value::Int64(100));
+    assert_eq!(run(fruit_plan().filter(nothing).aggregate(&[], &aggregates)), vec![
+      vec![Value::Int64(0), Value::Null, Value::Null, Value::Int64(0)],
+    ]);
+    assert_eq!(run(fruit_plan().aggregate(&[], &[Aggregate::new(AggregateFunction::Min, 1)])), vec![vec![Value::Int64(1)]]);
+
+    // Utf8 groups sort by their strings, not in the order they were read
+    let by_name = names(fruit_plan().aggregate(&[0], &[Aggregate::count()]));
+    assert_eq!(by_name, vec![utf8("apple"), utf8("banana"), utf8("fig"), utf8("pear"), Value::Null]);
+
+    // Sums may overflow an Int64 on the way to one which fits
+    let schema = Schema::new(vec![Column::new("v", ColumnType::Int64)]);
+    let records: Vec<Record> = [i64::MAX, i64::MAX, -i64::MAX].iter()
+      .map(|v| {
+        let mut record = Record::new(&[0, 0]);
+        record.set_value(&schema, 0, &Value::Int64(*v));
+        record
+      })
+      .collect();
+    let sum = Plan::records(schema, records).aggregate(&[], &[Aggregate::new(AggregateFunction::Sum, 0)]);
+    assert_eq!(run(sum), vec![vec![Value::Int64(i64::MAX)]]);
+  }
+
+  #[test]
+  fn test_join_plan_all_joins() {
+    // SELECT l.col2, COUNT(*), SUM(r.col5) FROM med1 l JOIN med2 r ON l.col2 = r.col4
+    // WHERE l.col1 >= -5 GROUP BY l.col2 ORDER BY 3 DESC, 1 LIMIT 3
+    let (med1, med2) = (SimpleTable::new(MED1), SimpleTable::new(MED2));
+    let mut groups: Vec<(i32, i64, i64)> = Vec::new();
+    for l in med1.record_iterator().filter(|l| *l.get_column(0) >= -5) {
+      for r in med2.record_iterator().filter(|r| r.get_column(1) == l.get_column(1)) {
+        match groups.iter_mut().find(|g| g.0 == *l.get_column(1)) {
+          Some(g) => {
+            g.1 += 1;
+            g.2 += *r.get_column(2) as i64;
+          },
+          None => groups.push((*l.get_column(1), 1, *r.get_column(2) as i64)),
+        }
+      }
+    }
+    groups.sort_by_key(|g| (-g.2, g.0));
+    let expected: Vec<Vec<Value>> = groups.iter()
+      .take(3)
+      .map(|g| vec![Value::Int32(g.0), Value::Int64(g.1), Value::Int64(g.2)])
+      .collect();
+    assert_eq!(expected.len(), 3);
+
+    let registry = JoinRegistry::new();
+    for algo in registry.algos() {
+      let params = JoinParams {
+        left_block_size: 2,
+        right_block_size: 3,
+        memory_budget_bytes: 200,
+        radix_bits: vec![2, 1],
+        ..JoinParams::default()
+      };
+      let plan = Plan::scan(MED1)
+        .filter(Predicate::compare(0, CompareOp::Ge, Value::Int32(-5)))
+        .join(Plan::scan(MED2), algo, JoinKeys::single(1, 1), params)
+        .aggregate(&[1], &[Aggregate::count(), Aggregate::new(AggregateFunction::Sum, 5)])
+        .sort(&[SortKey::desc(2), SortKey::asc(0)])
+        .limit(3);
+      assert_eq!(run(plan), expected);
+    }
+
+    // Other join kinds and theta joins compose too
+    let params = JoinParams {
+      join_kind: JoinKind::LeftAnti,
+      ..JoinParams::default()
+    };
+    let anti = Plan::scan(MED1).join(Plan::scan(MED2), JoinAlgos::SimpleHashJoin, JoinKeys::single(1, 1), params);
+    let num_matched = med1.record_iterator().filter(|l| med2.record_iterator().any(|r| r.get_column(1) == l.get_column(1))).count();
+    assert_eq!(run(anti.aggregate(&[], &[Aggregate::count()])), vec![vec![Value::Int64((med1.get_num_records() - num_matched) as i64)]]);
+
+    let band: ThetaPredicate = "2:0~3".parse().unwrap();
+    let num_pairs = med1.record_iterator()
+      .flat_map(|l| med2.record_iterator().map(move |r| (l, r)))
+      .filter(|(l, r)| band.matches(l, r))
+      .count();
+    let theta = Plan::scan(MED1).theta_join(Plan::scan(MED2), JoinAlgos::BandJoin, band.clone(), JoinParams::default());
+    assert_eq!(run(theta.aggregate(&[], &[Aggregate::count()])), vec![vec![Value::Int64(num_pairs as i64)]]);
+  }
+
+  #[test]
+  fn test_scan_errors() {
+    assert!(Plan::scan("tables/no_such_table.csv").limit(1).execute(&JoinRegistry::new()).is_err());
+  }
+}
@@ -0,0 +1,155 @@
+extern crate joinlib;
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::TempDir;
+  use joinlib::readtable::*;
+  use joinlib::key::KeyRef;
+  use joinlib::record::Record;
+  use joinlib::schema::{ColumnType, Schema, Value};
+  use joinlib::table::{read_table, write_table, SimpleTable};
+
+  // Writes contents to a CSV file in the test's dir
+  fn csv_file(dir: &TempDir, name: &str, contents: &str) -> String {
+    let path = dir.file(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+  }
+
+  fn values(table: &CsvTable) -> Vec<Vec<i32>> {
+    table.records.iter().map(|r| r.get_column_values().to_vec()).collect()
+  }
+
+  #[test]
+  fn test_strict_errors() {
+    let dir = TempDir::new("readtable_strict");
+    let ragged = csv_file(&dir, "ragged.csv", "a,b,c\n1,2,3\n4,5\n");
+    assert_eq!(
+      read_csv_table(&ragged, &CsvOptions::default()).err().unwrap(),
+      CsvError::Ragged { file: ragged.clone(), line: 3, expected: 3, found: 2 });
+
+    let bad = csv_file(&dir, "bad.csv", "a,b,c\n1,2,3\n4,x5,6\n");
+    match read_csv_table(&bad, &CsvOptions::default()) {
+      Err(CsvError::Parse { line: 3, column: 1, reason, .. }) => assert!(reason.contains("x5")),
+      other => panic!("Expected a parse error, got {:?}", other.err()),
+    }
+    assert!(SimpleTable::try_new(&bad).is_err());
+
+    let empty = csv_file(&dir, "empty.csv", "");
+    assert_eq!(read_csv_table(&empty, &CsvOptions::default()).err().unwrap(), CsvError::Empty { file: empty.clone() });
+
+    let missing = csv_file(&dir, "exists.csv", "a\n").replace("exists", "missing");
+    assert!(matches!(read_csv_table(&missing, &CsvOptions::default()), Err(CsvError::Io { .. })));
+
+    let wide = csv_file(&dir, "wide.csv", &format!("{}\n", vec!["1"; 21].join(",")));
+    let options = CsvOptions { has_headers: false, ..CsvOptions::default() };
+    assert!(matches!(read_csv_table(&wide, &options), Err(CsvError::TooManyColumns { found: 21, .. })));
+  }
+
+  #[test]
+  fn test_lenient_skips_bad_rows() {
+    let dir = TempDir::new("readtable_lenient");
+    let path = csv_file(&dir, "lenient.csv", "a,b\n1,2\n3\n4,five\n6,7\n8,9,10\n");
+    let options = CsvOptions { mode: CsvMode::Lenient, ..CsvOptions::default() };
+    let table = read_csv_table(&path, &options).unwrap();
+    assert_eq!(values(&table), vec![vec![1, 2], vec![6, 7]]);
+    assert_eq!(table.skipped.iter().map(|e| e.line().unwrap()).collect::<Vec<u64>>(), vec![3, 4, 6]);
+    assert_eq!(SimpleTable::from_csv(&path, &options).unwrap().get_num_records(), 2);
+  }
+
+  #[test]
+  fn test_options() {
+    let dir = TempDir::new("readtable_options");
+
+    // Semicolons, no header and a projection of reordered columns
+    let path = csv_file(&dir, "options.csv", "1;2;3\n4; 5 ;6\n");
+    let options = CsvOptions {
+      delimiter: b';',
+      has_headers: false,
+      columns: Some(vec![CsvColumn::Index(2), CsvColumn::Index(0)]),
+      ..CsvOptions::default()
+    };
+    let table = read_csv_table(&path, &options).unwrap();
+    assert_eq!(table.schema.column_names(), vec!["col3", "col1"]);
+    assert_eq!(values(&table), vec![vec![3, 1], vec![6, 4]]);
+
+    // Columns by name, with an empty field as NULL
+    let path = csv_file(&dir, "names.csv", "id,key,value\n1,,10\n2,7,20\n");
+    let options = CsvOptions {
+      columns: Some(vec!["value".parse().unwrap(), "key".parse().unwrap()]),
+      ..CsvOptions::default()
+    };
+    let table = read_csv_table(&path, &options).unwrap();
+    assert_eq!(table.schema.column_names(), vec!["value", "key"]);
+    let mut expected = Record::new(&[10, 0]);
+    expected.set_null(1);
+    assert_eq!(table.records, vec![expected, Record::new(&[20, 7])]);
+    let options = CsvOptions { columns: Some(vec![CsvColumn::Name("nope".to_owned())]), ..CsvOptions::default() };
+    assert!(matches!(read_csv_table(&path, &options), Err(CsvError::UnknownColumn { .. })));
+
+    // A header and no rows is an empty table
+    let path = csv_file(&dir, "header.csv", "a,b\n");
+    let table = SimpleTable::try_new(&path).unwrap();
+    assert_eq!(table.get_num_records(), 0);
+    assert_eq!(table.get_num_columns_per_record(), 2);
+  }
+
+  #[test]
+  fn test_typed_columns() {
+    let dir = TempDir::new("readtable_typed");
+    let path = csv_file(&dir, "typed.csv", "id,name,score\n-5000000000,ab,0.5\n,,\n3,c,-1e300\n");
+    let options = CsvOptions {
+      types: Some(vec![ColumnType::Int64, ColumnType::Utf8, ColumnType::Float64]),
+      ..CsvOptions::default()
+    };
+    let expected = vec![
+      vec![Value::Int64(-5_000_000_000), Value::Utf8("ab".into()), Value::Float64(0.5)],
+      vec![Value::Null, Value::Null, Value::Null],
+      vec![Value::Int64(3), Value::Utf8("c".into()), Value::Float64(-1e300)],
+    ];
+    assert_eq!(fetch_records(&path, &options).unwrap(), expected);
+    let table = read_csv_table(&path, &options).unwrap();
+    assert_eq!(table.schema.num_slots(), 5);
+    assert!(table.records[1].get_column_values().iter().all(|v| *v == 0));
+
+    // Bad values and a wrong number of types
+    let bad = csv_file(&dir, "typed_bad.csv", "id\n1.5\n");
+    let options_i64 = CsvOptions { types: Some(vec![ColumnType::Int64]), ..CsvOptions::default() };
+    assert!(matches!(read_csv_table(&bad, &options_i64), Err(CsvError::Parse { line: 2, .. })));
+    assert!(matches!(read_csv_table(&path, &options_i64), Err(CsvError::TypeCount { expected: 3, found: 1, .. })));
+
+    // Wide columns sort like their values
+    let mut sorted = table.records.clone();
+    let slots: Vec<usize> = table.schema.slots(2).collect();
+    sorted.sort_by(|a, b| KeyRef::new(a, &slots).cmp(&KeyRef::new(b, &slots)));
+    let scores: Vec<Value> = sorted.iter().map(|r| r.get_value(&table.schema, 2)).collect();
+    assert_eq!(scores, vec![Value::Float64(-1e300), Value::Float64(0.5), Value::Null]);
+
+    // Types survive Arrow and Parquet, but strings cannot go in binary tables
+    for extension in ["arrow", "parquet", "csv"] {
+      let out = dir.file(&format!("typed_out.{}", extension));
+      write_table(&out, &table.schema, &table.records).unwrap();
+      if extension == "csv" {
+        assert_eq!(fetch_records(&out, &options).unwrap(), expected);
+      } else {
+        assert_eq!(read_table(&out).unwrap(), (table.schema.clone(), table.records.clone()));
+      }
+    }
+    let bin = dir.file("typed_out.bin");
+    assert!(write_table(&bin, &table.schema, &table.records).is_err());
+    let numbers = Schema::new(vec![table.schema.columns()[0].clone(), table.schema.columns()[2].clone()]);
+    let records: Vec<Record> = expected.iter()
+      .map(|row| Record::from_values(&numbers, &[row[0].clone(), row[2].clone()]))
+      .collect();
+    for extension in ["bin", "rec"] {
+      let out = dir.file(&format!("typed_out.{}", extension));
+      write_table(&out, &numbers, &records).unwrap();
+      let (schema, read) = read_table(&out).unwrap();
+      assert_eq!(schema, numbers);
+      assert_eq!(read[0].get_value(&schema, 0), Value::Int64(-5_000_000_000));
+      assert_eq!(read[2].get_value(&schema, 1), Value::Float64(-1e300));
+    }
+  }
+}
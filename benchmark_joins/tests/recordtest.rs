@@ -1,6 +1,7 @@
 extern crate joinlib;
 use joinlib::record::Record;
 use joinlib::record::M;
+use joinlib::rows::Rows;
 
 use std::panic;
 
@@ -19,8 +20,8 @@ mod tests {
 	}
 
 	fn check_columns_in_order(r: &Record, columns: &[i32]) {
-		for i in 0..columns.len() {
-			check_column(r, i, &columns[i]);
+		for (i, c) in columns.iter().enumerate() {
+			check_column(r, i, c);
 		}
 	}
 	
@@ -104,4 +105,59 @@ mod tests {
 		let r = Record::new(&columns);
 		check_record(&r, &columns);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn test_null_record() {
+		let r = Record::nulls(3);
+		check_size(&r, 3);
+		for i in 0..3 {
+			assert!(r.is_null(i));
+			assert_eq!(r.get_nullable_column(i), None);
+		}
+		assert_ne!(r, Record::new(&[0, 0, 0]));
+	}
+
+	#[test]
+	fn test_merge_nulls() {
+		let mut left = Record::new(&[1, 2]);
+		left.set_null(1);
+		let r = Record::merge(&left, &Record::nulls(2));
+		check_size(&r, 4);
+		assert!(!r.is_null(0));
+		assert!(r.is_null(1));
+		assert!(r.is_null(2));
+		assert!(r.is_null(3));
+
+		// Setting a value clears its NULL
+		let mut r = r;
+		r.set_column(2, 7);
+		assert_eq!(r.get_nullable_column(2), Some(&7));
+	}
+
+	#[test]
+	fn test_wide_rows() {
+		// Rows are not capped at M columns
+		let width = 3 * M + 5;
+		let values: Vec<i32> = (0..width as i32).collect();
+		let mut rows = Rows::new(width);
+		rows.push(&values);
+		rows.push_nulls();
+		assert_eq!(rows.len(), 2);
+		assert_eq!(rows.get(0).get_column_values(), values.as_slice());
+		assert!((0..width).all(|i| rows.get(1).is_null(i)));
+		rows.set_column(1, width - 1, 9);
+		assert_eq!(rows.get(1).get_nullable_column(width - 1), Some(&9));
+
+		// Merged rows keep the NULLs of both sides
+		let mut left = Record::new(&[1, 2]);
+		left.set_null(1);
+		let left = Rows::from_records(2, &[left]);
+		let right = Rows::from_records(2, &[Record::new(&[3, 4])]);
+		let mut merged = Rows::new(4);
+		merged.push_merged(Some(left.get(0)), 2, Some(right.get(0)));
+		merged.push_merged(None, 2, Some(right.get(0)));
+		let records = merged.to_records();
+		assert_eq!(records[0], Record::merge(&left.get(0).to_record(), &right.get(0).to_record()));
+		assert_eq!(records[1], Record::merge(&Record::nulls(2), &Record::new(&[3, 4])));
+	}
+}